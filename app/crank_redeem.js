@@ -0,0 +1,160 @@
+#!/usr/bin/env node
+// app/crank_redeem.js — Permissionless redemption crank
+// Scans every position with shares, groups them by the round their next redeem settles against,
+// and redeems them in batches through crank_redeem. Any wallet can run this; it earns a small
+// bounty per paid position. Usage: node app/crank_redeem.js [round_id]
+
+const fs = require("fs");
+const crypto = require("crypto");
+const {
+  Connection, PublicKey, Keypair, SystemProgram,
+  Transaction, TransactionInstruction, sendAndConfirmTransaction,
+  ComputeBudgetProgram,
+} = require("@solana/web3.js");
+
+/* ---------------- CONFIG ---------------- */
+const RPC = process.env.ANCHOR_PROVIDER_URL || "http://127.0.0.1:8899";
+const WALLET = process.env.CRANK_WALLET || process.env.ANCHOR_WALLET || `${process.env.HOME}/.config/solana/id.json`;
+const BATCH_SIZE = parseInt(process.env.CRANK_BATCH_SIZE || "8"); // on-chain max is 16 pairs
+
+const PID = new PublicKey("EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF");
+const AMM_SEED = Buffer.from("amm_btc_v6");
+const VAULT_SOL_SEED = Buffer.from("vault_sol");
+const USER_VAULT_SEED = Buffer.from("user_vault");
+const POS_SEED = Buffer.from("pos");
+const ROUND_SEED = Buffer.from("round");
+const INSURANCE_SEED = Buffer.from("insurance");
+
+/* ---------------- Helpers ---------------- */
+function discriminator(ixName) {
+  return crypto.createHash("sha256").update(`global:${ixName}`).digest().slice(0, 8);
+}
+
+function u64le(n) {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+}
+
+async function findClaimablePositions(conn, ammPda) {
+  // Positions are 957 bytes, or 901 before migrate_position_v2_to_v3 (see settlement_bot.js findAllPositions)
+  const accounts = [];
+  for (const dataSize of [8 + 893 + 56, 8 + 893]) {
+    accounts.push(...(await conn.getProgramAccounts(PID, { filters: [{ dataSize }] })));
+  }
+
+  const positions = [];
+  for (const { pubkey, account } of accounts) {
+    try {
+      const data = account.data;
+      let offset = 8;
+      const owner = new PublicKey(data.slice(offset, offset + 32)); offset += 32;
+      const yesShares = data.readBigInt64LE(offset); offset += 8;
+      const noShares = data.readBigInt64LE(offset); offset += 8;
+      offset += 32 + 8 + 1; // master_wallet + vault_balance + vault_bump
+      const nonceCount = data.readUInt32LE(offset); offset += 4 + 8 * nonceCount;
+      if (offset + 32 > data.length) {
+        // Full legacy nonce list: can't be read on-chain until grown (app/migrate-positions.js)
+        console.log(`  skip ${pubkey.toString()}: needs migrate_position_v2_to_v3`);
+        continue;
+      }
+      const roundId = data.readBigUInt64LE(offset); offset += 8;
+      const carryRoundId = data.readBigUInt64LE(offset); offset += 8;
+      const carryYes = data.readBigInt64LE(offset); offset += 8;
+      const carryNo = data.readBigInt64LE(offset);
+      const hasCarry = carryYes !== 0n || carryNo !== 0n;
+
+      const [expectedPda] = PublicKey.findProgramAddressSync(
+        [POS_SEED, ammPda.toBuffer(), owner.toBuffer()],
+        PID
+      );
+      if (!expectedPda.equals(pubkey)) continue;
+      if (!hasCarry && yesShares === 0n && noShares === 0n) continue;
+
+      const [userVault] = PublicKey.findProgramAddressSync([USER_VAULT_SEED, pubkey.toBuffer()], PID);
+      positions.push({ pubkey, userVault, claimRoundId: hasCarry ? carryRoundId : roundId });
+    } catch (err) {
+      // Skip invalid accounts
+    }
+  }
+  return positions;
+}
+
+async function crankBatch(conn, kp, ammPda, vaultPda, insurancePda, roundId, batch) {
+  const [roundResultPda] = PublicKey.findProgramAddressSync(
+    [ROUND_SEED, ammPda.toBuffer(), u64le(roundId)],
+    PID
+  );
+
+  const keys = [
+    { pubkey: ammPda, isSigner: false, isWritable: true },
+    { pubkey: kp.publicKey, isSigner: true, isWritable: true },  // cranker (bounty recipient)
+    { pubkey: vaultPda, isSigner: false, isWritable: true },
+    { pubkey: roundResultPda, isSigner: false, isWritable: true },
+    { pubkey: insurancePda, isSigner: false, isWritable: true },
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+  ];
+  // remaining_accounts: (pos, user_vault) pairs
+  for (const p of batch) {
+    keys.push({ pubkey: p.pubkey, isSigner: false, isWritable: true });
+    keys.push({ pubkey: p.userVault, isSigner: false, isWritable: true });
+  }
+
+  const ix = new TransactionInstruction({
+    programId: PID,
+    keys,
+    data: Buffer.concat([discriminator("crank_redeem"), u64le(roundId)]),
+  });
+  const budgetIx = ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 });
+  const tx = new Transaction().add(budgetIx, ix);
+  return sendAndConfirmTransaction(conn, tx, [kp], { commitment: "confirmed" });
+}
+
+/* ---------------- Main ---------------- */
+async function main() {
+  const conn = new Connection(RPC, "confirmed");
+  const kp = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(WALLET, "utf8"))));
+  const onlyRound = process.argv[2] !== undefined ? BigInt(process.argv[2]) : null;
+
+  const [ammPda] = PublicKey.findProgramAddressSync([AMM_SEED], PID);
+  const [vaultPda] = PublicKey.findProgramAddressSync([VAULT_SOL_SEED, ammPda.toBuffer()], PID);
+  const [insurancePda] = PublicKey.findProgramAddressSync([INSURANCE_SEED, ammPda.toBuffer()], PID);
+
+  console.log(`Cranker: ${kp.publicKey.toString()}`);
+  const positions = await findClaimablePositions(conn, ammPda);
+
+  // Group by claim round; each crank_redeem settles against a single RoundResult
+  const byRound = new Map();
+  for (const p of positions) {
+    if (onlyRound !== null && p.claimRoundId !== onlyRound) continue;
+    const key = p.claimRoundId.toString();
+    if (!byRound.has(key)) byRound.set(key, []);
+    byRound.get(key).push(p);
+  }
+
+  if (byRound.size === 0) {
+    console.log("Nothing to redeem");
+    return;
+  }
+
+  const before = await conn.getBalance(kp.publicKey);
+  for (const [roundId, list] of byRound) {
+    console.log(`Round ${roundId}: ${list.length} position(s)`);
+    for (let i = 0; i < list.length; i += BATCH_SIZE) {
+      const batch = list.slice(i, i + BATCH_SIZE);
+      try {
+        const sig = await crankBatch(conn, kp, ammPda, vaultPda, insurancePda, roundId, batch);
+        console.log(`  ✓ ${batch.length} redeemed: ${sig}`);
+      } catch (err) {
+        console.error(`  ✗ batch ${i / BATCH_SIZE} failed: ${err.message}`);
+      }
+    }
+  }
+  const after = await conn.getBalance(kp.publicKey);
+  console.log(`Net balance change (bounty - tx fees): ${after - before} lamports`);
+}
+
+main().catch((err) => {
+  console.error(err);
+  process.exit(1);
+});
@@ -63,12 +63,19 @@ const KEEPER_WALLET = process.env.KEEPER_WALLET || process.env.ANCHOR_WALLET ||
 const ORDER_BOOK_API = process.env.ORDER_BOOK_API || config.orderbookApi || 'http://localhost:3436';
 const CHECK_INTERVAL = parseInt(process.env.KEEPER_CHECK_INTERVAL || String(config.pollIntervalMs || 2000)); // ms
 const MIN_PROFIT_LAMPORTS = parseInt(process.env.KEEPER_MIN_PROFIT || '100000'); // 0.0001 SOL
+// Oracle feed the market reads (fills are rejected during the trading lockout)
+const ORACLE_STATE = new web3_js_1.PublicKey(process.env.ORACLE_STATE || config.oracleState || '4KYeNyv1B9YjjQkfJk2C6Uqo71vKzFZriRe5NXg6GyCq');
 // Program IDs
 const PID = new web3_js_1.PublicKey('EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF');
 const AMM_SEED = Buffer.from('amm_btc_v6');
 const POS_SEED = Buffer.from('pos');
 const VAULT_SOL_SEED = Buffer.from('vault_sol');
 const USER_VAULT_SEED = Buffer.from('user_vault');
+const INSURANCE_SEED = Buffer.from('insurance');
+const ORDER_FILL_SEED = Buffer.from('order_fill');
+const ORDER_GROUP_SEED = Buffer.from('order_group');
+// Position of the Ed25519 verify instruction in the transaction (after the compute budget ix)
+const ED25519_IX_INDEX = 1;
 /* ==================== HELPERS ==================== */
 function loadKeeper(path) {
     const rawKey = JSON.parse(fs.readFileSync(path, 'utf8'));
@@ -90,6 +97,24 @@ function getUserVaultPda(position) {
     const [pda] = web3_js_1.PublicKey.findProgramAddressSync([USER_VAULT_SEED, position.toBuffer()], PID);
     return pda;
 }
+// Bytes a user signs for an order: a Solana off-chain message (header v0) with this program as
+// the application domain, wrapping the ASCII body "limit_order v<version> " || hex(borsh(order)).
+// Must match limit_order_message in programs/cpi_oracle.
+const LIMIT_ORDER_VERSION = 5;
+function limitOrderMessage(orderBytes, user) {
+    const body = Buffer.from(`limit_order v${LIMIT_ORDER_VERSION} ${orderBytes.toString('hex')}`, 'ascii');
+    const bodyLen = Buffer.alloc(2);
+    bodyLen.writeUInt16LE(body.length);
+    return Buffer.concat([
+        Buffer.from('\xffsolana offchain', 'latin1'), // signing domain
+        Buffer.from([0]), // header version
+        PID.toBuffer(), // application domain
+        Buffer.from([0, 1]), // format: restricted ASCII, 1 signer
+        user.toBuffer(),
+        bodyLen,
+        body,
+    ]);
+}
 function serializeOrder(order) {
     // Serialize LimitOrder struct to match Rust Borsh encoding
     // Order must match Rust struct field order exactly
@@ -138,6 +163,26 @@ function serializeOrder(order) {
     const minFillBuf = Buffer.alloc(2);
     minFillBuf.writeUInt16LE(order.minFillBps);
     buffers.push(minFillBuf);
+    // trigger_kind: u8 (1 byte)
+    buffers.push(Buffer.from([order.triggerKind]));
+    // trigger_price_e6: i64 (8 bytes, little-endian)
+    const triggerPriceBuf = Buffer.alloc(8);
+    triggerPriceBuf.writeBigInt64LE(BigInt(order.triggerPriceE6.toString()));
+    buffers.push(triggerPriceBuf);
+    // group_id: u64 (8 bytes, little-endian)
+    const groupIdBuf = Buffer.alloc(8);
+    groupIdBuf.writeBigUInt64LE(BigInt(order.groupId.toString()));
+    buffers.push(groupIdBuf);
+    // reduce_only: bool (1 byte)
+    buffers.push(Buffer.from([order.reduceOnly ? 1 : 0]));
+    // slice_count: u16 (2 bytes, little-endian)
+    const sliceCountBuf = Buffer.alloc(2);
+    sliceCountBuf.writeUInt16LE(order.sliceCount);
+    buffers.push(sliceCountBuf);
+    // slice_interval_secs: i64 (8 bytes, little-endian)
+    const sliceIntervalBuf = Buffer.alloc(8);
+    sliceIntervalBuf.writeBigInt64LE(BigInt(order.sliceIntervalSecs.toString()));
+    buffers.push(sliceIntervalBuf);
     return Buffer.concat(buffers);
 }
 /* ==================== PRICE CALCULATION ==================== */
@@ -216,6 +261,44 @@ async function checkIfExecutable(connection, amm, order) {
                 return false;
             }
         }
+        // Stop-loss / take-profit exits need shares held on their side; reduce-only orders need net
+        // exposure left to close
+        const isExit = order.trigger_kind === 3 || order.trigger_kind === 4;
+        if (order.reduce_only || isExit) {
+            const positionInfo = await connection.getAccountInfo(getPositionPda(getAmmPda(), new web3_js_1.PublicKey(order.user)));
+            // Position layout: discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + ...
+            const yes = positionInfo ? Number(positionInfo.data.readBigInt64LE(40)) : 0;
+            const no = positionInfo ? Number(positionInfo.data.readBigInt64LE(48)) : 0;
+            const [own, other] = order.side === 1 ? [yes, no] : [no, yes];
+            const reducible = order.action === 2 ? own - other : other - own;
+            if ((isExit && own <= 0) || (order.reduce_only && reducible <= 0)) {
+                console.log(`   ⚠️  Reduce-only: nothing to reduce (YES ${yes}, NO ${no})`);
+                return false;
+            }
+        }
+        // TWAP: a new slice waits slice_interval_secs after the fill that closed the previous one, a partly
+        // filled one doesn't. OrderFill layout: discriminator(8) + position(32) + nonce(8) + expiry_ts(8) +
+        // payer(32) + cancelled(1) + bump(1) + filled_shares(8) + cost(8) + fills(4) + filled_by(8) +
+        // last_fill_ts(8) + slice_filled(8) + slices_done(4): filled_shares at 90, last_fill_ts at 118,
+        // slice_filled at 126 and slices_done at 134
+        if ((order.slice_count ?? 0) > 1) {
+            const positionPda = getPositionPda(getAmmPda(), new web3_js_1.PublicKey(order.user));
+            const [orderFillPda] = web3_js_1.PublicKey.findProgramAddressSync([order.group_id ? ORDER_GROUP_SEED : ORDER_FILL_SEED, positionPda.toBuffer(), new bn_js_1.default(order.group_id || order.nonce).toArrayLike(Buffer, 'le', 8)], PID);
+            const fillInfo = await connection.getAccountInfo(orderFillPda);
+            if (fillInfo) {
+                const filled = Number(fillInfo.data.readBigInt64LE(90));
+                const sliceFilled = Number(fillInfo.data.readBigInt64LE(126));
+                const slicesDone = fillInfo.data.readUInt32LE(134);
+                const nextSliceTs = Number(fillInfo.data.readBigInt64LE(118)) + (order.slice_interval_secs ?? 0);
+                if (filled >= order.shares_e6) {
+                    return false;
+                }
+                if (sliceFilled === 0 && slicesDone > 0 && now < nextSliceTs) {
+                    console.log(`   ⏳ TWAP: slice ${slicesDone + 1}/${order.slice_count} due in ${nextSliceTs - now}s`);
+                    return false;
+                }
+            }
+        }
         // Calculate current price for ONE share to check executability
         // Order shares are in e6 (1_000_000 = 1 share) but LMSR calc needs "share units" (10M = 1 share)
         const currentPrice = calculateCurrentPrice(amm, order.action, order.side, 1);
@@ -254,7 +337,11 @@ async function checkIfExecutable(connection, amm, order) {
                 // Cost formula: (shares_e6 / 1e6) * (execPrice / 1e6) = cost in XNT
                 // Then multiply by 1e6 to get cost_e6
                 // Simplified: shares_e6 * execPrice / 1e6
-                const estimatedCost_e6 = Math.ceil(order.shares_e6 * execPrice / 1e6);
+                // (TWAP orders only pay for one slice per fill)
+                const fillShares_e6 = (order.slice_count ?? 0) > 1
+                    ? Math.ceil(order.shares_e6 / order.slice_count)
+                    : order.shares_e6;
+                const estimatedCost_e6 = Math.ceil(fillShares_e6 * execPrice / 1e6);
                 // Add protocol fee buffer (use AMM fee_bps)
                 const feeBps = amm.feeBps;
                 const protocolFee_e6 = Math.ceil(estimatedCost_e6 * feeBps / 10000);
@@ -271,6 +358,14 @@ async function checkIfExecutable(connection, amm, order) {
                 // Continue anyway - let on-chain check handle it
             }
         }
+        // Stop-loss / take-profit: wait until the side's price crosses the trigger
+        if (order.trigger_kind === 3 || order.trigger_kind === 4) {
+            const trigger = order.trigger_price_e6 ?? 0;
+            const hit = order.trigger_kind === 3 ? currentPrice <= trigger : currentPrice >= trigger;
+            console.log(`   ${order.trigger_kind === 3 ? 'Stop-loss' : 'Take-profit'} trigger: ${currentPrice} ${order.trigger_kind === 3 ? '<=' : '>='} ${trigger} = ${hit}`);
+            if (!hit)
+                return false;
+        }
         // Check price condition with slippage buffer
         if (order.action === 1) { // BUY
             // For BUY: price must be below limit, accounting for slippage making it worse
@@ -331,6 +426,11 @@ async function executeOrder(connection, keeper, order, signature, orderId) {
         const userPubkey = new web3_js_1.PublicKey(order.user);
         const positionPda = getPositionPda(ammPda, userPubkey);
         const vaultSolPda = getVaultPda(ammPda);
+        const [insurancePda] = web3_js_1.PublicKey.findProgramAddressSync([INSURANCE_SEED, ammPda.toBuffer()], PID);
+        // Replay guard for this order's nonce, or its OCO group (created by the fill, paid by the keeper)
+        const [orderFillPda] = web3_js_1.PublicKey.findProgramAddressSync([order.group_id ? ORDER_GROUP_SEED : ORDER_FILL_SEED, positionPda.toBuffer(), new bn_js_1.default(order.group_id || order.nonce).toArrayLike(Buffer, 'le', 8)], PID);
+        // The order's own nonce state: an OCO order cancelled by nonce is marked there
+        const [nonceFillPda] = web3_js_1.PublicKey.findProgramAddressSync([ORDER_FILL_SEED, positionPda.toBuffer(), new bn_js_1.default(order.nonce).toArrayLike(Buffer, 'le', 8)], PID);
         const userVaultPda = getUserVaultPda(positionPda);
         console.log(`\n🔧 Building transaction for order ${orderId}...`);
         console.log(`   User: ${order.user.slice(0, 5)}...`);
@@ -362,6 +462,12 @@ async function executeOrder(connection, keeper, order, signature, orderId) {
             nonce: new bn_js_1.default(order.nonce),
             keeperFeeBps: order.keeper_fee_bps,
             minFillBps: order.min_fill_bps,
+            triggerKind: order.trigger_kind ?? 0,
+            triggerPriceE6: new bn_js_1.default(order.trigger_price_e6 ?? 0),
+            groupId: new bn_js_1.default(order.group_id ?? 0),
+            reduceOnly: order.reduce_only ?? false,
+            sliceCount: order.slice_count ?? 0,
+            sliceIntervalSecs: new bn_js_1.default(order.slice_interval_secs ?? 0),
         };
         // Convert signature from hex to Uint8Array (64 bytes)
         const signatureBytes = Buffer.from(signature, 'hex');
@@ -370,7 +476,7 @@ async function executeOrder(connection, keeper, order, signature, orderId) {
         }
         const signatureArray = Array.from(signatureBytes);
         // Serialize the order using Borsh (must match Rust serialization)
-        const messageBytes = serializeOrder(limitOrderStruct);
+        const messageBytes = limitOrderMessage(serializeOrder(limitOrderStruct), userPubkey);
         // Manually create Ed25519 verification instruction without account metadata
         // This avoids the "writable privilege escalation" error
         const ED25519_PROGRAM_ID = new web3_js_1.PublicKey('Ed25519SigVerify111111111111111111111111111');
@@ -425,16 +531,20 @@ async function executeOrder(connection, keeper, order, signature, orderId) {
         console.log(`     - Instructions Sysvar: ${web3_js_1.SYSVAR_INSTRUCTIONS_PUBKEY.toString()}`);
         // Execute the limit order instruction with Ed25519 verification
         const tx = await program.methods
-            .executeLimitOrder(limitOrderStruct, signatureArray)
+            .executeLimitOrder(limitOrderStruct, signatureArray, ED25519_IX_INDEX)
             .accountsStrict({
             amm: ammPda,
             position: positionPda,
+            orderFill: orderFillPda,
+            nonceFill: nonceFillPda,
             userVault: userVaultPda,
             feeDest: feeDest,
             vaultSol: vaultSolPda,
+            oracleState: ORACLE_STATE,
             user: userPubkey,
             keeper: keeper.publicKey,
             instructions: web3_js_1.SYSVAR_INSTRUCTIONS_PUBKEY,
+            insurance: insurancePda,
             systemProgram: web3_js_1.SystemProgram.programId,
         })
             .preInstructions([
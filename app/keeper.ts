@@ -17,6 +17,7 @@ interface KeeperConfig {
   keeperWallet?: string;
   pollIntervalMs?: number;
   keeperFeeBps?: number;
+  oracleState?: string;
 }
 
 function loadConfig(): KeeperConfig {
@@ -38,6 +39,8 @@ const KEEPER_WALLET = process.env.KEEPER_WALLET || process.env.ANCHOR_WALLET ||
 const ORDER_BOOK_API = process.env.ORDER_BOOK_API || config.orderbookApi || 'http://localhost:3436';
 const CHECK_INTERVAL = parseInt(process.env.KEEPER_CHECK_INTERVAL || String(config.pollIntervalMs || 2000)); // ms
 const MIN_PROFIT_LAMPORTS = parseInt(process.env.KEEPER_MIN_PROFIT || '100000'); // 0.0001 SOL
+// Oracle feed the market reads (fills are rejected during the trading lockout)
+const ORACLE_STATE = new PublicKey(process.env.ORACLE_STATE || config.oracleState || '4KYeNyv1B9YjjQkfJk2C6Uqo71vKzFZriRe5NXg6GyCq');
 
 // Program IDs
 const PID = new PublicKey('EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF');
@@ -45,6 +48,11 @@ const AMM_SEED = Buffer.from('amm_btc_v6');
 const POS_SEED = Buffer.from('pos');
 const VAULT_SOL_SEED = Buffer.from('vault_sol');
 const USER_VAULT_SEED = Buffer.from('user_vault');
+const INSURANCE_SEED = Buffer.from('insurance');
+const ORDER_FILL_SEED = Buffer.from('order_fill');
+const ORDER_GROUP_SEED = Buffer.from('order_group');
+// Position of the Ed25519 verify instruction in the transaction (after the compute budget ix)
+const ED25519_IX_INDEX = 1;
 
 /* ==================== TYPES ==================== */
 interface LimitOrder {
@@ -60,6 +68,12 @@ interface LimitOrder {
   nonce: number;
   keeper_fee_bps: number;
   min_fill_bps: number;
+  trigger_kind?: number;     // 0=none, 1=oracle at/above, 2=oracle at/below, 3=stop-loss, 4=take-profit
+  trigger_price_e6?: number; // underlying price for oracle triggers, share price for 3/4
+  group_id?: number;         // OCO group (0 = none)
+  reduce_only?: boolean;     // only fill what shrinks net exposure
+  slice_count?: number;      // TWAP: number of slices (0 or 1 = not sliced)
+  slice_interval_secs?: number; // TWAP: minimum seconds between slices
 }
 
 interface OrderData {
@@ -132,6 +146,25 @@ function getUserVaultPda(position: PublicKey): PublicKey {
   return pda;
 }
 
+// Bytes a user signs for an order: a Solana off-chain message (header v0) with this program as
+// the application domain, wrapping the ASCII body "limit_order v<version> " || hex(borsh(order)).
+// Must match limit_order_message in programs/cpi_oracle.
+const LIMIT_ORDER_VERSION = 5;
+function limitOrderMessage(orderBytes: Buffer, user: PublicKey): Buffer {
+  const body = Buffer.from(`limit_order v${LIMIT_ORDER_VERSION} ${orderBytes.toString('hex')}`, 'ascii');
+  const bodyLen = Buffer.alloc(2);
+  bodyLen.writeUInt16LE(body.length);
+  return Buffer.concat([
+    Buffer.from('\xffsolana offchain', 'latin1'), // signing domain
+    Buffer.from([0]),                             // header version
+    PID.toBuffer(),                               // application domain
+    Buffer.from([0, 1]),                          // format: restricted ASCII, 1 signer
+    user.toBuffer(),
+    bodyLen,
+    body,
+  ]);
+}
+
 function serializeOrder(order: any): Buffer {
   // Serialize LimitOrder struct to match Rust Borsh encoding
   // Order must match Rust struct field order exactly
@@ -193,6 +226,32 @@ function serializeOrder(order: any): Buffer {
   minFillBuf.writeUInt16LE(order.minFillBps);
   buffers.push(minFillBuf);
 
+  // trigger_kind: u8 (1 byte)
+  buffers.push(Buffer.from([order.triggerKind]));
+
+  // trigger_price_e6: i64 (8 bytes, little-endian)
+  const triggerPriceBuf = Buffer.alloc(8);
+  triggerPriceBuf.writeBigInt64LE(BigInt(order.triggerPriceE6.toString()));
+  buffers.push(triggerPriceBuf);
+
+  // group_id: u64 (8 bytes, little-endian)
+  const groupIdBuf = Buffer.alloc(8);
+  groupIdBuf.writeBigUInt64LE(BigInt(order.groupId.toString()));
+  buffers.push(groupIdBuf);
+
+  // reduce_only: bool (1 byte)
+  buffers.push(Buffer.from([order.reduceOnly ? 1 : 0]));
+
+  // slice_count: u16 (2 bytes, little-endian)
+  const sliceCountBuf = Buffer.alloc(2);
+  sliceCountBuf.writeUInt16LE(order.sliceCount);
+  buffers.push(sliceCountBuf);
+
+  // slice_interval_secs: i64 (8 bytes, little-endian)
+  const sliceIntervalBuf = Buffer.alloc(8);
+  sliceIntervalBuf.writeBigInt64LE(BigInt(order.sliceIntervalSecs.toString()));
+  buffers.push(sliceIntervalBuf);
+
   return Buffer.concat(buffers);
 }
 
@@ -284,6 +343,49 @@ async function checkIfExecutable(
       }
     }
 
+    // Stop-loss / take-profit exits need shares held on their side; reduce-only orders need net
+    // exposure left to close
+    const isExit = order.trigger_kind === 3 || order.trigger_kind === 4;
+    if (order.reduce_only || isExit) {
+      const positionInfo = await connection.getAccountInfo(getPositionPda(getAmmPda(), new PublicKey(order.user)));
+      // Position layout: discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + ...
+      const yes = positionInfo ? Number(positionInfo.data.readBigInt64LE(40)) : 0;
+      const no = positionInfo ? Number(positionInfo.data.readBigInt64LE(48)) : 0;
+      const [own, other] = order.side === 1 ? [yes, no] : [no, yes];
+      const reducible = order.action === 2 ? own - other : other - own;
+      if ((isExit && own <= 0) || (order.reduce_only && reducible <= 0)) {
+        console.log(`   ⚠️  Reduce-only: nothing to reduce (YES ${yes}, NO ${no})`);
+        return false;
+      }
+    }
+
+    // TWAP: a new slice waits slice_interval_secs after the fill that closed the previous one, a partly
+    // filled one doesn't. OrderFill layout: discriminator(8) + position(32) + nonce(8) + expiry_ts(8) +
+    // payer(32) + cancelled(1) + bump(1) + filled_shares(8) + cost(8) + fills(4) + filled_by(8) +
+    // last_fill_ts(8) + slice_filled(8) + slices_done(4): filled_shares at 90, last_fill_ts at 118,
+    // slice_filled at 126 and slices_done at 134
+    if ((order.slice_count ?? 0) > 1) {
+      const positionPda = getPositionPda(getAmmPda(), new PublicKey(order.user));
+      const [orderFillPda] = PublicKey.findProgramAddressSync(
+        [order.group_id ? ORDER_GROUP_SEED : ORDER_FILL_SEED, positionPda.toBuffer(), new BN(order.group_id || order.nonce).toArrayLike(Buffer, 'le', 8)],
+        PID
+      );
+      const fillInfo = await connection.getAccountInfo(orderFillPda);
+      if (fillInfo) {
+        const filled = Number(fillInfo.data.readBigInt64LE(90));
+        const sliceFilled = Number(fillInfo.data.readBigInt64LE(126));
+        const slicesDone = fillInfo.data.readUInt32LE(134);
+        const nextSliceTs = Number(fillInfo.data.readBigInt64LE(118)) + (order.slice_interval_secs ?? 0);
+        if (filled >= order.shares_e6) {
+          return false;
+        }
+        if (sliceFilled === 0 && slicesDone > 0 && now < nextSliceTs) {
+          console.log(`   ⏳ TWAP: slice ${slicesDone + 1}/${order.slice_count} due in ${nextSliceTs - now}s`);
+          return false;
+        }
+      }
+    }
+
     // Calculate current price for ONE share to check executability
     // Order shares are in e6 (1_000_000 = 1 share) but LMSR calc needs "share units" (10M = 1 share)
     const currentPrice = calculateCurrentPrice(amm, order.action, order.side, 1);
@@ -334,7 +436,11 @@ async function checkIfExecutable(
         // Cost formula: (shares_e6 / 1e6) * (execPrice / 1e6) = cost in XNT
         // Then multiply by 1e6 to get cost_e6
         // Simplified: shares_e6 * execPrice / 1e6
-        const estimatedCost_e6 = Math.ceil(order.shares_e6 * execPrice / 1e6);
+        // (TWAP orders only pay for one slice per fill)
+        const fillShares_e6 = (order.slice_count ?? 0) > 1
+          ? Math.ceil(order.shares_e6 / order.slice_count!)
+          : order.shares_e6;
+        const estimatedCost_e6 = Math.ceil(fillShares_e6 * execPrice / 1e6);
 
         // Add protocol fee buffer (use AMM fee_bps)
         const feeBps = amm.feeBps;
@@ -353,6 +459,14 @@ async function checkIfExecutable(
       }
     }
 
+    // Stop-loss / take-profit: wait until the side's price crosses the trigger
+    if (order.trigger_kind === 3 || order.trigger_kind === 4) {
+      const trigger = order.trigger_price_e6 ?? 0;
+      const hit = order.trigger_kind === 3 ? currentPrice <= trigger : currentPrice >= trigger;
+      console.log(`   ${order.trigger_kind === 3 ? 'Stop-loss' : 'Take-profit'} trigger: ${currentPrice} ${order.trigger_kind === 3 ? '<=' : '>='} ${trigger} = ${hit}`);
+      if (!hit) return false;
+    }
+
     // Check price condition with slippage buffer
     if (order.action === 1) { // BUY
       // For BUY: price must be below limit, accounting for slippage making it worse
@@ -426,6 +540,17 @@ async function executeOrder(
     const userPubkey = new PublicKey(order.user);
     const positionPda = getPositionPda(ammPda, userPubkey);
     const vaultSolPda = getVaultPda(ammPda);
+    const [insurancePda] = PublicKey.findProgramAddressSync([INSURANCE_SEED, ammPda.toBuffer()], PID);
+    // Replay guard for this order's nonce, or its OCO group (created by the fill, paid by the keeper)
+    const [orderFillPda] = PublicKey.findProgramAddressSync(
+      [order.group_id ? ORDER_GROUP_SEED : ORDER_FILL_SEED, positionPda.toBuffer(), new BN(order.group_id || order.nonce).toArrayLike(Buffer, 'le', 8)],
+      PID
+    );
+    // The order's own nonce state: an OCO order cancelled by nonce is marked there
+    const [nonceFillPda] = PublicKey.findProgramAddressSync(
+      [ORDER_FILL_SEED, positionPda.toBuffer(), new BN(order.nonce).toArrayLike(Buffer, 'le', 8)],
+      PID
+    );
     const userVaultPda = getUserVaultPda(positionPda);
 
     console.log(`\n🔧 Building transaction for order ${orderId}...`);
@@ -466,6 +591,12 @@ async function executeOrder(
       nonce: new BN(order.nonce),
       keeperFeeBps: order.keeper_fee_bps,
       minFillBps: order.min_fill_bps,
+      triggerKind: order.trigger_kind ?? 0,
+      triggerPriceE6: new BN(order.trigger_price_e6 ?? 0),
+      groupId: new BN(order.group_id ?? 0),
+      reduceOnly: order.reduce_only ?? false,
+      sliceCount: order.slice_count ?? 0,
+      sliceIntervalSecs: new BN(order.slice_interval_secs ?? 0),
     };
 
     // Convert signature from hex to Uint8Array (64 bytes)
@@ -476,7 +607,7 @@ async function executeOrder(
     const signatureArray = Array.from(signatureBytes);
 
     // Serialize the order using Borsh (must match Rust serialization)
-    const messageBytes = serializeOrder(limitOrderStruct);
+    const messageBytes = limitOrderMessage(serializeOrder(limitOrderStruct), userPubkey);
 
     // Manually create Ed25519 verification instruction without account metadata
     // This avoids the "writable privilege escalation" error
@@ -540,16 +671,20 @@ async function executeOrder(
 
     // Execute the limit order instruction with Ed25519 verification
     const tx = await program.methods
-      .executeLimitOrder(limitOrderStruct, signatureArray)
+      .executeLimitOrder(limitOrderStruct, signatureArray, ED25519_IX_INDEX)
       .accountsStrict({
         amm: ammPda,
         position: positionPda,
+        orderFill: orderFillPda,
+        nonceFill: nonceFillPda,
         userVault: userVaultPda,
         feeDest: feeDest,
         vaultSol: vaultSolPda,
+        oracleState: ORACLE_STATE,
         user: userPubkey,
         keeper: keeper.publicKey,
         instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        insurance: insurancePda,
         systemProgram: SystemProgram.programId,
       })
       .preInstructions([
@@ -26,6 +26,11 @@ try {
   if (process.env.ORACLE_STATE) ORACLE_STATE = new PublicKey(process.env.ORACLE_STATE);
 } catch (_) {}
 
+// Expected oracle update_authority pinned with the feed (unset = not checked; push feeds need it)
+const ORACLE_AUTHORITY = process.env.ORACLE_AUTHORITY
+  ? new PublicKey(process.env.ORACLE_AUTHORITY)
+  : PublicKey.default;
+
 /* ---------------- Color helpers ---------------- */
 const WANT_COLOR = !process.env.NO_COLOR && process.stdout.isTTY;
 const C = WANT_COLOR ? {
@@ -175,7 +180,7 @@ async function cmdRestart(conn, kp, ammPda, vaultPda) {
       { pubkey: kp.publicKey, isSigner: false, isWritable: true }, // fee_dest
       { pubkey: vaultPda, isSigner: false, isWritable: true },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: PublicKey.findProgramAddressSync([Buffer.from("round_counter"), ammPda.toBuffer()], PID)[0], isSigner: false, isWritable: true },
     ],
     data: initData,
   });
@@ -191,14 +196,23 @@ async function cmdRestart(conn, kp, ammPda, vaultPda) {
 
   await new Promise((r) => setTimeout(r, 1500));
 
-  // Step 3: Take oracle snapshot
+  // Step 3: Pin the oracle (unpinned markets read no feed) and take the snapshot
   if (!ORACLE_STATE) {
-    logError("ORACLE_STATE environment variable not set - cannot take snapshot");
+    logError("ORACLE_STATE environment variable not set - cannot pin oracle or take snapshot");
     log("Market initialized but snapshot not taken");
     return;
   }
 
-  log("\nTaking oracle snapshot...");
+  log("\nPinning oracle and taking snapshot...");
+  const pinIx = new TransactionInstruction({
+    programId: PID,
+    keys: [
+      { pubkey: ammPda, isSigner: false, isWritable: true },
+      { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+      { pubkey: kp.publicKey, isSigner: true, isWritable: false },
+    ],
+    data: Buffer.concat([discriminator("pin_oracle"), ORACLE_AUTHORITY.toBuffer()]),
+  });
   const snapshotIx = new TransactionInstruction({
     programId: PID,
     keys: [
@@ -210,7 +224,7 @@ async function cmdRestart(conn, kp, ammPda, vaultPda) {
 
   try {
     const budgetIx = ComputeBudgetProgram.setComputeUnitLimit({ units: 200_000 });
-    const tx = new Transaction().add(budgetIx, snapshotIx);
+    const tx = new Transaction().add(budgetIx, pinIx, snapshotIx);
     const sig = await sendAndConfirmTransaction(conn, tx, [kp]);
     logSuccess(`Snapshot taken: ${sig}`);
   } catch (err) {
@@ -0,0 +1,78 @@
+#!/usr/bin/env node
+// app/migrate-amm.js - Grow the v6 AMM account to the current layout in place
+// Positions and vaults are keyed by the AMM address, so the account is resized rather than
+// re-created under a new seed. Safe to run twice: an AMM already at the current size is left as is.
+// A migrated AMM has no pinned oracle, and every oracle read fails until it does: with ORACLE_STATE
+// set (and ORACLE_AUTHORITY, required for push feeds) the feed is pinned right after, even mid-round.
+
+const anchor = require('@coral-xyz/anchor');
+const { Connection, PublicKey, Keypair, SystemProgram } = require('@solana/web3.js');
+const fs = require('fs');
+
+const RPC = process.env.ANCHOR_PROVIDER_URL || 'https://rpc.testnet.x1.xyz';
+const WALLET = process.env.ANCHOR_WALLET || `${process.env.HOME}/.config/solana/id.json`;
+const PID = new PublicKey('EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF');
+const AMM_SEED = Buffer.from('amm_btc_v6');
+const ORACLE_STATE = process.env.ORACLE_STATE ? new PublicKey(process.env.ORACLE_STATE) : null;
+const ORACLE_AUTHORITY = process.env.ORACLE_AUTHORITY
+  ? new PublicKey(process.env.ORACLE_AUTHORITY)
+  : PublicKey.default;
+
+async function main() {
+  const payer = Keypair.fromSecretKey(new Uint8Array(JSON.parse(fs.readFileSync(WALLET, 'utf8'))));
+  const connection = new Connection(RPC, 'confirmed');
+  const [ammPda] = PublicKey.findProgramAddressSync([AMM_SEED], PID);
+
+  const before = await connection.getAccountInfo(ammPda);
+  if (!before) {
+    console.log(`❌ AMM ${ammPda.toString()} does not exist. Nothing to migrate.`);
+    process.exit(0);
+  }
+  console.log(`AMM:   ${ammPda.toString()}`);
+  console.log(`Size:  ${before.data.length} bytes`);
+
+  const idl = JSON.parse(fs.readFileSync('target/idl/cpi_oracle.json', 'utf8'));
+  const provider = new anchor.AnchorProvider(connection, new anchor.Wallet(payer), { commitment: 'confirmed' });
+  const program = new anchor.Program(idl, provider);
+
+  try {
+    const tx = await program.methods
+      .migrateAmm()
+      .accountsStrict({
+        amm: ammPda,
+        payer: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    const after = await connection.getAccountInfo(ammPda);
+    console.log(`✅ Migrated: ${before.data.length} -> ${after.data.length} bytes`);
+    console.log(`   Transaction: ${tx}`);
+
+    const amm = await program.account.amm.fetch(ammPda);
+    if (!amm.oracleState.equals(PublicKey.default)) {
+      console.log(`Oracle already pinned: ${amm.oracleState.toString()}`);
+    } else if (!ORACLE_STATE) {
+      console.log('⚠️  No oracle pinned: set ORACLE_STATE (and ORACLE_AUTHORITY) and re-run, or trading stays locked');
+    } else {
+      const pinTx = await program.methods
+        .pinOracle(ORACLE_AUTHORITY)
+        .accountsStrict({
+          amm: ammPda,
+          oracleState: ORACLE_STATE,
+          admin: payer.publicKey,
+        })
+        .rpc();
+      console.log(`✅ Oracle pinned to ${ORACLE_STATE.toString()}`);
+      console.log(`   Transaction: ${pinTx}`);
+    }
+  } catch (err) {
+    console.error('❌ Migration failed:', err.message);
+    if (err.logs) err.logs.forEach(log => console.error('  ', log));
+    process.exit(1);
+  }
+}
+
+main().catch(err => {
+  console.error('Fatal error:', err);
+  process.exit(1);
+});
@@ -0,0 +1,79 @@
+#!/usr/bin/env node
+// app/migrate-positions.js - Grow 901-byte positions so their tail fields get their own bytes
+// A position whose legacy nonce list is (nearly) full can't be read by the program until it is
+// grown, which blocks its deposit/withdraw/trade/redeem. migrate_position_v2_to_v3 is
+// permissionless: any wallet can run this and pays the extra rent.
+// Usage: node app/migrate-positions.js [owner_pubkey]   (default: every 901-byte position)
+
+const fs = require('fs');
+const crypto = require('crypto');
+const {
+  Connection, PublicKey, Keypair, SystemProgram,
+  Transaction, TransactionInstruction, sendAndConfirmTransaction,
+} = require('@solana/web3.js');
+
+const RPC = process.env.ANCHOR_PROVIDER_URL || 'http://127.0.0.1:8899';
+const WALLET = process.env.ANCHOR_WALLET || `${process.env.HOME}/.config/solana/id.json`;
+const BATCH_SIZE = 8; // migrate instructions per transaction
+
+const PID = new PublicKey('EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF');
+const AMM_SEED = Buffer.from('amm_btc_v6');
+const POS_SEED = Buffer.from('pos');
+const LEGACY_POSITION_SIZE = 8 + 893;
+
+function discriminator(ixName) {
+  return crypto.createHash('sha256').update(`global:${ixName}`).digest().slice(0, 8);
+}
+
+function migrateIx(ammPda, owner, position, payer) {
+  return new TransactionInstruction({
+    programId: PID,
+    keys: [
+      { pubkey: ammPda, isSigner: false, isWritable: false },
+      { pubkey: owner, isSigner: false, isWritable: false },
+      { pubkey: position, isSigner: false, isWritable: true },
+      { pubkey: payer, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ],
+    data: discriminator('migrate_position_v2_to_v3'),
+  });
+}
+
+async function main() {
+  const conn = new Connection(RPC, 'confirmed');
+  const kp = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(WALLET, 'utf8'))));
+  const onlyOwner = process.argv[2] ? new PublicKey(process.argv[2]) : null;
+  const [ammPda] = PublicKey.findProgramAddressSync([AMM_SEED], PID);
+
+  const accounts = await conn.getProgramAccounts(PID, { filters: [{ dataSize: LEGACY_POSITION_SIZE }] });
+  const legacy = [];
+  for (const { pubkey, account } of accounts) {
+    const owner = new PublicKey(account.data.slice(8, 40));
+    if (onlyOwner && !owner.equals(onlyOwner)) continue;
+    const [expectedPda] = PublicKey.findProgramAddressSync([POS_SEED, ammPda.toBuffer(), owner.toBuffer()], PID);
+    if (expectedPda.equals(pubkey)) legacy.push({ owner, position: pubkey });
+  }
+
+  if (legacy.length === 0) {
+    console.log('No 901-byte positions to migrate');
+    return;
+  }
+  console.log(`Migrating ${legacy.length} position(s) with payer ${kp.publicKey.toString()}`);
+
+  for (let i = 0; i < legacy.length; i += BATCH_SIZE) {
+    const batch = legacy.slice(i, i + BATCH_SIZE);
+    const tx = new Transaction();
+    for (const p of batch) tx.add(migrateIx(ammPda, p.owner, p.position, kp.publicKey));
+    try {
+      const sig = await sendAndConfirmTransaction(conn, tx, [kp], { commitment: 'confirmed' });
+      console.log(`  ✓ ${batch.length} grown: ${sig}`);
+    } catch (err) {
+      console.error(`  ✗ batch ${i / BATCH_SIZE} failed: ${err.message}`);
+    }
+  }
+}
+
+main().catch((err) => {
+  console.error(err);
+  process.exit(1);
+});
@@ -83,8 +83,8 @@ async function main() {
   console.log(`   Owner: ${positionInfo.owner.toString()}`);
   console.log(`   Lamports: ${positionInfo.lamports}`);
 
-  if (positionInfo.data.length === 901) {
-    console.log('\n✅ Position is already migrated to new layout (901 bytes).');
+  if (positionInfo.data.length >= 901) {
+    console.log('\n✅ Position is already migrated to new layout (901+ bytes).');
     console.log('   No migration needed!');
     process.exit(0);
   }
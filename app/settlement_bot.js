@@ -5,6 +5,7 @@
 
 const fs = require("fs");
 const crypto = require("crypto");
+const anchor = require("@coral-xyz/anchor");
 const http = require("http");
 const {
   Connection, PublicKey, Keypair, SystemProgram,
@@ -20,12 +21,22 @@ const ORACLE_STATE = process.env.ORACLE_STATE
   ? new PublicKey(process.env.ORACLE_STATE)
   : new PublicKey(DEFAULT_ORACLE_STATE);
 
+// Expected oracle update_authority pinned with the feed (unset = authority not checked)
+const ORACLE_AUTHORITY = process.env.ORACLE_AUTHORITY
+  ? new PublicKey(process.env.ORACLE_AUTHORITY)
+  : PublicKey.default;
+
 // === PROGRAM IDs / SEEDS ===
 const PID = new PublicKey("EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF");
 const AMM_SEED = Buffer.from("amm_btc_v6");  // v6: added market_end_time for time-based trading lockout
 const VAULT_SOL_SEED = Buffer.from("vault_sol");
 const USER_VAULT_SEED = Buffer.from("user_vault");
 const POS_SEED = Buffer.from("pos");
+const ROUND_SEED = Buffer.from("round");
+const INSURANCE_SEED = Buffer.from("insurance");
+
+// Amm accounts are decoded with the program IDL, so layout changes don't shift field reads
+const IDL_PATH = process.env.IDL_PATH || "./target/idl/cpi_oracle.json";
 
 // === TIMING CONSTANTS ===
 const PREMARKET_DURATION_MS = 1 * 60 * 1000;  // 5 minutes pre-market (no snapshot yet)
@@ -282,6 +293,9 @@ function readStatus() {
 }
 
 /* ---------------- Market Operations ---------------- */
+const MARKET_B = 5_000_000_000; // 5,000.00 XNT liquidity (5B in e6 scale, where 1 XNT = 10M e6)
+const MARKET_FEE_BPS = 25;
+
 async function closeMarket(conn, kp, ammPda) {
   log("Closing existing market...");
   const closeIx = new TransactionInstruction({
@@ -300,8 +314,8 @@ async function closeMarket(conn, kp, ammPda) {
 
 async function initMarket(conn, kp, ammPda, vaultPda) {
   log(C.bold("Initializing new market..."));
-  const b = 5_000_000_000; // 5,000.00 XNT liquidity (5B in e6 scale, where 1 XNT = 10M e6)
-  const feeBps = 25;
+  const b = MARKET_B;
+  const feeBps = MARKET_FEE_BPS;
 
   // Display market parameters
   const bDisplay = (b / E6_PER_XNT).toFixed(2);
@@ -325,7 +339,7 @@ async function initMarket(conn, kp, ammPda, vaultPda) {
       { pubkey: kp.publicKey, isSigner: false, isWritable: true }, // fee_dest
       { pubkey: vaultPda, isSigner: false, isWritable: true },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: PublicKey.findProgramAddressSync([Buffer.from("round_counter"), ammPda.toBuffer()], PID)[0], isSigner: false, isWritable: true },
     ],
     data: initData,
   });
@@ -342,6 +356,63 @@ async function initMarket(conn, kp, ammPda, vaultPda) {
   logInfo(`  🔗 Explorer: https://explorer.solana.com/tx/${sig}?cluster=custom`);
 }
 
+// Reinitialize the settled market in place; unclaimed winnings stay redeemable
+async function reopenMarket(conn, kp, ammPda, vaultPda) {
+  log(C.bold("Reopening market in place..."));
+  const data = Buffer.alloc(8 + 8 + 2);
+  discriminator("reopen_amm").copy(data, 0);
+  data.writeBigInt64LE(BigInt(MARKET_B), 8);
+  data.writeUInt16LE(MARKET_FEE_BPS, 16);
+
+  const ix = new TransactionInstruction({
+    programId: PID,
+    keys: [
+      { pubkey: ammPda, isSigner: false, isWritable: true },
+      { pubkey: vaultPda, isSigner: false, isWritable: false },
+      { pubkey: kp.publicKey, isSigner: true, isWritable: false },
+    ],
+    data,
+  });
+
+  const tx = new Transaction().add(ix);
+  const sig = await sendTransactionWithRetry(conn, tx, [kp]);
+  logSuccess(`Market reopened: ${sig}`);
+}
+
+// Pin ORACLE_STATE (and ORACLE_AUTHORITY, if set) so the market reads no other feed
+async function pinOracle(conn, kp, ammPda) {
+  const ix = new TransactionInstruction({
+    programId: PID,
+    keys: [
+      { pubkey: ammPda, isSigner: false, isWritable: true },
+      { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+      { pubkey: kp.publicKey, isSigner: true, isWritable: false },
+    ],
+    data: Buffer.concat([discriminator("pin_oracle"), ORACLE_AUTHORITY.toBuffer()]),
+  });
+
+  const tx = new Transaction().add(ix);
+  const sig = await sendTransactionWithRetry(conn, tx, [kp]);
+  logSuccess(`Oracle pinned to ${ORACLE_STATE.toString()}: ${sig}`);
+}
+
+// Reopen a settled market, or close + init when it can't be reopened (unsettled / missing)
+async function openMarket(conn, kp, ammPda, vaultPda) {
+  const ammInfo = await conn.getAccountInfo(ammPda);
+  if (ammInfo) {
+    try {
+      await reopenMarket(conn, kp, ammPda, vaultPda);
+      return;
+    } catch (err) {
+      logInfo(C.y(`Reopen failed (${err.message}); closing and re-initializing`));
+    }
+    await closeMarket(conn, kp, ammPda);
+    await new Promise(r => setTimeout(r, 2000));
+  }
+  await initMarket(conn, kp, ammPda, vaultPda);
+  await pinOracle(conn, kp, ammPda);
+}
+
 async function snapshotStart(conn, kp, ammPda) {
   if (!ORACLE_STATE) {
     logError("ORACLE_STATE not set - cannot take snapshot");
@@ -408,6 +479,16 @@ async function stopMarket(conn, kp, ammPda) {
   logSuccess(`Market stopped: ${sig}`);
 }
 
+let program = null;
+function programFor(conn, kp) {
+  if (!program) {
+    const idl = JSON.parse(fs.readFileSync(IDL_PATH, "utf8"));
+    const provider = new anchor.AnchorProvider(conn, new anchor.Wallet(kp), { commitment: "confirmed" });
+    program = new anchor.Program(idl, provider);
+  }
+  return program;
+}
+
 async function settleMarket(conn, kp, ammPda) {
   if (!ORACLE_STATE) {
     logError("ORACLE_STATE not set - cannot settle");
@@ -419,11 +500,27 @@ async function settleMarket(conn, kp, ammPda) {
   discriminator("settle_by_oracle").copy(settleData, 0);
   settleData.writeUInt8(1, 8); // ge_wins_yes = true
 
+  // Settlement writes the round's result into its own PDA: [b"round", amm, round_id]
+  const amm = await programFor(conn, kp).account.amm.fetch(ammPda);
+  const roundIdBuf = amm.roundId.toArrayLike(Buffer, "le", 8);
+  const [roundResultPda] = PublicKey.findProgramAddressSync(
+    [ROUND_SEED, ammPda.toBuffer(), roundIdBuf],
+    PID
+  );
+  // Settlement may draw on the insurance fund to top the vault up to 1.0 per winning share
+  const [vaultPda] = PublicKey.findProgramAddressSync([VAULT_SOL_SEED, ammPda.toBuffer()], PID);
+  const [insurancePda] = PublicKey.findProgramAddressSync([INSURANCE_SEED, ammPda.toBuffer()], PID);
+
   const settleIx = new TransactionInstruction({
     programId: PID,
     keys: [
       { pubkey: ammPda, isSigner: false, isWritable: true },
       { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+      { pubkey: roundResultPda, isSigner: false, isWritable: true },
+      { pubkey: kp.publicKey, isSigner: true, isWritable: true },
+      { pubkey: vaultPda, isSigner: false, isWritable: true },
+      { pubkey: insurancePda, isSigner: false, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
     ],
     data: settleData,
   });
@@ -440,16 +537,14 @@ async function findAllPositions(conn, ammPda) {
 
   const POS_SEED = Buffer.from("pos");
 
-  // Get all program accounts for our program that match Position size
-  // Position size: discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + master_wallet(32) + vault_balance_e6(8) + vault_bump(1) + vec_len(4) + nonces(8*100) = 901 bytes
-  // Note: Vec<u64> makes this dynamic, but typically allocated for 100 nonces
-  const accounts = await conn.getProgramAccounts(PID, {
-    filters: [
-      {
-        dataSize: 8 + 893, // 901 bytes total (8-byte discriminator + 893-byte Position struct with Vec<u64> for 100 nonces)
-      },
-    ],
-  });
+  // Get all program accounts for our program that match a Position size
+  // Legacy: discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + master_wallet(32) + vault_balance_e6(8) + vault_bump(1) + vec_len(4) + nonces(8*100) = 901 bytes
+  // Current: legacy + tail (round_id, carry_round_id, carry_yes, carry_no, iou, iou_round_id, min_nonce) = 957 bytes
+  // (901-byte positions are grown by migrate_position_v2_to_v3, see app/migrate-positions.js)
+  const accounts = [];
+  for (const dataSize of [8 + 893 + 56, 8 + 893]) {
+    accounts.push(...(await conn.getProgramAccounts(PID, { filters: [{ dataSize }] })));
+  }
 
   const positions = [];
   for (const { pubkey, account } of accounts) {
@@ -467,7 +562,20 @@ async function findAllPositions(conn, ammPda) {
 
       // Read shares (i64 each)
       const yesShares = Number(data.readBigInt64LE(offset)); offset += 8;
-      const noShares = Number(data.readBigInt64LE(offset));
+      const noShares = Number(data.readBigInt64LE(offset)); offset += 8;
+
+      // Skip master_wallet + vault_balance + vault_bump, then the used_nonces vec
+      offset += 32 + 8 + 1;
+      const nonceCount = data.readUInt32LE(offset); offset += 4 + 8 * nonceCount;
+
+      // Tail: round_id, then shares parked from an earlier round (carry_*). A legacy position
+      // with a full nonce list has no tail yet (reads as round 0, no carry once grown)
+      const hasTail = offset + 32 <= data.length;
+      const roundId = hasTail ? data.readBigUInt64LE(offset) : 0n; offset += 8;
+      const carryRoundId = hasTail ? data.readBigUInt64LE(offset) : 0n; offset += 8;
+      const carryYes = hasTail ? Number(data.readBigInt64LE(offset)) : 0; offset += 8;
+      const carryNo = hasTail ? Number(data.readBigInt64LE(offset)) : 0;
+      const hasCarry = carryYes !== 0 || carryNo !== 0;
 
       // Verify this position belongs to the current AMM by checking PDA derivation
       const [expectedPda] = PublicKey.findProgramAddressSync(
@@ -476,14 +584,16 @@ async function findAllPositions(conn, ammPda) {
       );
 
       // Only include if:
-      // 1. Has shares
+      // 1. Has shares (current or parked from an earlier round)
       // 2. PDA matches current AMM (not from old markets)
-      if ((yesShares > 0 || noShares > 0) && expectedPda.equals(pubkey)) {
+      if ((yesShares > 0 || noShares > 0 || hasCarry) && expectedPda.equals(pubkey)) {
         positions.push({
           pubkey,
           owner,
           yesShares,
           noShares,
+          // Round the next redeem settles against (parked shares first)
+          claimRoundId: hasCarry ? carryRoundId : roundId,
         });
       }
     } catch (err) {
@@ -517,6 +627,8 @@ async function autoRedeemAllPositions(conn, kp, ammPda, vaultPda, snapshotPrice
 
   logInfo(`Settlement prices: Snapshot=$${snapshotPrice?.toFixed(2) || '?'}, Settle=$${settlePrice?.toFixed(2) || '?'}`);
 
+  const [insurancePda] = PublicKey.findProgramAddressSync([INSURANCE_SEED, ammPda.toBuffer()], PID);
+
   let successCount = 0;
   let failCount = 0;
 
@@ -547,6 +659,14 @@ async function autoRedeemAllPositions(conn, kp, ammPda, vaultPda, snapshotPrice
         PID
       );
 
+      // RoundResult PDA for the round this redeem settles against
+      const claimRoundBuf = Buffer.alloc(8);
+      claimRoundBuf.writeBigUInt64LE(pos.claimRoundId);
+      const [roundResultPda] = PublicKey.findProgramAddressSync(
+        [ROUND_SEED, ammPda.toBuffer(), claimRoundBuf],
+        PID
+      );
+
       log(`  Position PDA: ${pos.pubkey.toString()}`);
       log(`  User (session wallet): ${pos.owner.toString()}`);
       log(`  User Vault PDA: ${userVaultPda.toString()}`);
@@ -568,6 +688,8 @@ async function autoRedeemAllPositions(conn, kp, ammPda, vaultPda, snapshotPrice
           { pubkey: kp.publicKey, isSigner: false, isWritable: true }, // fee_dest (same as admin)
           { pubkey: vaultPda, isSigner: false, isWritable: true }, // vault_sol
           { pubkey: userVaultPda, isSigner: false, isWritable: true }, // user_vault (NEW!)
+          { pubkey: roundResultPda, isSigner: false, isWritable: true }, // round_result
+          { pubkey: insurancePda, isSigner: false, isWritable: true }, // insurance fund
           { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         ],
         data: discriminator("admin_redeem"),
@@ -773,19 +895,10 @@ async function runCycle(conn, kp, ammPda, vaultPda) {
   log(C.bold(C.y(`🔄 Next Cycle Scheduled: ${formatTime(new Date(nextCycleStartTime))} (in ${formatCountdown(nextCycleStartTime - Date.now())})`)));
 
   try {
-    // Step 1: Wipe positions from previous cycle, close market, then IMMEDIATELY open new one
-    const ammInfo = await conn.getAccountInfo(ammPda);
-    if (ammInfo) {
-      log("Wiping positions from previous cycle...");
-      await wipeAllPositions(conn, kp, ammPda);
-      await new Promise(r => setTimeout(r, 1000));
-
-      await closeMarket(conn, kp, ammPda);
-      await new Promise(r => setTimeout(r, 2000));
-    }
-
-    // Step 2: Initialize new market IMMEDIATELY (no waiting period)
-    await initMarket(conn, kp, ammPda, vaultPda);
+    // Step 1-2: Reopen the market IMMEDIATELY (no waiting period)
+    // (positions from the previous cycle park their shares via their round_id stamp,
+    //  and unredeemed winnings stay claimable from the reserve)
+    await openMarket(conn, kp, ammPda, vaultPda);
     await new Promise(r => setTimeout(r, 1500));
 
     // Reset volume for new market cycle
@@ -968,54 +1081,6 @@ async function runCycle(conn, kp, ammPda, vaultPda) {
   }
 }
 
-/* ---------------- Wipe All Positions ---------------- */
-async function wipeAllPositions(conn, kp, ammPda) {
-  log(C.bold("\n=== WIPING ALL POSITIONS ==="));
-
-  const positions = await findAllPositions(conn, ammPda);
-
-  if (positions.length === 0) {
-    logInfo("No positions to wipe");
-    return;
-  }
-
-  logInfo(`Found ${positions.length} positions to wipe`);
-
-  let successCount = 0;
-  let failCount = 0;
-
-  for (const pos of positions) {
-    try {
-      const ownerShort = pos.owner.toString().slice(0, 8);
-      log(`[${successCount + failCount + 1}/${positions.length}] Wiping position for ${ownerShort}...`);
-
-      // Call wipe_position instruction
-      const wipeIx = new TransactionInstruction({
-        programId: PID,
-        keys: [
-          { pubkey: ammPda, isSigner: false, isWritable: true },
-          { pubkey: kp.publicKey, isSigner: true, isWritable: false }, // admin
-          { pubkey: pos.owner, isSigner: false, isWritable: false }, // owner (for PDA derivation)
-          { pubkey: pos.pubkey, isSigner: false, isWritable: true }, // position account
-        ],
-        data: discriminator("wipe_position"),
-      });
-
-      const budgetIx = ComputeBudgetProgram.setComputeUnitLimit({ units: 150_000 });
-      const tx = new Transaction().add(budgetIx, wipeIx);
-      const sig = await sendTransactionWithRetry(conn, tx, [kp]);
-
-      logSuccess(`  ✓ Wiped: ${sig.slice(0, 16)}...`);
-      successCount++;
-    } catch (err) {
-      logError(`  ✗ Failed for ${pos.owner.toString().slice(0, 8)}: ${err.message}`);
-      failCount++;
-    }
-  }
-
-  logSuccess(`\nWipe complete: ${successCount} success, ${failCount} failed`);
-}
-
 /* ---------------- Main ---------------- */
 async function main() {
   console.log(C.bold("\n╔═══════════════════════════════════════════════╗"));
@@ -1048,18 +1113,7 @@ async function main() {
   // Initialize market IMMEDIATELY on startup (allow pre-market trading right away)
   log(C.bold("\n🚀 Initializing market for immediate pre-market trading...\n"));
 
-  const ammInfo = await conn.getAccountInfo(ammPda);
-  if (ammInfo) {
-    log("Found existing market - wiping old positions first...");
-    await wipeAllPositions(conn, kp, ammPda);
-    await new Promise(r => setTimeout(r, 1000));
-
-    log("Closing existing market...");
-    await closeMarket(conn, kp, ammPda);
-    await new Promise(r => setTimeout(r, 2000));
-  }
-
-  await initMarket(conn, kp, ammPda, vaultPda);
+  await openMarket(conn, kp, ammPda, vaultPda);
   await new Promise(r => setTimeout(r, 1500));
 
   // Reset volume for new market cycle
@@ -15,6 +15,7 @@ const ORDER_BOOK_API = process.env.ORDER_BOOK_API || 'http://localhost:3436';
 
 // Program IDs
 const PID = new PublicKey('EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF');
+const LIMIT_ORDER_VERSION = 5;                 // signing envelope version (LIMIT_ORDER_VERSION on-chain)
 const AMM_SEED = Buffer.from('amm_btc_v6');
 
 /* ---------------- Borsh Schema ---------------- */
@@ -32,6 +33,12 @@ const LimitOrderSchema = borsh.struct([
   borsh.u64('nonce'),
   borsh.u16('keeper_fee_bps'),
   borsh.u16('min_fill_bps'),
+  borsh.u8('trigger_kind'),
+  borsh.i64('trigger_price_e6'),
+  borsh.u64('group_id'),
+  borsh.bool('reduce_only'),
+  borsh.u16('slice_count'),
+  borsh.i64('slice_interval_secs'),
 ]);
 
 /* ---------------- Helpers ---------------- */
@@ -67,6 +74,14 @@ async function submitLimitOrder() {
   const keeperFeeBps = parseInt(parseArg('--keeper-fee', '10')); // 0.1% default
   const minFillBps = parseInt(parseArg('--min-fill', '5000')); // 50% default
   const marketOverride = parseArg('--market');
+  const oracleAbove = parseArg('--oracle-above') ? parseFloat(parseArg('--oracle-above')) : null;
+  const oracleBelow = parseArg('--oracle-below') ? parseFloat(parseArg('--oracle-below')) : null;
+  const stopLoss = parseArg('--stop') ? parseFloat(parseArg('--stop')) : null;
+  const takeProfit = parseArg('--take-profit') ? parseFloat(parseArg('--take-profit')) : null;
+  const groupId = parseInt(parseArg('--group', '0'));
+  const reduceOnly = hasFlag('--reduce-only');
+  const sliceCount = parseInt(parseArg('--slices', '0'));
+  const sliceInterval = parseInt(parseArg('--interval', '0'));
   const help = hasFlag('--help') || hasFlag('-h');
 
   if (help) {
@@ -86,6 +101,14 @@ Options:
   --keeper-fee <bps>      Keeper fee in basis points (default: 10 = 0.1%)
   --min-fill <bps>        Minimum fill percentage (default: 5000 = 50%)
   --market <pubkey>       Market pubkey (default: derived from AMM seed)
+  --oracle-above <price>  Only fill once the underlying (oracle) price is at or above this
+  --oracle-below <price>  Only fill once the underlying (oracle) price is at or below this
+  --stop <price>          Stop-loss SELL: fill once the side's price is at or below this
+  --take-profit <price>   Take-profit SELL: fill once the side's price is at or above this
+  --group <id>            OCO group id: once one order of the group fills, the others can't
+  --reduce-only           Only fill what shrinks net exposure (--stop / --take-profit only sell shares held)
+  --slices <n>            TWAP: split the order into n equal slices (a partial slice stays open)
+  --interval <seconds>    TWAP: minimum time between slices (default: 0)
   -h, --help              Show this help
 
 Environment Variables:
@@ -100,6 +123,20 @@ Examples:
   # Sell 50 NO shares at min $0.60 per share
   ANCHOR_WALLET=./userB.json node app/submit-order.js \\
     --action 2 --side 2 --shares 50 --price 0.60 --min-proceeds 30
+
+  # Buy 100 YES at max $0.70 once BTC trades at or above $100,000
+  ANCHOR_WALLET=./userA.json node app/submit-order.js \\
+    --action 1 --side 1 --shares 100 --price 0.70 --oracle-above 100000
+
+  # Bracket 100 YES shares: stop out below $0.30, take profit above $0.80 (one cancels the other)
+  ANCHOR_WALLET=./userA.json node app/submit-order.js \
+    --action 2 --side 1 --shares 100 --price 0.25 --stop 0.30 --group 7
+  ANCHOR_WALLET=./userA.json node app/submit-order.js \
+    --action 2 --side 1 --shares 100 --price 0.75 --take-profit 0.80 --group 7
+
+  # TWAP: buy 1000 YES at max $0.55 in 10 slices, at most one every 5 minutes
+  ANCHOR_WALLET=./userA.json node app/submit-order.js \
+    --action 1 --side 1 --shares 1000 --price 0.55 --slices 10 --interval 300 --ttl 7200
     `);
     process.exit(0);
   }
@@ -125,6 +162,33 @@ Examples:
     process.exit(1);
   }
 
+  const triggers = [oracleAbove, oracleBelow, stopLoss, takeProfit].filter((t) => t !== null);
+  if (triggers.length > 1) {
+    console.error('❌ Error: use only one of --oracle-above / --oracle-below / --stop / --take-profit');
+    process.exit(1);
+  }
+  const triggerPrice = triggers.length ? triggers[0] : null;
+  if (triggerPrice !== null && !(triggerPrice > 0)) {
+    console.error('❌ Error: trigger price must be a positive number');
+    process.exit(1);
+  }
+  if ((stopLoss !== null || takeProfit !== null) && action !== '2') {
+    console.error('❌ Error: --stop / --take-profit orders must be SELL (--action 2)');
+    process.exit(1);
+  }
+  if (!Number.isInteger(groupId) || groupId < 0) {
+    console.error('❌ Error: --group must be a non-negative integer');
+    process.exit(1);
+  }
+  if (!Number.isInteger(sliceCount) || sliceCount < 0 || sliceCount > 65535) {
+    console.error('❌ Error: --slices must be an integer between 0 and 65535');
+    process.exit(1);
+  }
+  if (!Number.isInteger(sliceInterval) || sliceInterval < 0) {
+    console.error('❌ Error: --interval must be a non-negative number of seconds');
+    process.exit(1);
+  }
+
   // Load wallet
   console.log('📁 Loading wallet:', WALLET);
   const wallet = loadWallet(WALLET);
@@ -152,6 +216,13 @@ Examples:
     nonce,
     keeper_fee_bps: keeperFeeBps,
     min_fill_bps: minFillBps,
+    trigger_kind: oracleAbove !== null ? 1 : oracleBelow !== null ? 2
+      : stopLoss !== null ? 3 : takeProfit !== null ? 4 : 0,
+    trigger_price_e6: triggerPrice !== null ? Math.floor(triggerPrice * 1e6) : 0,
+    group_id: groupId,
+    reduce_only: reduceOnly,
+    slice_count: sliceCount,
+    slice_interval_secs: sliceInterval,
   };
 
   console.log('\n📝 Order Details:');
@@ -165,6 +236,16 @@ Examples:
   console.log('  Keeper Fee:', `${keeperFeeBps / 100}%`);
   console.log('  Min Fill:', `${minFillBps / 100}%`);
   console.log('  Nonce:', nonce);
+  if (order.trigger_kind) {
+    const what = order.trigger_kind <= 2 ? 'oracle' : 'share price';
+    const op = order.trigger_kind === 1 || order.trigger_kind === 4 ? '>=' : '<=';
+    console.log('  Trigger:', `${what} ${op} $${triggerPrice}`);
+  }
+  if (order.group_id) console.log('  OCO Group:', order.group_id);
+  if (order.reduce_only) console.log('  Reduce Only: yes');
+  if (order.slice_count > 1) {
+    console.log('  TWAP:', `${sliceCount} slices, at least ${sliceInterval}s apart`);
+  }
 
   // Serialize order with Borsh (manual binary encoding)
   console.log('\n🔐 Signing order...');
@@ -193,7 +274,7 @@ Examples:
   }
 
   // Manually encode according to Borsh layout
-  const messageBytes = Buffer.concat([
+  const orderBytes = Buffer.concat([
     order.market.toBuffer(),                 // 32 bytes: market pubkey
     order.user.toBuffer(),                   // 32 bytes: user pubkey
     writeU8(order.action),                   // 1 byte: action
@@ -206,6 +287,28 @@ Examples:
     writeU64LE(order.nonce),                 // 8 bytes: nonce
     writeU16LE(order.keeper_fee_bps),        // 2 bytes: keeper_fee_bps
     writeU16LE(order.min_fill_bps),          // 2 bytes: min_fill_bps
+    writeU8(order.trigger_kind),             // 1 byte: trigger_kind
+    writeI64LE(order.trigger_price_e6),      // 8 bytes: trigger_price_e6
+    writeU64LE(order.group_id),              // 8 bytes: group_id
+    writeU8(order.reduce_only ? 1 : 0),      // 1 byte: reduce_only
+    writeU16LE(order.slice_count),           // 2 bytes: slice_count
+    writeI64LE(order.slice_interval_secs),   // 8 bytes: slice_interval_secs
+  ]);
+
+  // Wrap in the signing envelope: Solana off-chain message (header v0) with this program as the
+  // application domain and ASCII body "limit_order v<version> " || hex(borsh(order))
+  // (must match limit_order_message in programs/cpi_oracle)
+  const body = Buffer.from(`limit_order v${LIMIT_ORDER_VERSION} ${orderBytes.toString('hex')}`, 'ascii');
+  const bodyLen = Buffer.alloc(2);
+  bodyLen.writeUInt16LE(body.length);
+  const messageBytes = Buffer.concat([
+    Buffer.from('\xffsolana offchain', 'latin1'), // signing domain
+    writeU8(0),                                   // header version
+    PID.toBuffer(),                               // application domain
+    writeU8(0), writeU8(1),                       // format: restricted ASCII, 1 signer
+    order.user.toBuffer(),
+    bodyLen,
+    body,
   ]);
 
   // Sign with wallet using Ed25519
@@ -232,6 +335,12 @@ Examples:
     nonce: order.nonce,
     keeper_fee_bps: order.keeper_fee_bps,
     min_fill_bps: order.min_fill_bps,
+    trigger_kind: order.trigger_kind,
+    trigger_price_e6: order.trigger_price_e6,
+    group_id: order.group_id,
+    reduce_only: order.reduce_only,
+    slice_count: order.slice_count,
+    slice_interval_secs: order.slice_interval_secs,
   };
 
   // Submit to order book API
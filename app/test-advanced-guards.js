@@ -17,6 +17,7 @@ const AMM_SEED = Buffer.from("amm_btc_v6");
 const POS_SEED = Buffer.from("pos");
 const VAULT_SOL_SEED = Buffer.from("vault_sol");
 const USER_VAULT_SEED = Buffer.from("user_vault");
+const INSURANCE_SEED = Buffer.from("insurance");
 
 // Oracle state on testnet
 const ORACLE_STATE = new PublicKey("4KYeNyv1B9YjjQkfJk2C6Uqo71vKzFZriRe5NXg6GyCq");
@@ -57,6 +58,10 @@ function vaultSolPda(amm) {
   return PublicKey.findProgramAddressSync([VAULT_SOL_SEED, amm.toBuffer()], PID)[0];
 }
 
+function insurancePda(amm) {
+  return PublicKey.findProgramAddressSync([INSURANCE_SEED, amm.toBuffer()], PID)[0];
+}
+
 function userVaultPda(pos) {
   return PublicKey.findProgramAddressSync([USER_VAULT_SEED, pos.toBuffer()], PID)[0];
 }
@@ -71,10 +76,11 @@ function getInstructionDiscriminator(name) {
 // Encode trade_advanced instruction
 // Layout: disc(8) + side(1) + action(1) + amount(8) + AdvancedGuardConfig
 // AdvancedGuardConfig: price_limit_e6(8) + max_slippage_bps(2) + quote_price_e6(8) +
-//                      quote_timestamp(8) + max_total_cost_e6(8) + allow_partial(1) + min_fill_shares_e6(8)
+//                      quote_timestamp(8) + max_total_cost_e6(8) + allow_partial(1) + min_fill_shares_e6(8) +
+//                      reduce_only(1)
 function encodeTradeAdvanced(side, action, amount, guards) {
   const discriminator = getInstructionDiscriminator("trade_advanced");
-  const buf = Buffer.alloc(8 + 1 + 1 + 8 + 8 + 2 + 8 + 8 + 8 + 1 + 8); // Total: 61 bytes
+  const buf = Buffer.alloc(8 + 1 + 1 + 8 + 8 + 2 + 8 + 8 + 8 + 1 + 8 + 1); // Total: 62 bytes
 
   let offset = 0;
   discriminator.copy(buf, offset);
@@ -123,6 +129,10 @@ function encodeTradeAdvanced(side, action, amount, guards) {
   // min_fill_shares_e6 (i64)
   const minFillBN = new BN(guards.min_fill_shares_e6 || 0);
   minFillBN.toArrayLike(Buffer, "le", 8).copy(buf, offset);
+  offset += 8;
+
+  // reduce_only (bool/u8)
+  buf.writeUInt8(guards.reduce_only ? 1 : 0, offset);
 
   return buf;
 }
@@ -204,6 +214,7 @@ async function main() {
   const amm = ammPda();
   const pos = posPda(payer.publicKey, amm);
   const vaultSol = vaultSolPda(amm);
+  const insurance = insurancePda(amm);
   const userVault = userVaultPda(pos);
 
   // Fetch fee_dest from AMM account
@@ -249,6 +260,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -289,6 +301,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -329,6 +342,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -370,6 +384,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -410,6 +425,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -449,6 +465,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -489,6 +506,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -529,6 +547,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -0,0 +1,285 @@
+#!/usr/bin/env node
+// app/test-ed25519-negative.js — Malformed Ed25519 verify instructions must not authorize a fill
+//
+// Builds execute_limit_order transactions around a correctly signed order and tampers with the
+// Ed25519 instruction (or the verify_ix_index argument) one way per case. Each case is simulated
+// and must be rejected: either by the Ed25519 precompile itself, or by the program with
+// InvalidSignature. The "valid" case must get past signature verification.
+//
+// Needs a running cluster with the market initialised and a position for USER_WALLET.
+// Usage: USER_WALLET=./userA.json node app/test-ed25519-negative.js
+
+const fs = require("fs");
+const crypto = require("crypto");
+const {
+  Connection, PublicKey, Keypair, SystemProgram, Transaction, TransactionInstruction,
+  ComputeBudgetProgram, SYSVAR_INSTRUCTIONS_PUBKEY,
+} = require("@solana/web3.js");
+
+/* ---------------- CONFIG ---------------- */
+const RPC = process.env.ANCHOR_PROVIDER_URL || "http://127.0.0.1:8899";
+const KEEPER_WALLET = process.env.KEEPER_WALLET || process.env.ANCHOR_WALLET || `${process.env.HOME}/.config/solana/id.json`;
+const USER_WALLET = process.env.USER_WALLET || KEEPER_WALLET;
+const ORACLE_STATE = new PublicKey(process.env.ORACLE_STATE || "4KYeNyv1B9YjjQkfJk2C6Uqo71vKzFZriRe5NXg6GyCq");
+
+const PID = new PublicKey("EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF");
+const ED25519_PROGRAM_ID = new PublicKey("Ed25519SigVerify111111111111111111111111111");
+const AMM_SEED = Buffer.from("amm_btc_v6");
+const POS_SEED = Buffer.from("pos");
+const VAULT_SOL_SEED = Buffer.from("vault_sol");
+const USER_VAULT_SEED = Buffer.from("user_vault");
+const INSURANCE_SEED = Buffer.from("insurance");
+const ORDER_FILL_SEED = Buffer.from("order_fill");
+
+const SELF = 0xffff;       // Ed25519 offsets instruction index meaning "this instruction"
+const ED25519_IX = 1;      // [compute budget, ed25519, execute_limit_order]
+const EXECUTE_IX = 2;
+
+/* ---------------- Helpers ---------------- */
+function loadKeypair(path) {
+  return Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(path, "utf8"))));
+}
+
+function discriminator(ixName) {
+  return crypto.createHash("sha256").update(`global:${ixName}`).digest().slice(0, 8);
+}
+
+function sign(keypair, message) {
+  // Raw 32-byte seed wrapped as a PKCS#8 Ed25519 private key
+  const der = Buffer.concat([Buffer.from("302e020100300506032b657004220420", "hex"), keypair.secretKey.slice(0, 32)]);
+  const key = crypto.createPrivateKey({ key: der, format: "der", type: "pkcs8" });
+  return crypto.sign(null, message, key);
+}
+
+// Borsh layout of LimitOrder (same as app/test-ed25519.js)
+function serializeOrder(o) {
+  const b = Buffer.alloc(32 + 32 + 1 + 1 + 8 * 6 + 2 + 2 + 1 + 8 + 8 + 1 + 2 + 8);
+  let off = 0;
+  o.market.toBuffer().copy(b, off); off += 32;
+  o.user.toBuffer().copy(b, off); off += 32;
+  b.writeUInt8(o.action, off); off += 1;
+  b.writeUInt8(o.side, off); off += 1;
+  for (const v of [o.sharesE6, o.limitPriceE6, o.maxCostE6, o.minProceedsE6, o.expiryTs]) {
+    b.writeBigInt64LE(BigInt(v), off); off += 8;
+  }
+  b.writeBigUInt64LE(BigInt(o.nonce), off); off += 8;
+  b.writeUInt16LE(o.keeperFeeBps, off); off += 2;
+  b.writeUInt16LE(o.minFillBps, off); off += 2;
+  b.writeUInt8(o.triggerKind, off); off += 1;
+  b.writeBigInt64LE(BigInt(o.triggerPriceE6), off); off += 8;
+  b.writeBigUInt64LE(BigInt(o.groupId), off); off += 8;
+  b.writeUInt8(o.reduceOnly ? 1 : 0, off); off += 1;
+  b.writeUInt16LE(o.sliceCount, off); off += 2;
+  b.writeBigInt64LE(BigInt(o.sliceIntervalSecs), off);
+  return b;
+}
+
+// Signing envelope (limit_order_message on-chain): Solana off-chain message, header v0
+function limitOrderMessage(orderBytes, user) {
+  const body = Buffer.from(`limit_order v5 ${orderBytes.toString("hex")}`, "ascii");
+  const bodyLen = Buffer.alloc(2);
+  bodyLen.writeUInt16LE(body.length);
+  return Buffer.concat([
+    Buffer.from("\xffsolana offchain", "latin1"), Buffer.from([0]), PID.toBuffer(),
+    Buffer.from([0, 1]), user.toBuffer(), bodyLen, body,
+  ]);
+}
+
+// Ed25519 verify data: [count, pad] + 14-byte offsets per entry + (pubkey, sig, msg) per entry.
+// An entry may override any offsets field to point elsewhere.
+function ed25519Data(entries) {
+  const headerLen = 2 + 14 * entries.length;
+  const header = Buffer.alloc(headerLen);
+  header.writeUInt8(entries.length, 0);
+  const bodies = [];
+  let cursor = headerLen;
+  entries.forEach((e, i) => {
+    const pkOff = cursor;
+    const sigOff = pkOff + 32;
+    const msgOff = sigOff + 64;
+    const fields = {
+      sigOff, sigIx: SELF, pkOff, pkIx: SELF, msgOff, msgSize: e.msg.length, msgIx: SELF,
+      ...e.override,
+    };
+    const r = 2 + 14 * i;
+    header.writeUInt16LE(fields.sigOff, r);
+    header.writeUInt16LE(fields.sigIx, r + 2);
+    header.writeUInt16LE(fields.pkOff, r + 4);
+    header.writeUInt16LE(fields.pkIx, r + 6);
+    header.writeUInt16LE(fields.msgOff, r + 8);
+    header.writeUInt16LE(fields.msgSize, r + 10);
+    header.writeUInt16LE(fields.msgIx, r + 12);
+    bodies.push(e.pubkey, e.sig, e.msg);
+    cursor = msgOff + e.msg.length;
+  });
+  return Buffer.concat([header, ...bodies]);
+}
+
+/* ---------------- Cases ---------------- */
+// expect: "ok"          → passes signature verification (may still fail later, e.g. on price)
+//         "rejected"    → precompile failure or program InvalidSignature
+function buildCases(user, keeper, orderBytes, message, sig) {
+  const entry = { pubkey: user.publicKey.toBuffer(), sig, msg: message };
+  // In the execute_limit_order data: [disc 8][order][signature 64][verify_ix_index 2]
+  const inExecute = { order: 8, user: 8 + 32, sig: 8 + orderBytes.length };
+  const otherMsg = Buffer.from("unrelated message");
+
+  return [
+    { name: "valid", expect: "ok", data: ed25519Data([entry]) },
+    {
+      name: "message read from another instruction",
+      expect: "rejected",
+      data: ed25519Data([{
+        ...entry,
+        sig: sign(user, orderBytes),
+        override: { msgIx: EXECUTE_IX, msgOff: inExecute.order, msgSize: orderBytes.length },
+      }]),
+    },
+    {
+      name: "public key read from another instruction",
+      expect: "rejected",
+      data: ed25519Data([{ ...entry, override: { pkIx: EXECUTE_IX, pkOff: inExecute.user } }]),
+    },
+    {
+      name: "signature read from another instruction",
+      expect: "rejected",
+      data: ed25519Data([{ ...entry, override: { sigIx: EXECUTE_IX, sigOff: inExecute.sig } }]),
+    },
+    {
+      name: "two signatures for one order",
+      expect: "rejected",
+      data: ed25519Data([entry, { pubkey: keeper.publicKey.toBuffer(), sig: sign(keeper, otherMsg), msg: otherMsg }]),
+    },
+    { name: "zero signatures", expect: "rejected", data: Buffer.from([0, 0]) },
+    {
+      name: "count larger than the offsets present",
+      expect: "rejected",
+      data: (() => { const d = ed25519Data([entry]); d.writeUInt8(3, 0); return d; })(),
+    },
+    { name: "truncated offsets record", expect: "rejected", data: ed25519Data([entry]).slice(0, 10) },
+    {
+      name: "message runs past the end of the data",
+      expect: "rejected",
+      data: ed25519Data([{ ...entry, override: { msgSize: message.length + 64 } }]),
+    },
+    {
+      name: "signature over a different order",
+      expect: "rejected",
+      data: (() => {
+        const other = Buffer.from(orderBytes);
+        other.writeUInt16LE(other.readUInt16LE(114) + 1, 114); // keeper_fee_bps + 1 (after the 114-byte prefix)
+        const otherMessage = limitOrderMessage(other, user.publicKey);
+        return ed25519Data([{ ...entry, msg: otherMessage, sig: sign(user, otherMessage) }]);
+      })(),
+    },
+    {
+      name: "bare order without the signing envelope",
+      expect: "rejected",
+      data: ed25519Data([{ ...entry, msg: orderBytes, sig: sign(user, orderBytes) }]),
+    },
+    {
+      name: "envelope for another program id",
+      expect: "rejected",
+      data: (() => {
+        const foreign = Buffer.from(message);
+        Keypair.generate().publicKey.toBuffer().copy(foreign, 17); // application domain
+        return ed25519Data([{ ...entry, msg: foreign, sig: sign(user, foreign) }]);
+      })(),
+    },
+    { name: "verify_ix_index points at the compute budget ix", expect: "rejected", data: ed25519Data([entry]), verifyIx: 0 },
+    { name: "verify_ix_index points at this instruction", expect: "rejected", data: ed25519Data([entry]), verifyIx: EXECUTE_IX },
+    { name: "verify_ix_index past the end", expect: "rejected", data: ed25519Data([entry]), verifyIx: 9 },
+  ];
+}
+
+function classify(sim) {
+  const logs = sim.value.logs || [];
+  const err = sim.value.err;
+  if (!err) return { result: "ok", why: "simulation succeeded" };
+  const failedIx = err.InstructionError ? err.InstructionError[0] : null;
+  if (failedIx === ED25519_IX) return { result: "rejected", why: `Ed25519 precompile: ${JSON.stringify(err.InstructionError[1])}` };
+  if (logs.some((l) => l.includes("Error Code: InvalidSignature"))) return { result: "rejected", why: "program: InvalidSignature" };
+  if (logs.some((l) => l.includes("Ed25519 signature verified"))) {
+    const code = logs.find((l) => l.includes("Error Code:")) || JSON.stringify(err);
+    return { result: "ok", why: `verified; later failure ${code.trim()}` };
+  }
+  return { result: "other", why: JSON.stringify(err) };
+}
+
+/* ---------------- Main ---------------- */
+async function main() {
+  const conn = new Connection(RPC, "confirmed");
+  const keeper = loadKeypair(KEEPER_WALLET);
+  const user = loadKeypair(USER_WALLET);
+
+  const [amm] = PublicKey.findProgramAddressSync([AMM_SEED], PID);
+  const [position] = PublicKey.findProgramAddressSync([POS_SEED, amm.toBuffer(), user.publicKey.toBuffer()], PID);
+  const [userVault] = PublicKey.findProgramAddressSync([USER_VAULT_SEED, position.toBuffer()], PID);
+  const [vaultSol] = PublicKey.findProgramAddressSync([VAULT_SOL_SEED, amm.toBuffer()], PID);
+  const [insurance] = PublicKey.findProgramAddressSync([INSURANCE_SEED, amm.toBuffer()], PID);
+  const ammInfo = await conn.getAccountInfo(amm);
+  if (!ammInfo) throw new Error(`AMM ${amm.toString()} not found`);
+  const feeDest = new PublicKey(ammInfo.data.slice(70, 102));
+
+  const order = {
+    market: amm, user: user.publicKey, action: 1, side: 1,
+    sharesE6: 1_000_000, limitPriceE6: 990_000, maxCostE6: 0, minProceedsE6: 0,
+    expiryTs: Math.floor(Date.now() / 1000) + 600,
+    nonce: BigInt(Date.now()) * 1000n + BigInt(Math.floor(Math.random() * 1000)),
+    keeperFeeBps: 10, minFillBps: 0, triggerKind: 0, triggerPriceE6: 0, groupId: 0, reduceOnly: false,
+    sliceCount: 0, sliceIntervalSecs: 0,
+  };
+  const orderBytes = serializeOrder(order);
+  const message = limitOrderMessage(orderBytes, user.publicKey);
+  const sig = sign(user, message);
+
+  const nonceLe = Buffer.alloc(8);
+  nonceLe.writeBigUInt64LE(order.nonce);
+  const [orderFill] = PublicKey.findProgramAddressSync([ORDER_FILL_SEED, position.toBuffer(), nonceLe], PID);
+
+  const keys = [
+    { pubkey: amm, isSigner: false, isWritable: true },
+    { pubkey: position, isSigner: false, isWritable: true },
+    { pubkey: orderFill, isSigner: false, isWritable: true },
+    { pubkey: orderFill, isSigner: false, isWritable: false }, // nonce_fill: same PDA for an ungrouped order
+    { pubkey: userVault, isSigner: false, isWritable: true },
+    { pubkey: feeDest, isSigner: false, isWritable: true },
+    { pubkey: vaultSol, isSigner: false, isWritable: true },
+    { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+    { pubkey: user.publicKey, isSigner: false, isWritable: false },
+    { pubkey: keeper.publicKey, isSigner: true, isWritable: true },
+    { pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false },
+    { pubkey: insurance, isSigner: false, isWritable: true },
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+  ];
+
+  let failures = 0;
+  for (const c of buildCases(user, keeper, orderBytes, message, sig)) {
+    const verifyIx = Buffer.alloc(2);
+    verifyIx.writeUInt16LE(c.verifyIx ?? ED25519_IX);
+    const executeIx = new TransactionInstruction({
+      programId: PID,
+      keys,
+      data: Buffer.concat([discriminator("execute_limit_order"), orderBytes, sig, verifyIx]),
+    });
+    const ed25519Ix = new TransactionInstruction({ programId: ED25519_PROGRAM_ID, keys: [], data: c.data });
+    const tx = new Transaction().add(
+      ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+      ed25519Ix,
+      executeIx,
+    );
+
+    const got = classify(await conn.simulateTransaction(tx, [keeper]));
+    const pass = got.result === c.expect;
+    if (!pass) failures++;
+    console.log(`${pass ? "✓" : "✗"} ${c.name}: ${got.result} (${got.why})`);
+  }
+
+  console.log(failures === 0 ? "\nAll cases behaved as expected" : `\n${failures} case(s) failed`);
+  process.exit(failures === 0 ? 0 : 1);
+}
+
+main().catch((err) => {
+  console.error(err);
+  process.exit(1);
+});
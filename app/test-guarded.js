@@ -17,6 +17,7 @@ const AMM_SEED = Buffer.from("amm_btc_v6");
 const POS_SEED = Buffer.from("pos");
 const VAULT_SOL_SEED = Buffer.from("vault_sol");
 const USER_VAULT_SEED = Buffer.from("user_vault");
+const INSURANCE_SEED = Buffer.from("insurance");
 
 // Oracle state on testnet
 const ORACLE_STATE = new PublicKey("4KYeNyv1B9YjjQkfJk2C6Uqo71vKzFZriRe5NXg6GyCq");
@@ -56,6 +57,10 @@ function vaultSolPda(amm) {
   return PublicKey.findProgramAddressSync([VAULT_SOL_SEED, amm.toBuffer()], PID)[0];
 }
 
+function insurancePda(amm) {
+  return PublicKey.findProgramAddressSync([INSURANCE_SEED, amm.toBuffer()], PID)[0];
+}
+
 function userVaultPda(pos) {
   return PublicKey.findProgramAddressSync([USER_VAULT_SEED, pos.toBuffer()], PID)[0];
 }
@@ -117,6 +122,7 @@ async function main() {
   const amm = ammPda();
   const pos = posPda(payer.publicKey, amm);
   const vaultSol = vaultSolPda(amm);
+  const insurance = insurancePda(amm);
   const userVault = userVaultPda(pos);
 
   console.log("PDAs:");
@@ -185,6 +191,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -232,6 +239,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -282,6 +290,7 @@ async function main() {
         { pubkey: feeDest, isSigner: false, isWritable: true },
         { pubkey: vaultSol, isSigner: false, isWritable: true },
         { pubkey: ORACLE_STATE, isSigner: false, isWritable: false },
+        { pubkey: insurance, isSigner: false, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
       ],
@@ -22,6 +22,7 @@ const AMM_SEED       = Buffer.from("amm_btc_v6");  // v6: time-based trading loc
 const POS_SEED       = Buffer.from("pos");
 const VAULT_SOL_SEED = Buffer.from("vault_sol");
 const USER_VAULT_SEED = Buffer.from("user_vault");
+const INSURANCE_SEED = Buffer.from("insurance");
 
 // Must match the on-chain LAMPORTS_PER_E6 for correct SOL reporting
 const LAMPORTS_PER_E6 = 100; // CRITICAL: Must match Rust program (lib.rs:982)
@@ -97,6 +98,7 @@ function whoKeyByIndex(i){ return String.fromCharCode(65 + i); }            // "
 async function ammPda(){ return PublicKey.findProgramAddressSync([AMM_SEED], PID)[0]; }
 function posPda(owner, amm){ return PublicKey.findProgramAddressSync([POS_SEED, amm.toBuffer(), owner.toBuffer()], PID)[0]; }
 function vaultSolPda(amm){ return PublicKey.findProgramAddressSync([VAULT_SOL_SEED, amm.toBuffer()], PID)[0]; }
+function insurancePda(amm){ return PublicKey.findProgramAddressSync([INSURANCE_SEED, amm.toBuffer()], PID)[0]; }
 function userVaultPda(pos){ return PublicKey.findProgramAddressSync([USER_VAULT_SEED, pos.toBuffer()], PID)[0]; }
 
 function disc(name){ return crypto.createHash("sha256").update("global:"+name).digest().subarray(0,8); }
@@ -258,6 +260,7 @@ async function ixInitAmm(conn, payer, bHuman=500000, feeBps=25){
     { pubkey: feeDest,                 isSigner:false, isWritable:true  },
     { pubkey: vaultSol,                isSigner:false, isWritable:true  },
     { pubkey: SystemProgram.programId, isSigner:false, isWritable:false },
+    { pubkey: PublicKey.findProgramAddressSync([Buffer.from("round_counter"), amm.toBuffer()], PID)[0], isSigner:false, isWritable:true },
   ];
   const tx = new Transaction().add(
     ...budgetIxs(),
@@ -315,6 +318,7 @@ async function ixTrade(conn, payer, side /*1 yes 2 no*/, action /*1 buy 2 sell*/
   const userVault = userVaultPda(pos);
   const feeDest = FEE_DEST_GLOBAL || getFeeDest(payer.publicKey);
   const vaultSol = vaultSolPda(amm);
+  if (!ORACLE_STATE) throw new Error("trade requires --oracle <pubkey> or ORACLE_STATE env (lockout check)");
 
   const data = Buffer.concat([D_TRADE, u8(side), u8(action), i64le(amountScaled)]);
   const keys = [
@@ -324,6 +328,8 @@ async function ixTrade(conn, payer, side /*1 yes 2 no*/, action /*1 buy 2 sell*/
     { pubkey: userVault,               isSigner:false, isWritable:true  },
     { pubkey: feeDest,                 isSigner:false, isWritable:true  },
     { pubkey: vaultSol,                isSigner:false, isWritable:true  },
+    { pubkey: ORACLE_STATE,            isSigner:false, isWritable:false },
+    { pubkey: insurancePda(amm),       isSigner:false, isWritable:true  },
     { pubkey: SystemProgram.programId, isSigner:false, isWritable:false },
     { pubkey: SYSVAR_RENT_PUBKEY,      isSigner:false, isWritable:false },
   ];
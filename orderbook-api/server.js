@@ -127,6 +127,33 @@ function validateOrder(order) {
     return 'limit_price_e6 must be positive';
   }
 
+  // Validate trigger (0=none, 1=oracle at/above, 2=oracle at/below, 3=stop-loss, 4=take-profit)
+  if (order.trigger_kind !== undefined && ![0, 1, 2, 3, 4].includes(order.trigger_kind)) {
+    return 'Invalid trigger_kind';
+  }
+  if ((order.trigger_kind === 3 || order.trigger_kind === 4) && order.action !== 2) {
+    return 'Stop-loss / take-profit orders must be SELL';
+  }
+  if (order.group_id !== undefined && !(Number.isInteger(order.group_id) && order.group_id >= 0)) {
+    return 'Invalid group_id';
+  }
+  if (order.reduce_only !== undefined && typeof order.reduce_only !== 'boolean') {
+    return 'reduce_only must be a boolean';
+  }
+
+  // Validate TWAP slicing (slice_count 0/1 = not sliced)
+  if (order.slice_count !== undefined &&
+      !(Number.isInteger(order.slice_count) && order.slice_count >= 0 && order.slice_count <= 65535)) {
+    return 'Invalid slice_count';
+  }
+  if (order.slice_interval_secs !== undefined &&
+      !(Number.isInteger(order.slice_interval_secs) && order.slice_interval_secs >= 0)) {
+    return 'slice_interval_secs must be a non-negative integer';
+  }
+  if (order.trigger_kind && !(order.trigger_price_e6 > 0)) {
+    return 'trigger_price_e6 must be positive for a triggered order';
+  }
+
   // Validate expiry is in the future
   const now = Math.floor(Date.now() / 1000);
   if (order.expiry_ts <= now) {
@@ -433,9 +460,13 @@ app.post('/api/orders/:order_id/fill', (req, res) => {
     const price = (execution_price / 1e6).toFixed(6);
     const txShort = tx_signature.slice(0, 8);
 
+    // Orders can fill in several pieces on-chain; stay pending until the full size is filled
+    const totalFilled = (order.filled_shares_e6 || 0) + shares_filled;
+    const fullyFilled = totalFilled >= orderData.shares_e6;
+
     const sql = `
       UPDATE orders
-      SET status = 'filled',
+      SET status = ?,
           filled_tx = ?,
           filled_at = strftime('%s', 'now'),
           filled_shares_e6 = ?,
@@ -444,7 +475,7 @@ app.post('/api/orders/:order_id/fill', (req, res) => {
       WHERE id = ? AND status = 'pending'
     `;
 
-    db.run(sql, [tx_signature, shares_filled, execution_price, keeper_pubkey, order_id], function(err) {
+    db.run(sql, [fullyFilled ? 'filled' : 'pending', tx_signature, totalFilled, execution_price, keeper_pubkey, order_id], function(err) {
       if (err) {
         console.error('Error marking order as filled:', err);
         broadcastKeeperLog(`❌ Failed to mark order #${order_id} as filled: ${err.message}`, 'error');
@@ -455,7 +486,9 @@ app.post('/api/orders/:order_id/fill', (req, res) => {
         return res.status(404).json({ error: 'Order not found or already processed' });
       }
 
-      const logMsg = `✅ Order #${order_id} filled: ${action} ${shares} ${side} @ $${price}`;
+      const logMsg = fullyFilled
+        ? `✅ Order #${order_id} filled: ${action} ${shares} ${side} @ $${price}`
+        : `🧩 Order #${order_id} partially filled: ${action} ${shares} ${side} @ $${price} (${(totalFilled / 10_000_000).toFixed(2)} total)`;
       console.log(logMsg);
       broadcastKeeperLog(logMsg, 'success');
       broadcastKeeperLog(`TX: ${tx_signature}`, 'tx');
@@ -464,7 +497,7 @@ app.post('/api/orders/:order_id/fill', (req, res) => {
       broadcastOrderUpdate('order_filled', {
         order_id: parseInt(order_id),
         order: orderData,
-        status: 'filled',
+        status: fullyFilled ? 'filled' : 'pending',
         filled_tx: tx_signature,
         filled_shares: parseFloat(shares),
         execution_price: parseFloat(price),
@@ -474,11 +507,38 @@ app.post('/api/orders/:order_id/fill', (req, res) => {
       // Notify main server about the fill to update cost basis
       notifyMainServerOfFill(orderData, shares_filled, execution_price);
 
+      // OCO: the rest of the group can no longer fill on-chain
+      if (orderData.group_id) {
+        cancelOcoSiblings(order_id, orderData);
+      }
+
       res.json({ success: true, order_id: parseInt(order_id) });
     });
   });
 });
 
+// Cancel the other pending orders of a filled order's OCO group
+function cancelOcoSiblings(orderId, orderData) {
+  db.all(
+    `SELECT id, order_json FROM orders WHERE user_pubkey = ? AND status = 'pending' AND id != ?`,
+    [orderData.user, orderId],
+    (err, rows) => {
+      if (err) {
+        console.error('Error loading OCO siblings:', err);
+        return;
+      }
+      for (const row of rows) {
+        if (JSON.parse(row.order_json).group_id !== orderData.group_id) continue;
+        db.run(`UPDATE orders SET status = 'cancelled' WHERE id = ? AND status = 'pending'`, [row.id], function(err) {
+          if (err || this.changes === 0) return;
+          console.log(`🚫 Order ${row.id} cancelled (OCO group ${orderData.group_id} filled by #${orderId})`);
+          broadcastOrderUpdate('order_cancelled', { order_id: row.id, status: 'cancelled' });
+        });
+      }
+    }
+  );
+}
+
 // Helper function to notify main server of filled order for cost basis tracking
 async function notifyMainServerOfFill(orderData, sharesFilled, executionPrice) {
   try {
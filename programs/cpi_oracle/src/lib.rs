@@ -10,9 +10,19 @@ declare_id!("EeQNdiGDUVj4jzPMBkx59J45p1y93JpKByTWifWtuxjF");
 // =========================
 // Oracle (foreign) settings
 // =========================
+#[cfg(not(feature = "mock-oracle"))]
 pub const ORACLE_PROGRAM_ID: Pubkey =
     pubkey!("7ARBeYF5rGCanAGiRaxhVpiuZZpGXazo5UJqHMoJgkuE");
 
+// programs/mock_oracle: same account layout, prices and timestamps set by the test
+#[cfg(feature = "mock-oracle")]
+pub const ORACLE_PROGRAM_ID: Pubkey =
+    pubkey!("Azo5s4RrEgwm1qiHnYseYQZXVnTYTmWZxAdM61UTtLG7");
+
+// Owner of Pyth-style (v2) price accounts
+pub const PYTH_PROGRAM_ID: Pubkey =
+    pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi9epH");
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct OracleStateMirror {
     pub update_authority: Pubkey,
@@ -85,6 +95,9 @@ pub struct AdvancedGuardConfig {
     // Partial fill support
     pub allow_partial: bool,            // Allow partial execution?
     pub min_fill_shares_e6: i64,       // Minimum shares to execute (if partial)
+
+    // Close-only trading
+    pub reduce_only: bool,             // Clip to what shrinks net exposure; fail if there is none
 }
 
 // ===========================
@@ -117,6 +130,49 @@ pub struct LimitOrder {
     pub keeper_fee_bps: u16,
     /// Minimum fill percentage (basis points) - 5000 = 50%
     pub min_fill_bps: u16,
+    /// Extra condition before the order may fill: 0=none, 1=oracle at/above, 2=oracle at/below,
+    /// 3=stop-loss (side price at/below), 4=take-profit (side price at/above)
+    pub trigger_kind: u8,
+    /// Trigger level (1e6 scale) - underlying price for oracle triggers, share price for 3/4
+    pub trigger_price_e6: i64,
+    /// OCO group (0 = none): orders sharing a group id share one fill state, so once one of them
+    /// fills the others can't
+    pub group_id: u64,
+    /// Only fill what shrinks the user's net exposure (stop-loss / take-profit are separately
+    /// clipped to the shares held on their side)
+    pub reduce_only: bool,
+    /// TWAP: split shares_e6 into this many equal slices (0 or 1 = not sliced); a slice that only
+    /// partly fills stays open for the rest of its size
+    pub slice_count: u16,
+    /// TWAP: minimum seconds between the fill completing one slice and the start of the next
+    pub slice_interval_secs: i64,
+}
+
+impl LimitOrder {
+    /// Id the order's OrderFill state is keyed by: its group if any, else its own nonce.
+    pub fn fill_id(&self) -> u64 {
+        if self.group_id != 0 { self.group_id } else { self.nonce }
+    }
+
+    /// Seed of the order's OrderFill state. Groups get their own namespace, so a group id can
+    /// never collide with (and cancel or fill through) a nonce of the same value.
+    pub fn fill_seed(&self) -> &'static [u8] {
+        if self.group_id != 0 { OrderFill::GROUP_SEED } else { OrderFill::SEED }
+    }
+
+    #[inline] pub fn is_sliced(&self) -> bool {
+        self.slice_count > 1
+    }
+
+    /// Most shares one fill may take: a TWAP slice (rounded up), or the whole order.
+    pub fn slice_size_e6(&self) -> i64 {
+        if self.is_sliced() {
+            let n = self.slice_count as i64;
+            self.shares_e6.saturating_add(n - 1) / n
+        } else {
+            self.shares_e6
+        }
+    }
 }
 
 impl AdvancedGuardConfig {
@@ -129,6 +185,7 @@ impl AdvancedGuardConfig {
             max_total_cost_e6: 0,
             allow_partial: false,
             min_fill_shares_e6: 0,
+            reduce_only: false,
         }
     }
 
@@ -154,6 +211,33 @@ impl AdvancedGuardConfig {
     }
 }
 
+// ===========================
+// Series template (recurring rounds spawned by roll_round)
+// ===========================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SeriesParams {
+    pub asset: u8,              // Oracle triplet: 1=BTC, 2=ETH, 3=SOL
+    pub duration_secs: i64,     // Round open -> market_end_time
+    pub results_secs: i64,      // Settlement -> next round opens (redemption window)
+    pub b: i64,                 // LMSR liquidity (1e6)
+    pub fee_bps: u16,
+    pub lockout_secs: i64,      // Trading lockout before market_end_time
+    pub ge_wins_yes: bool,      // Tie rule: true => YES wins when settle == start
+}
+
+impl SeriesParams {
+    pub fn validate(&self) -> Result<()> {
+        require!((ASSET_BTC..=ASSET_SOL).contains(&self.asset), ReaderError::BadParam);
+        require!(self.duration_secs > 0, ReaderError::BadParam);
+        require!(self.results_secs >= 0, ReaderError::BadParam);
+        require!(self.b > 0, ReaderError::BadParam);
+        require!(self.fee_bps <= 10_000, ReaderError::BadParam);
+        require!(self.lockout_secs > 0 && self.lockout_secs < self.duration_secs, ReaderError::BadParam);
+        Ok(())
+    }
+}
+
 // ===========================
 // Market (single) with LMSR + coverage + settlement
 // ===========================
@@ -181,7 +265,7 @@ pub struct Amm {
 
     // lifecycle
     pub status: u8,       // MarketStatus as u8
-    pub winner: u8,       // 0=unknown, 1=YES, 2=NO
+    pub winner: u8,       // 0=unknown, 1=YES, 2=NO, 3=VOID (refund)
 
     // settlement snapshot
     pub w_total_e6: i64,  // total winning shares at stop
@@ -202,10 +286,46 @@ pub struct Amm {
     // Market timing
     pub market_end_slot: u64,   // Slot when market ends (0 = not set) - DEPRECATED, use market_end_time
     pub market_end_time: i64,   // Unix timestamp when market ends (0 = not set)
+
+    // Settlement timeout
+    pub settle_grace_secs: i64, // Seconds after market_end_time before anyone may void an unsettled market
+
+    // Round identity
+    pub round_id: u64,          // (epoch << 32) | round within the epoch - never reused, see RoundCounter
+
+    // Round stats (1e6) - copied into RoundResult at settlement
+    pub volume_e6: i64,         // Gross traded notional: BUY spend + SELL proceeds
+
+    // Per-round template (copied from the Series on roll_round)
+    pub asset: u8,              // Oracle triplet: 1=BTC, 2=ETH, 3=SOL (0 = legacy, BTC)
+    pub lockout_secs: i64,      // Trading locks this many seconds before market_end_time (0 = default)
+
+    // Unclaimed winnings (1e6) - backed by vault_sol lamports but excluded from vault_e6
+    pub liability_e6: i64,      // This round: owed to winners at settlement, minus redeemed
+    pub reserved_e6: i64,       // Earlier rounds: carried liability still claimable via their RoundResult
+    pub claim_window_secs: i64, // Seconds after settlement before unclaimed winnings may be swept (0 = default)
+    pub iou_e6: i64,            // Redeem shortfalls owed to positions, paid by claim_iou from vault surplus
+
+    // Insurance fund ([b"insurance", amm] lamports PDA)
+    pub insurance_bps: u16,     // Slice of every fee (bps of the fee) routed to the insurance fund
+
+    // Oracle adapter used to read oracle_state: 1=triplet, 2=Pyth-style, 3=push (0 = legacy, triplet)
+    pub oracle_kind: u8,
+
+    // Oracle sanity limits (0 = default)
+    pub oracle_max_spread_bps: u16, // Reject when the fresh sources disagree by more than this
+    pub oracle_max_age_secs: i64,   // A source older than this does not count toward the 2-of-3 quorum
+
+    // Pinned oracle feed (default = unpinned: every oracle read fails until pin_oracle)
+    pub oracle_state: Pubkey,       // The only oracle_state this market reads
+    pub oracle_authority: Pubkey,   // Expected update_authority of that feed (default = not checked; push feeds need one)
 }
 impl Amm {
     pub const SEED: &'static [u8] = b"amm_btc_v6";  // v6: added market_end_time for time-based trading lockout
+                                                     // (fields after it are appended in place: migrate_amm)
     pub const VAULT_SOL_SEED: &'static [u8] = b"vault_sol";
+    pub const TREASURY_SEED: &'static [u8] = b"treasury";   // system-owned PDA holding swept winnings
+    pub const INSURANCE_SEED: &'static [u8] = b"insurance"; // system-owned PDA backing coverage shortfalls
     pub const SPACE: usize = core::mem::size_of::<Amm>();
 
     #[inline] pub fn status(&self) -> MarketStatus {
@@ -215,6 +335,167 @@ impl Amm {
             _ => MarketStatus::Stopped,
         }
     }
+
+    #[inline] pub fn asset(&self) -> u8 {
+        if self.asset == 0 { ASSET_BTC } else { self.asset }
+    }
+
+    #[inline] pub fn lockout_secs(&self) -> i64 {
+        if self.lockout_secs > 0 { self.lockout_secs } else { TRADING_LOCKOUT_SECONDS }
+    }
+
+    #[inline] pub fn claim_window_secs(&self) -> i64 {
+        if self.claim_window_secs > 0 { self.claim_window_secs } else { DEFAULT_CLAIM_WINDOW_SECS }
+    }
+
+    #[inline] pub fn oracle_kind(&self) -> u8 {
+        if self.oracle_kind == 0 { ORACLE_KIND_TRIPLET } else { self.oracle_kind }
+    }
+
+    #[inline] fn oracle_checks(&self) -> OracleChecks {
+        let max_spread_bps = if self.oracle_max_spread_bps > 0 { self.oracle_max_spread_bps } else { DEFAULT_ORACLE_MAX_SPREAD_BPS };
+        let max_age_secs = if self.oracle_max_age_secs > 0 { self.oracle_max_age_secs } else { ORACLE_MAX_AGE_SECS };
+        OracleChecks { max_spread_bps, max_age_ms: max_age_secs.saturating_mul(1000) }
+    }
+}
+
+// Settled round record (PDA per round: [b"round", amm, round_id]) - written at settlement, redeemed_e6 tracks claims
+#[account]
+pub struct RoundResult {
+    pub round_id: u64,
+    pub winner: u8,              // 1=YES, 2=NO, 3=VOID
+    pub bump: u8,
+    pub start_price_e6: i64,     // oracle snapshot at open (0 if never snapshotted)
+    pub start_ts: i64,
+    pub settle_price_e6: i64,    // oracle price at settlement (0 for manual settle / void)
+    pub settle_ts: i64,
+    pub settled_at: i64,         // Clock unix_timestamp when the result was written
+    pub w_total_e6: i64,
+    pub pps_e6: i64,
+    pub fees_e6: i64,
+    pub volume_e6: i64,
+    pub liability_e6: i64,       // W * pps owed to winners at settlement
+    pub redeemed_e6: i64,        // Paid out so far (this round and after it was carried)
+    pub claim_deadline: i64,     // settled_at + claim window; unpaid liability is sweepable after this
+    pub swept_e6: i64,           // Moved to the treasury by sweep_unclaimed so far (0 = not swept)
+    pub late_paid_e6: i64,       // Paid back out of the treasury by claim_late
+    pub iou_e6: i64,             // Owed but unpaid at redeem time, moved to position IOUs
+}
+impl RoundResult {
+    pub const SEED: &'static [u8] = b"round";
+    pub const SPACE: usize = core::mem::size_of::<RoundResult>();
+}
+
+// Round id epochs (PDA: [b"round_counter", amm]) - outlives close_amm, so every init_amm starts a
+// fresh epoch; reset_round counts rounds inside it. Ids are unique even when close_amm and
+// init_amm land in the same slot.
+#[account]
+pub struct RoundCounter {
+    pub bump: u8,
+    pub epochs: u64,             // init_amm calls so far
+}
+impl RoundCounter {
+    pub const SEED: &'static [u8] = b"round_counter";
+    pub const SPACE: usize = 1 + 8;
+
+    /// First round id of the next epoch.
+    fn next_epoch(&mut self) -> u64 {
+        self.epochs = self.epochs.saturating_add(1);
+        self.epochs << 32
+    }
+}
+
+// Recurring market series (PDA: [b"series", amm]) - template for every round roll_round opens
+#[account]
+pub struct Series {
+    pub bump: u8,
+    pub authority: Pubkey,       // May update the template (fee_dest at creation)
+    pub oracle_state: Pubkey,    // Feed used for start/settle snapshots
+    pub params: SeriesParams,
+    pub rounds_opened: u64,      // Rounds opened by roll_round
+}
+impl Series {
+    pub const SEED: &'static [u8] = b"series";
+    pub const SPACE: usize = core::mem::size_of::<Series>();
+}
+
+// Push oracle (PDA: [b"push_oracle", authority, asset]) - prices written directly by its authority
+#[account]
+pub struct PushOracle {
+    pub authority: Pubkey,       // Only signer allowed to push
+    pub asset: u8,               // ASSET_* this feed prices
+    pub price_e6: i64,
+    pub conf_e6: i64,            // Publisher's confidence / spread (1e6)
+    pub ts_ms: i64,              // Publish time (unix ms, same unit as the triplet oracle)
+    pub bump: u8,
+}
+impl PushOracle {
+    pub const SEED: &'static [u8] = b"push_oracle";
+    pub const SPACE: usize = 32 + 1 + 8 + 8 + 8 + 1;
+}
+
+// Limit order state (PDA: [b"order_fill", position, nonce]) - created by the first fill or a cancel;
+// keepers may fill the order repeatedly until filled_shares_e6 reaches its size or it expires.
+// An OCO group shares one state at [b"order_group", position, group_id].
+#[account]
+#[derive(Default)]
+pub struct OrderFill {
+    pub position: Pubkey,
+    pub nonce: u64,
+    pub expiry_ts: i64,          // Order can't execute after this, so the state may then be closed
+    pub payer: Pubkey,           // Paid the rent; refunded by close_order_fill
+    pub cancelled: bool,         // Set by cancel_order_nonce; no further fills
+    pub bump: u8,
+    pub filled_shares_e6: i64,   // Shares executed so far, across fills
+    pub cost_e6: i64,            // Cumulative spend (BUY) or proceeds (SELL) before fees
+    pub fills: u32,              // Fills so far (a TWAP slice may take several)
+    pub filled_by: u64,          // Nonce of the order that filled (OCO: the only one that may continue)
+    pub last_fill_ts: i64,       // TWAP: the next slice may fill slice_interval_secs after this
+    pub slice_filled_e6: i64,    // TWAP: shares filled in the current slice
+    pub slices_done: u32,        // TWAP: slices filled to their size
+}
+impl OrderFill {
+    pub const SEED: &'static [u8] = b"order_fill";
+    pub const GROUP_SEED: &'static [u8] = b"order_group";
+    pub const SPACE: usize = 32 + 8 + 8 + 32 + 1 + 1 + 8 + 8 + 4 + 8 + 8 + 8 + 4;
+
+    #[inline] fn is_open(&self) -> bool {
+        self.position != Pubkey::default()
+    }
+
+    /// First touch of (position, fill id): record who the state belongs to.
+    fn open(&mut self, position: Pubkey, nonce: u64, expiry_ts: i64, payer: Pubkey, bump: u8) {
+        self.position = position;
+        self.nonce = nonce;
+        self.expiry_ts = expiry_ts;
+        self.payer = payer;
+        self.cancelled = false;
+        self.bump = bump;
+        self.filled_shares_e6 = 0;
+        self.cost_e6 = 0;
+        self.fills = 0;
+        self.filled_by = 0;
+        self.last_fill_ts = 0;
+        self.slice_filled_e6 = 0;
+        self.slices_done = 0;
+    }
+
+    /// Count one fill of `order`. A TWAP slice closes as soon as it reaches its size, even if the
+    /// fill ran a few units past it, so the next slice always waits out slice_interval_secs.
+    fn record(&mut self, order: &LimitOrder, shares_e6: i64, cost_e6: i64, now: i64) {
+        self.filled_shares_e6 = self.filled_shares_e6.saturating_add(shares_e6);
+        self.cost_e6 = self.cost_e6.saturating_add(cost_e6);
+        self.fills = self.fills.saturating_add(1);
+        self.filled_by = order.nonce;
+        self.last_fill_ts = now;
+        if order.is_sliced() {
+            self.slice_filled_e6 = self.slice_filled_e6.saturating_add(shares_e6);
+            if self.slice_filled_e6 >= order.slice_size_e6() {
+                self.slices_done = self.slices_done.saturating_add(1);
+                self.slice_filled_e6 = 0;
+            }
+        }
+    }
 }
 
 // Per-user position (PDA is per-market: [b"pos", amm, user])
@@ -226,14 +507,36 @@ pub struct Position {
     pub master_wallet: Pubkey,   // Backpack wallet that authorized this session wallet
     pub vault_balance_e6: i64,   // User's SOL balance in vault (1e6 scale)
     pub vault_bump: u8,          // Bump for user_vault PDA
-    pub used_nonces: Vec<u64>,   // Track used nonces for limit order replay protection (rolling window of last 100)
+    pub used_nonces: Vec<u64>,   // Legacy replay list (read-only, never trimmed; spent nonces now live in OrderFill PDAs)
+    pub round_id: u64,           // Amm round the shares belong to (0 = created before round stamping)
+    // Shares from an earlier round, parked when the position first trades in a later one
+    pub carry_round_id: u64,
+    pub carry_yes_e6: i64,
+    pub carry_no_e6: i64,
+    // Redeem shortfall still owed to this position (claim_iou)
+    pub iou_e6: i64,
+    pub iou_round_id: u64,       // Round of the most recent shortfall
+    // Limit orders with a lower nonce are void; only cancel_all_orders raises it
+    pub min_nonce: u64,
 }
 impl Position {
     pub const SEED: &'static [u8] = b"pos";
     pub const USER_VAULT_SEED: &'static [u8] = b"user_vault";
     // Note: SPACE is now dynamic due to Vec<u64>. Initial size + room for 100 nonces
-    pub const SPACE: usize = 32 + 8 + 8 + 32 + 8 + 1 + 4 + (8 * 100);  // owner + yes + no + master_wallet + vault_balance + vault_bump + vec_len + (nonces)
-    pub const MAX_NONCES: usize = 100; // Keep rolling window of last 100 nonces
+    pub const LEGACY_SPACE: usize = 32 + 8 + 8 + 32 + 8 + 1 + 4 + (8 * 100);  // owner + yes + no + master_wallet + vault_balance + vault_bump + vec_len + (nonces)
+    pub const TAIL_SPACE: usize = 8 + 8 + 8 + 8 + 8 + 8 + 8;  // round_id + carry_round_id + carry_yes + carry_no + iou + iou_round_id + min_nonce
+    // Fields after `used_nonces` get their own bytes; older 901-byte accounts are grown by
+    // migrate_position_v2_to_v3 (one holding 94+ nonces can't be deserialized before that)
+    pub const SPACE: usize = Self::LEGACY_SPACE + Self::TAIL_SPACE;
+
+    #[inline] pub fn has_carry(&self) -> bool {
+        self.carry_yes_e6 != 0 || self.carry_no_e6 != 0
+    }
+
+    /// Round the next redeem settles against: parked shares first, then the position's own round.
+    #[inline] pub fn claim_round_id(&self) -> u64 {
+        if self.has_carry() { self.carry_round_id } else { self.round_id }
+    }
 }
 
 // ---- Limits (all scaled 1e6) ----
@@ -245,6 +548,52 @@ const DQ_MAX_E6: i64    = 50_000_000_000;  // 50,000,000 shares per trade
 // ---- Market timing ----
 const TRADING_LOCKOUT_SLOTS: u64 = 90;      // Lock trading 90 slots (~45 seconds) before market end - DEPRECATED
 const TRADING_LOCKOUT_SECONDS: i64 = 45;    // Lock trading 45 seconds before market end
+const NEAR_EXPIRY_WINDOW_SECS: i64 = 300;   // In the last 5 minutes before market end...
+const NEAR_EXPIRY_MAX_ORACLE_AGE_SECS: i64 = 15; // ...trades need an oracle update at most 15 seconds old
+const DEFAULT_SETTLE_GRACE_SECS: i64 = 3_600; // Unsettled markets become voidable 1 hour after market end
+const DEFAULT_CLAIM_WINDOW_SECS: i64 = 7 * 86_400; // Unclaimed winnings become sweepable 7 days after settlement
+
+// ---- Oracle assets (triplet order in the oracle account) ----
+const ASSET_BTC: u8 = 1;
+const ASSET_ETH: u8 = 2;
+const ASSET_SOL: u8 = 3;
+
+// ---- Oracle adapters (Amm.oracle_kind) ----
+const ORACLE_KIND_TRIPLET: u8 = 1;            // ORACLE_PROGRAM_ID account: three sources per asset
+const ORACLE_KIND_PYTH: u8 = 2;               // Pyth-style v2 price account (one product per account)
+const ORACLE_KIND_PUSH: u8 = 3;               // PushOracle account owned by this program
+const DEFAULT_ORACLE_MAX_SPREAD_BPS: u16 = 100; // Sources may disagree by at most 1%
+
+// ---- Insurance fund ----
+const DEFAULT_INSURANCE_BPS: u16 = 1_000;     // 10% of fee revenue goes to the insurance fund
+const INSURANCE_DRAW_SELL: u8 = 1;            // vault_sol couldn't cover sell proceeds
+const INSURANCE_DRAW_SETTLE: u8 = 2;          // vault below W at settlement (pps would be < 1.0)
+const INSURANCE_DRAW_REDEEM: u8 = 3;          // vault lamports couldn't cover a redemption
+
+// ---- Redemption crank ----
+const CRANK_MAX_POSITIONS: usize = 16;        // (pos, user_vault) pairs per crank_redeem
+const CRANK_BOUNTY_E6: i64 = 50;              // 5_000 lamports per paid position, taken from the payout
+
+// ---- Keeper batches ----
+const LIMIT_BATCH_MAX_ORDERS: usize = 8;      // Orders per execute_limit_orders (4 remaining accounts each)
+const ED25519_PROGRAM_ID: Pubkey = pubkey!("Ed25519SigVerify111111111111111111111111111");
+
+// ---- Limit order signing envelope (Solana off-chain message, header v0) ----
+const OFFCHAIN_SIGNING_DOMAIN: &[u8; 16] = b"\xffsolana offchain";
+const OFFCHAIN_HEADER_VERSION: u8 = 0;
+const OFFCHAIN_FORMAT_ASCII: u8 = 0;          // restricted ASCII body (printable 0x20..=0x7e)
+const LIMIT_ORDER_TAG: &[u8] = b"limit_order";
+const LIMIT_ORDER_VERSION: u8 = 5;            // bump when the LimitOrder layout changes
+
+// ---- Limit order triggers (LimitOrder.trigger_kind) ----
+const TRIGGER_NONE: u8 = 0;
+const TRIGGER_ORACLE_ABOVE: u8 = 1;           // underlying >= trigger_price_e6
+const TRIGGER_ORACLE_BELOW: u8 = 2;           // underlying <= trigger_price_e6
+const TRIGGER_STOP_LOSS: u8 = 3;              // SELL once the side's price <= trigger_price_e6
+const TRIGGER_TAKE_PROFIT: u8 = 4;            // SELL once the side's price >= trigger_price_e6
+
+// ---- Settlement outcomes ----
+const WINNER_VOID: u8 = 3;                    // Market voided: every share (YES or NO) redeems at the refund pps
 
 // ---- Events ----
 #[event]
@@ -273,6 +622,9 @@ pub struct LimitOrderExecuted {
     pub execution_price: i64,
     pub keeper_fee_bps: u16,
     pub nonce: u64,
+    pub total_filled: i64,    // Across all fills of this order
+    pub group_id: u64,
+    pub fills: u32,           // Fills so far (a TWAP slice may take several)
 }
 
 #[event]
@@ -281,6 +633,80 @@ pub struct OrderNonceCancelled {
     pub nonce: u64,
 }
 
+#[event]
+pub struct OrderGroupCancelled {
+    pub user: Pubkey,
+    pub group_id: u64,
+}
+
+#[event]
+pub struct AllOrdersCancelled {
+    pub user: Pubkey,
+    pub min_nonce: u64,
+}
+
+#[event]
+pub struct LimitOrdersBatchExecuted {
+    pub keeper: Pubkey,
+    pub orders: u32,
+    pub filled: u32,
+    pub skipped: u32,         // could not fill (expired, cancelled, filled, price past limit)
+}
+
+#[event]
+pub struct MarketVoided {
+    pub market_end_time: i64,
+    pub voided_at: i64,
+    pub w_total_e6: i64,     // YES + NO shares outstanding at void
+    pub pps_e6: i64,         // refund per share (1e6)
+    pub vault_e6: i64,
+}
+
+#[event]
+pub struct InsuranceTopUp {
+    pub round_id: u64,
+    pub amount_e6: i64,
+    pub from_fees: bool,      // fee slice (true) or direct fund_insurance (false)
+    pub balance_lamports: u64,
+}
+
+#[event]
+pub struct InsuranceDraw {
+    pub round_id: u64,
+    pub reason: u8,           // 1=SELL, 2=SETTLE, 3=REDEEM
+    pub needed_e6: i64,
+    pub drawn_e6: i64,
+    pub balance_lamports: u64,
+}
+
+#[event]
+pub struct CrankRedeemed {
+    pub round_id: u64,
+    pub cranker: Pubkey,
+    pub positions: u32,       // positions whose claim was settled (paid or wiped)
+    pub paid: u32,            // of which paid out
+    pub bounty_lamports: u64,
+}
+
+#[event]
+pub struct UnclaimedSwept {
+    pub round_id: u64,
+    pub swept_e6: i64,       // unpaid winnings moved to the treasury
+    pub liability_e6: i64,   // owed to winners at settlement
+    pub redeemed_e6: i64,    // paid out before the sweep
+    pub treasury: Pubkey,
+    pub swept_at: i64,
+}
+
+#[event]
+pub struct RoundOpened {
+    pub round_id: u64,
+    pub asset: u8,
+    pub start_price_e6: i64,
+    pub start_ts: i64,
+    pub market_end_time: i64,
+}
+
 // ============================== Accounts ==============================
 
 #[derive(Accounts)]
@@ -318,6 +744,15 @@ pub struct InitAmm<'info> {
     pub vault_sol: UncheckedAccount<'info>,
 
     pub system_program: Program<'info, System>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoundCounter::SPACE,
+        seeds = [RoundCounter::SEED, amm.key().as_ref()],
+        bump
+    )]
+    pub round_counter: Account<'info, RoundCounter>,
 }
 
 #[derive(Accounts)]
@@ -394,6 +829,10 @@ pub struct Trade<'info> {
     /// CHECK: Oracle state account for reading BTC price and timestamp
     pub oracle_state: UncheckedAccount<'info>,
 
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -497,6 +936,35 @@ pub struct AdminOnly<'info> {
     pub amm: Account<'info, Amm>,
 }
 
+#[derive(Accounts)]
+pub struct AdminConfig<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// Admin signer — we gate this to the AMM's fee_dest
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VoidMarket<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoundResult::SPACE,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &amm.round_id.to_le_bytes()],
+        bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct Redeem<'info> {
     #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
@@ -533,6 +1001,18 @@ pub struct Redeem<'info> {
     )]
     pub user_vault: UncheckedAccount<'info>,
 
+    /// CHECK: RoundResult PDA for `pos.claim_round_id()`; empty if that round never settled
+    #[account(
+        mut,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &pos.claim_round_id().to_le_bytes()],
+        bump
+    )]
+    pub round_result: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -579,6 +1059,53 @@ pub struct AdminRedeem<'info> {
     )]
     pub user_vault: UncheckedAccount<'info>,
 
+    /// CHECK: RoundResult PDA for `pos.claim_round_id()`; empty if that round never settled
+    #[account(
+        mut,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &pos.claim_round_id().to_le_bytes()],
+        bump
+    )]
+    pub round_result: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Permissionless batch redeem; positions come in `remaining_accounts` as
+/// (pos, user_vault) pairs, all settled against `round_id`.
+#[derive(Accounts)]
+#[instruction(round_id: u64)]
+pub struct CrankRedeem<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// Anyone; receives the bounty
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space)
+    #[account(
+        mut,
+        seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()],
+        bump = amm.vault_sol_bump
+    )]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: RoundResult PDA for `round_id`; empty if that round never settled
+    #[account(
+        mut,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &round_id.to_le_bytes()],
+        bump
+    )]
+    pub round_result: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -595,92 +1122,411 @@ pub struct CloseAmm<'info> {
     pub recipient: Signer<'info>,
 }
 
-// ---- NEW: oracle snapshot / settlement contexts ----
 #[derive(Accounts)]
-pub struct SnapshotStart<'info> {
+#[instruction(round_id: u64)]
+pub struct SweepUnclaimed<'info> {
     #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
     pub amm: Account<'info, Amm>,
-    /// CHECK: must be owned by the oracle program
-    #[account(owner = ORACLE_PROGRAM_ID)]
-    pub oracle_state: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &round_id.to_le_bytes()],
+        bump = round_result.bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: treasury PDA (system-owned, 0 space) receiving the swept lamports
+    #[account(mut, seeds = [Amm::TREASURY_SEED, amm.key().as_ref()], bump)]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct SettleByOracle<'info> {
-    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+pub struct ClaimLate<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
     pub amm: Account<'info, Amm>,
-    /// CHECK: must be owned by the oracle program
-    #[account(owner = ORACLE_PROGRAM_ID)]
-    pub oracle_state: AccountInfo<'info>,
-}
 
+    pub user: Signer<'info>,
 
-#[derive(Accounts)]
-pub struct WipePosition<'info> {
-    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
-    pub amm: Account<'info, Amm>,
+    #[account(
+        mut,
+        seeds = [Position::SEED, amm.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = pos.owner == user.key() @ ReaderError::NotOwner
+    )]
+    pub pos: Account<'info, Position>,
 
-    /// Admin signer — we gate this to the AMM's fee_dest for safety
-    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &pos.claim_round_id().to_le_bytes()],
+        bump = round_result.bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
 
-    /// CHECK: used only for PDA seeds (owner pubkey)
-    pub owner: UncheckedAccount<'info>,
+    /// CHECK: treasury PDA (system-owned, 0 space) that pays late claims
+    #[account(mut, seeds = [Amm::TREASURY_SEED, amm.key().as_ref()], bump)]
+    pub treasury: UncheckedAccount<'info>,
 
+    /// CHECK: User's vault PDA that receives the payout
     #[account(
         mut,
-        seeds = [Position::SEED, amm.key().as_ref(), owner.key().as_ref()],
-        bump
+        seeds = [Position::USER_VAULT_SEED, pos.key().as_ref()],
+        bump = pos.vault_bump
     )]
-    pub pos: Account<'info, Position>,
+    pub user_vault: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct MigratePosition<'info> {
-    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+pub struct PinOracle<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
     pub amm: Account<'info, Amm>,
 
-    /// Owner of the position (session wallet) - must sign
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    /// CHECK: owner and layout checked by the market's oracle adapter (load_oracle)
+    pub oracle_state: AccountInfo<'info>,
 
-    /// Master wallet (Backpack wallet) - receives vault balance
-    /// CHECK: verified against old_position.master_wallet during migration
-    #[account(mut)]
-    pub master_wallet: UncheckedAccount<'info>,
+    /// Admin signer — we gate this to the AMM's fee_dest
+    pub admin: Signer<'info>,
+}
 
-    /// Old position account (97 bytes) - will be closed
-    /// CHECK: We manually deserialize this account since it uses old layout
+#[derive(Accounts)]
+#[instruction(asset: u8)]
+pub struct InitPushOracle<'info> {
     #[account(
-        mut,
-        seeds = [Position::SEED, amm.key().as_ref(), owner.key().as_ref()],
+        init,
+        payer = authority,
+        space = 8 + PushOracle::SPACE,
+        seeds = [PushOracle::SEED, authority.key().as_ref(), &[asset]],
         bump
     )]
-    pub old_position: UncheckedAccount<'info>,
+    pub feed: Account<'info, PushOracle>,
 
-    /// User vault PDA - holds the vault balance
-    /// CHECK: Derived from old_position, used to transfer funds
     #[account(mut)]
-    pub user_vault: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ExecuteLimitOrder<'info> {
-    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
-    pub amm: Account<'info, Amm>,
-
+pub struct PushPrice<'info> {
     #[account(
         mut,
-        seeds = [Position::SEED, amm.key().as_ref(), user.key().as_ref()],
-        bump
+        seeds = [PushOracle::SEED, authority.key().as_ref(), &[feed.asset]],
+        bump = feed.bump,
+        has_one = authority
     )]
-    pub position: Account<'info, Position>,
+    pub feed: Account<'info, PushOracle>,
 
-    /// CHECK: User vault PDA (system-owned, holds user's SOL)
-    #[account(
-        mut,
-        seeds = [Position::USER_VAULT_SEED, position.key().as_ref()],
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundInsurance<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimIou<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [Position::SEED, amm.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = pos.owner == user.key() @ ReaderError::NotOwner
+    )]
+    pub pos: Account<'info, Position>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: User's vault PDA that receives the payout
+    #[account(
+        mut,
+        seeds = [Position::USER_VAULT_SEED, pos.key().as_ref()],
+        bump = pos.vault_bump
+    )]
+    pub user_vault: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReopenAmm<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// CHECK: SOL vault PDA; its balance (minus reserved winnings) seeds the new round's vault_e6
+    #[account(seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// Admin signer — we gate this to the AMM's fee_dest
+    pub admin: Signer<'info>,
+}
+
+// ---- NEW: oracle snapshot / settlement contexts ----
+#[derive(Accounts)]
+pub struct SnapshotStart<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+    /// CHECK: owner and layout checked by the market's oracle adapter (read_oracle)
+    pub oracle_state: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleByOracle<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+    /// CHECK: owner and layout checked by the market's oracle adapter (read_oracle)
+    pub oracle_state: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoundResult::SPACE,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &amm.round_id.to_le_bytes()],
+        bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitSeries<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Series::SPACE,
+        seeds = [Series::SEED, amm.key().as_ref()],
+        bump
+    )]
+    pub series: Account<'info, Series>,
+
+    /// CHECK: owner and layout checked by the market's oracle adapter (read_oracle)
+    pub oracle_state: AccountInfo<'info>,
+
+    /// Admin signer — we gate this to the AMM's fee_dest
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSeries<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(mut, seeds = [Series::SEED, amm.key().as_ref()], bump = series.bump, has_one = authority)]
+    pub series: Account<'info, Series>,
+
+    /// CHECK: owner and layout checked by the market's oracle adapter (read_oracle)
+    pub oracle_state: AccountInfo<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RollRound<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(mut, seeds = [Series::SEED, amm.key().as_ref()], bump = series.bump)]
+    pub series: Account<'info, Series>,
+
+    /// CHECK: must be the series feed; owner and layout checked by the market's oracle adapter
+    #[account(address = series.oracle_state)]
+    pub oracle_state: AccountInfo<'info>,
+
+    /// CHECK: SOL vault PDA; its balance seeds the next round's vault_e6
+    #[account(mut, seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoundResult::SPACE,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &amm.round_id.to_le_bytes()],
+        bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
+
+    /// Cranker (anyone) - pays rent for the RoundResult
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleMarket<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoundResult::SPACE,
+        seeds = [RoundResult::SEED, amm.key().as_ref(), &amm.round_id.to_le_bytes()],
+        bump
+    )]
+    pub round_result: Account<'info, RoundResult>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()], bump = amm.vault_sol_bump)]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// Owner of the position (session wallet) - must sign
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Master wallet (Backpack wallet) - receives vault balance
+    /// CHECK: verified against old_position.master_wallet during migration
+    #[account(mut)]
+    pub master_wallet: UncheckedAccount<'info>,
+
+    /// Old position account (97 bytes) - will be closed
+    /// CHECK: We manually deserialize this account since it uses old layout
+    #[account(
+        mut,
+        seeds = [Position::SEED, amm.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub old_position: UncheckedAccount<'info>,
+
+    /// User vault PDA - holds the vault balance
+    /// CHECK: Derived from old_position, used to transfer funds
+    #[account(mut)]
+    pub user_vault: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GrowPosition<'info> {
+    #[account(seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// CHECK: Only seeds the position PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// 901-byte position whose tail fields don't fit next to a full nonce list
+    /// CHECK: May not deserialize until grown; the handler checks owner and discriminator
+    #[account(
+        mut,
+        seeds = [Position::SEED, amm.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub position: UncheckedAccount<'info>,
+
+    /// Pays the rent for the extra bytes
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAmm<'info> {
+    /// AMM created before the round, claim, insurance and oracle fields were appended
+    /// CHECK: The old layout can't be deserialized; the handler checks owner and discriminator
+    #[account(mut, seeds = [Amm::SEED], bump)]
+    pub amm: UncheckedAccount<'info>,
+
+    /// Pays the rent for the extra bytes
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(order: LimitOrder)]
+pub struct ExecuteLimitOrder<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    #[account(
+        mut,
+        seeds = [Position::SEED, amm.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+
+    /// Replay guard for this order's nonce, or its OCO group (created by the first fill)
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        space = 8 + OrderFill::SPACE,
+        seeds = [order.fill_seed(), position.key().as_ref(), &order.fill_id().to_le_bytes()],
+        bump
+    )]
+    pub order_fill: Account<'info, OrderFill>,
+
+    /// CHECK: the order's own [order_fill, position, nonce] state (may not exist). Same account as
+    /// order_fill unless the order is in an OCO group, where it carries a cancel_order_nonce.
+    #[account(seeds = [OrderFill::SEED, position.key().as_ref(), &order.nonce.to_le_bytes()], bump)]
+    pub nonce_fill: UncheckedAccount<'info>,
+
+    /// CHECK: User vault PDA (system-owned, holds user's SOL)
+    #[account(
+        mut,
+        seeds = [Position::USER_VAULT_SEED, position.key().as_ref()],
         bump = position.vault_bump
     )]
     pub user_vault: AccountInfo<'info>,
@@ -697,6 +1543,9 @@ pub struct ExecuteLimitOrder<'info> {
     )]
     pub vault_sol: UncheckedAccount<'info>,
 
+    /// CHECK: owner and layout checked by the market's oracle adapter (trading lockout)
+    pub oracle_state: UncheckedAccount<'info>,
+
     /// CHECK: User whose order is being executed (order owner, not a signer, not modified)
     pub user: UncheckedAccount<'info>,
 
@@ -708,10 +1557,15 @@ pub struct ExecuteLimitOrder<'info> {
     #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
     pub instructions: UncheckedAccount<'info>,
 
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(nonce: u64)]
 pub struct CancelOrderNonce<'info> {
     #[account(
         mut,
@@ -719,8 +1573,100 @@ pub struct CancelOrderNonce<'info> {
     )]
     pub position: Account<'info, Position>,
 
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OrderFill::SPACE,
+        seeds = [OrderFill::SEED, position.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub order_fill: Account<'info, OrderFill>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(group_id: u64)]
+pub struct CancelOrderGroup<'info> {
+    #[account(
+        mut,
+        has_one = owner @ ReaderError::NotOwner
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OrderFill::SPACE,
+        seeds = [OrderFill::GROUP_SEED, position.key().as_ref(), &group_id.to_le_bytes()],
+        bump
+    )]
+    pub order_fill: Account<'info, OrderFill>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAllOrders<'info> {
+    #[account(
+        mut,
+        has_one = owner @ ReaderError::NotOwner
+    )]
+    pub position: Account<'info, Position>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOrderFill<'info> {
+    #[account(mut, close = payer)]
+    pub order_fill: Account<'info, OrderFill>,
+
+    /// CHECK: rent refund goes back to whoever created the guard
+    #[account(mut, address = order_fill.payer)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Batch keeper fill. remaining_accounts: (position, user_vault, order_fill, nonce_fill) per order.
+#[derive(Accounts)]
+pub struct ExecuteLimitOrders<'info> {
+    #[account(mut, seeds = [Amm::SEED], bump = amm.bump)]
+    pub amm: Account<'info, Amm>,
+
+    /// CHECK: writable lamport recipient for fees; address checked against `amm.fee_dest`.
+    #[account(mut, address = amm.fee_dest)]
+    pub fee_dest: UncheckedAccount<'info>,
+
+    /// CHECK: writable SOL vault PDA (system-owned, 0 space) used as lamports pool.
+    #[account(
+        mut,
+        seeds = [Amm::VAULT_SOL_SEED, amm.key().as_ref()],
+        bump = amm.vault_sol_bump
+    )]
+    pub vault_sol: UncheckedAccount<'info>,
+
+    /// CHECK: owner and layout checked by the market's oracle adapter (trading lockout)
+    pub oracle_state: UncheckedAccount<'info>,
+
+    /// Keeper executing the orders (receives fees, pays rent for new order state)
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    /// CHECK: insurance fund PDA (system-owned, 0 space)
+    #[account(mut, seeds = [Amm::INSURANCE_SEED, amm.key().as_ref()], bump)]
+    pub insurance: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 
@@ -764,6 +1710,7 @@ pub mod cpi_oracle {
         amm.q_yes = 0;
         amm.q_no  = 0;
         amm.fees = 0;
+        amm.volume_e6 = 0;
 
         // CHANGED: Keep vault funds from previous market cycle
         // Read existing vault balance and sync vault_e6 accounting
@@ -786,9 +1733,25 @@ pub mod cpi_oracle {
 
         // Init market timing (0 = not set, to be set by external bot)
         amm.market_end_slot = 0;
-
-        msg!("✅ INIT: b={} (1e-6), fee_bps={}, status=Open, fee_dest={}, vault_e6={} ({} lamports carried over)",
-             b, fee_bps, amm.fee_dest, amm.vault_e6, vault_lamports);
+        amm.settle_grace_secs = DEFAULT_SETTLE_GRACE_SECS;
+        amm.claim_window_secs = DEFAULT_CLAIM_WINDOW_SECS;
+        amm.insurance_bps = DEFAULT_INSURANCE_BPS;
+        amm.oracle_kind = ORACLE_KIND_TRIPLET;
+        amm.oracle_max_spread_bps = DEFAULT_ORACLE_MAX_SPREAD_BPS;
+        amm.oracle_max_age_secs = ORACLE_MAX_AGE_SECS;
+        amm.lockout_secs = TRADING_LOCKOUT_SECONDS;
+        amm.asset = ASSET_BTC;
+
+        // New round: positions stamped with an older round_id reset on their next trade/redeem
+        let counter = &mut ctx.accounts.round_counter;
+        counter.bump = ctx.bumps.round_counter;
+        amm.round_id = counter.next_epoch();
+        amm.liability_e6 = 0;
+        amm.reserved_e6 = 0;
+        amm.iou_e6 = 0;
+
+        msg!("✅ INIT: round={} b={} (1e-6), fee_bps={}, status=Open, fee_dest={}, vault_e6={} ({} lamports carried over)",
+             amm.round_id, b, fee_bps, amm.fee_dest, amm.vault_e6, vault_lamports);
         Ok(())
     }
 
@@ -802,6 +1765,7 @@ pub mod cpi_oracle {
         pos.vault_balance_e6 = 0;
         pos.vault_bump = ctx.bumps.user_vault;
         pos.used_nonces = Vec::new();  // Initialize empty nonce list
+        pos.round_id = ctx.accounts.amm.round_id;
         msg!("✅ Position initialized for {} (master: {}, vault: {})",
              pos.owner, pos.master_wallet, ctx.accounts.user_vault.key());
         Ok(())
@@ -975,21 +1939,11 @@ pub mod cpi_oracle {
         let status = amm.status();
         require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::MarketClosed);
 
-        // Check trading lockout using oracle timestamp (45 seconds before market end)
-        if amm.market_end_time > 0 {
-            // Read current time from oracle via CPI (returns milliseconds)
-            let (_, oracle_ts_ms) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-            let oracle_ts = oracle_ts_ms / 1000; // Convert milliseconds to seconds
-            let lockout_start_time = amm.market_end_time - TRADING_LOCKOUT_SECONDS;
-            let time_until_lockout = lockout_start_time.saturating_sub(oracle_ts);
-            let time_until_end = amm.market_end_time.saturating_sub(oracle_ts);
-
-            if oracle_ts >= lockout_start_time {
-                msg!("LOCKED: ts={} lockout={} end={}", oracle_ts, lockout_start_time, amm.market_end_time);
-                return err!(ReaderError::TradingLocked);
-            }
-            // No success logging - saves CU
-        }
+        // Check trading lockout (lockout_secs before market end, on max(clock, oracle ts))
+        check_trading_lockout(amm, &ctx.accounts.oracle_state)?;
+
+        // Shares left over from an earlier round are parked for redemption, not traded
+        sync_position_round(pos, amm)?;
 
         match (side, action) {
             (1, 1) => { // BUY YES - amount is SHARES to buy (not spend)
@@ -1023,7 +1977,7 @@ pub mod cpi_oracle {
                     core::slice::from_ref(&pos.vault_bump),
                 ];
                 transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.vault_sol.to_account_info(), e6_to_lamports(net_e6), &[seeds])?;
-                transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), e6_to_lamports(fee_e6), &[seeds])?;
+                pay_fee(amm, sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), &ctx.accounts.insurance, fee_e6, &[seeds])?;
 
                 // Update vault balance with actual spend
                 pos.vault_balance_e6 -= spend_e6;
@@ -1033,6 +1987,7 @@ pub mod cpi_oracle {
                      spend_e6, desired_shares_e6, amm.q_yes, amm.q_no, amm.vault_e6);
 
                 // Emit trade event for monitoring
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 1, 1, spend_e6, desired_shares_e6, avg_h);
             }
             (2, 1) => { // BUY NO - amount is SHARES to buy (not spend)
@@ -1066,7 +2021,7 @@ pub mod cpi_oracle {
                     core::slice::from_ref(&pos.vault_bump),
                 ];
                 transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.vault_sol.to_account_info(), e6_to_lamports(net_e6), &[seeds])?;
-                transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), e6_to_lamports(fee_e6), &[seeds])?;
+                pay_fee(amm, sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), &ctx.accounts.insurance, fee_e6, &[seeds])?;
 
                 // Update vault balance with actual spend
                 pos.vault_balance_e6 -= spend_e6;
@@ -1076,6 +2031,7 @@ pub mod cpi_oracle {
                      spend_e6, desired_shares_e6, amm.q_yes, amm.q_no, amm.vault_e6);
 
                 // Emit trade event for monitoring
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 2, 1, spend_e6, desired_shares_e6, avg_h);
             }
             (1, 2) => { // SELL YES → pay proceeds to user_vault
@@ -1086,13 +2042,7 @@ pub mod cpi_oracle {
                 let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_yes(amm, sell_e6)?;
 
                 // Check actual vault_sol PDA balance (not the accounting mirror which can drift)
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                msg!("SELL YES COVERAGE CHECK: vault_sol_ACTUAL={:.6} XNT, AMM_vault_e6_accounting={:.6} XNT (drift={:.6}), proceeds_needed={:.6} XNT",
-                     usd(vault_sol_actual_e6), usd(amm.vault_e6), usd(vault_sol_actual_e6 - amm.vault_e6), usd(proceeds_e6));
-
-                // Use ACTUAL vault balance for coverage check, not the drifted accounting mirror
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 // Transfer from vault_sol to user_vault
                 let amm_key = amm.key();
@@ -1112,6 +2062,7 @@ pub mod cpi_oracle {
                      proceeds_e6, sold_e6.round() as i64, amm.q_yes, amm.q_no, amm.vault_e6);
 
                 // Emit trade event for monitoring
+                record_volume(amm, proceeds_e6);
                 emit_trade(amm, 1, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
             }
             (2, 2) => { // SELL NO → pay proceeds to user_vault
@@ -1122,13 +2073,7 @@ pub mod cpi_oracle {
                 let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_no(amm, sell_e6)?;
 
                 // Check actual vault_sol PDA balance (not the accounting mirror which can drift)
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                msg!("SELL NO COVERAGE CHECK: vault_sol_ACTUAL={:.6} XNT, AMM_vault_e6_accounting={:.6} XNT (drift={:.6}), proceeds_needed={:.6} XNT",
-                     usd(vault_sol_actual_e6), usd(amm.vault_e6), usd(vault_sol_actual_e6 - amm.vault_e6), usd(proceeds_e6));
-
-                // Use ACTUAL vault balance for coverage check, not the drifted accounting mirror
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 // Transfer from vault_sol to user_vault
                 let amm_key = amm.key();
@@ -1148,6 +2093,7 @@ pub mod cpi_oracle {
                      proceeds_e6, sold_e6.round() as i64, amm.q_yes, amm.q_no, amm.vault_e6);
 
                 // Emit trade event for monitoring
+                record_volume(amm, proceeds_e6);
                 emit_trade(amm, 2, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
             }
             _ => return err!(ReaderError::BadParam),
@@ -1172,16 +2118,10 @@ pub mod cpi_oracle {
         require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::MarketClosed);
 
         // Check trading lockout (same as regular trade)
-        if amm.market_end_time > 0 {
-            let (_, oracle_ts_ms) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-            let oracle_ts = oracle_ts_ms / 1000;
-            let lockout_start_time = amm.market_end_time - TRADING_LOCKOUT_SECONDS;
-
-            if oracle_ts >= lockout_start_time {
-                msg!("LOCKED: ts={} lockout={} end={}", oracle_ts, lockout_start_time, amm.market_end_time);
-                return err!(ReaderError::TradingLocked);
-            }
-        }
+        check_trading_lockout(amm, &ctx.accounts.oracle_state)?;
+
+        // Shares left over from an earlier round are parked for redemption, not traded
+        sync_position_round(pos, amm)?;
 
         // For BUY: Calculate price per share BEFORE execution, check against limit
         // For SELL: Calculate price per share BEFORE execution, check against limit
@@ -1227,6 +2167,7 @@ pub mod cpi_oracle {
 
                 msg!("✅ GUARDED BUY YES: shares={} spend={} price={:.6} qY={} qN={} vault={}",
                      desired_shares_e6, spend_e6, avg_h, amm.q_yes, amm.q_no, amm.vault_e6);
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 1, 1, spend_e6, desired_shares_e6, avg_h);
             }
             (2, 1) => { // BUY NO with limit
@@ -1267,6 +2208,7 @@ pub mod cpi_oracle {
 
                 msg!("✅ GUARDED BUY NO: shares={} spend={} price={:.6} qY={} qN={} vault={}",
                      desired_shares_e6, spend_e6, avg_h, amm.q_yes, amm.q_no, amm.vault_e6);
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 2, 1, spend_e6, desired_shares_e6, avg_h);
             }
             (1, 2) => { // SELL YES with limit
@@ -1284,9 +2226,7 @@ pub mod cpi_oracle {
                     require!(price_per_share_e6 >= guard.price_limit_e6, ReaderError::PriceLimitNotMet);
                 }
 
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 let amm_key = amm.key();
                 let seeds: &[&[u8]] = &[
@@ -1301,6 +2241,7 @@ pub mod cpi_oracle {
 
                 msg!("✅ GUARDED SELL YES: proceeds={} sold={} price={:.6} qY={} qN={} vault={}",
                      proceeds_e6, sold_e6.round() as i64, avg_h, amm.q_yes, amm.q_no, amm.vault_e6);
+                record_volume(amm, proceeds_e6);
                 emit_trade(amm, 1, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
             }
             (2, 2) => { // SELL NO with limit
@@ -1318,9 +2259,7 @@ pub mod cpi_oracle {
                     require!(price_per_share_e6 >= guard.price_limit_e6, ReaderError::PriceLimitNotMet);
                 }
 
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 let amm_key = amm.key();
                 let seeds: &[&[u8]] = &[
@@ -1335,6 +2274,7 @@ pub mod cpi_oracle {
 
                 msg!("✅ GUARDED SELL NO: proceeds={} sold={} price={:.6} qY={} qN={} vault={}",
                      proceeds_e6, sold_e6.round() as i64, avg_h, amm.q_yes, amm.q_no, amm.vault_e6);
+                record_volume(amm, proceeds_e6);
                 emit_trade(amm, 2, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
             }
             _ => return err!(ReaderError::BadParam),
@@ -1398,16 +2338,19 @@ pub mod cpi_oracle {
         require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::MarketClosed);
 
         // Check trading lockout
-        if amm.market_end_time > 0 {
-            let (_, oracle_ts_ms) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-            let oracle_ts = oracle_ts_ms / 1000;
-            let lockout_start_time = amm.market_end_time - TRADING_LOCKOUT_SECONDS;
-
-            if oracle_ts >= lockout_start_time {
-                msg!("LOCKED: ts={} lockout={} end={}", oracle_ts, lockout_start_time, amm.market_end_time);
-                return err!(ReaderError::TradingLocked);
-            }
-        }
+        check_trading_lockout(amm, &ctx.accounts.oracle_state)?;
+
+        // Shares left over from an earlier round are parked for redemption, not traded
+        sync_position_round(pos, amm)?;
+
+        // Reduce-only: clip the request to what brings net exposure back toward zero
+        let amount = if guards.reduce_only {
+            let reducible = reducible_shares(action, side, pos);
+            require!(reducible > 0, ReaderError::NothingToReduce);
+            amount.min(reducible)
+        } else {
+            amount
+        };
 
         // Validate guards and get executable shares (may be less than requested if partial fills enabled)
         let shares_to_execute = validate_advanced_guards(action, side, amount, &guards, amm)?;
@@ -1437,13 +2380,14 @@ pub mod cpi_oracle {
                     core::slice::from_ref(&pos.vault_bump),
                 ];
                 transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.vault_sol.to_account_info(), e6_to_lamports(net_e6), &[seeds])?;
-                transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), e6_to_lamports(fee_e6), &[seeds])?;
+                pay_fee(amm, sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), &ctx.accounts.insurance, fee_e6, &[seeds])?;
 
                 pos.vault_balance_e6 -= spend_e6;
 
                 msg!("ADV BUY YES: requested={} executed={} spend={} qY={} qN={} vault={}",
                      amount, shares_to_execute, spend_e6, amm.q_yes, amm.q_no, amm.vault_e6);
 
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 1, 1, spend_e6, shares_to_execute, avg_h);
             }
             (2, 1) => { // BUY NO
@@ -1469,13 +2413,14 @@ pub mod cpi_oracle {
                     core::slice::from_ref(&pos.vault_bump),
                 ];
                 transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.vault_sol.to_account_info(), e6_to_lamports(net_e6), &[seeds])?;
-                transfer_sol_signed(sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), e6_to_lamports(fee_e6), &[seeds])?;
+                pay_fee(amm, sys, &ctx.accounts.user_vault.to_account_info(), &ctx.accounts.fee_dest.to_account_info(), &ctx.accounts.insurance, fee_e6, &[seeds])?;
 
                 pos.vault_balance_e6 -= spend_e6;
 
                 msg!("ADV BUY NO: requested={} executed={} spend={} qY={} qN={} vault={}",
                      amount, shares_to_execute, spend_e6, amm.q_yes, amm.q_no, amm.vault_e6);
 
+                record_volume(amm, spend_e6);
                 emit_trade(amm, 2, 1, spend_e6, shares_to_execute, avg_h);
             }
             (1, 2) => { // SELL YES
@@ -1485,9 +2430,7 @@ pub mod cpi_oracle {
 
                 let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_yes(amm, sell_e6)?;
 
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 let amm_key = amm.key();
                 let seeds: &[&[u8]] = &[
@@ -1500,6 +2443,8 @@ pub mod cpi_oracle {
                 pos.vault_balance_e6 += proceeds_e6;
                 pos.yes_shares_e6 = pos.yes_shares_e6.saturating_sub(sold_e6.round() as i64);
 
+                record_volume(amm, proceeds_e6);
+
                 msg!("ADV SELL YES: requested={} executed={} proceeds={} qY={} qN={} vault={}",
                      amount, sold_e6.round() as i64, proceeds_e6, amm.q_yes, amm.q_no, amm.vault_e6);
             }
@@ -1510,9 +2455,7 @@ pub mod cpi_oracle {
 
                 let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_no(amm, sell_e6)?;
 
-                let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-                let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-                require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+                ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
                 let amm_key = amm.key();
                 let seeds: &[&[u8]] = &[
@@ -1525,6 +2468,8 @@ pub mod cpi_oracle {
                 pos.vault_balance_e6 += proceeds_e6;
                 pos.no_shares_e6 = pos.no_shares_e6.saturating_sub(sold_e6.round() as i64);
 
+                record_volume(amm, proceeds_e6);
+
                 msg!("ADV SELL NO: requested={} executed={} proceeds={} qY={} qN={} vault={}",
                      amount, sold_e6.round() as i64, proceeds_e6, amm.q_yes, amm.q_no, amm.vault_e6);
             }
@@ -1543,27 +2488,11 @@ pub mod cpi_oracle {
         let status = amm.status();
         require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::MarketClosed);
 
-        // Check trading lockout using oracle timestamp (45 seconds before market end)
-        if amm.market_end_time > 0 {
-            // Read current time from oracle via CPI (returns milliseconds)
-            let (_, oracle_ts_ms) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-            let oracle_ts = oracle_ts_ms / 1000; // Convert milliseconds to seconds
-            let lockout_start_time = amm.market_end_time - TRADING_LOCKOUT_SECONDS;
-            let time_until_lockout = lockout_start_time.saturating_sub(oracle_ts);
-            let time_until_end = amm.market_end_time.saturating_sub(oracle_ts);
-
-            if oracle_ts >= lockout_start_time {
-                msg!("🚫 CLOSE POSITION LOCKED: Oracle time {} >= lockout start {} (market ends at {})",
-                     oracle_ts, lockout_start_time, amm.market_end_time);
-                msg!("   Time until market end: {} seconds", time_until_end);
-                return err!(ReaderError::TradingLocked);
-            } else {
-                msg!("✅ Close Position Lockout Check PASSED: Oracle time {} < lockout start {}",
-                     oracle_ts, lockout_start_time);
-                msg!("   Time until lockout: {} seconds | Time until market end: {} seconds",
-                     time_until_lockout, time_until_end);
-            }
-        }
+        // Check trading lockout (lockout_secs before market end, on max(clock, oracle ts))
+        check_trading_lockout(amm, &ctx.accounts.oracle_state)?;
+
+        // Shares left over from an earlier round are parked for redemption, not traded
+        sync_position_round(pos, amm)?;
 
         msg!("🔒 [CLOSE POSITION] Starting position closure");
         msg!("   YES shares: {:.6} ({} e6)", pos.yes_shares_e6 as f64 / 1e6, pos.yes_shares_e6);
@@ -1579,13 +2508,7 @@ pub mod cpi_oracle {
 
             let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_yes(amm, sell_yes_e6)?;
 
-            // Check actual vault_sol PDA balance
-            let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-            let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-            msg!("   Coverage check: vault={:.6} XNT, needed={:.6} XNT",
-                 usd(vault_sol_actual_e6), usd(proceeds_e6));
-
-            require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+            ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
             // Transfer from vault_sol to user_vault
             let amm_key = amm.key();
@@ -1601,6 +2524,7 @@ pub mod cpi_oracle {
 
             msg!("✅ Sold {:.6} YES shares for {:.6} XNT (avg price: {:.6})",
                  sold_e6, usd(proceeds_e6), avg_h);
+            record_volume(amm, proceeds_e6);
             emit_trade(amm, 1, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
         }
 
@@ -1611,13 +2535,7 @@ pub mod cpi_oracle {
 
             let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_no(amm, sell_no_e6)?;
 
-            // Check actual vault_sol PDA balance
-            let vault_sol_actual_lamports = ctx.accounts.vault_sol.lamports();
-            let vault_sol_actual_e6 = lamports_to_e6(vault_sol_actual_lamports);
-            msg!("   Coverage check: vault={:.6} XNT, needed={:.6} XNT",
-                 usd(vault_sol_actual_e6), usd(proceeds_e6));
-
-            require!(vault_sol_actual_e6 >= proceeds_e6, ReaderError::NoCoverage);
+            ensure_sell_coverage(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, sys, proceeds_e6, ctx.bumps.insurance)?;
 
             // Transfer from vault_sol to user_vault
             let amm_key = amm.key();
@@ -1633,6 +2551,7 @@ pub mod cpi_oracle {
 
             msg!("✅ Sold {:.6} NO shares for {:.6} XNT (avg price: {:.6})",
                  sold_e6, usd(proceeds_e6), avg_h);
+            record_volume(amm, proceeds_e6);
             emit_trade(amm, 2, 2, proceeds_e6, sold_e6.round() as i64, avg_h);
         }
 
@@ -1648,441 +2567,857 @@ pub mod cpi_oracle {
         Ok(())
     }
 
-    // ---------- STOP ----------
-    pub fn stop_market(ctx: Context<AdminOnly>) -> Result<()> {
+    // ---------- STOP ----------
+    pub fn stop_market(ctx: Context<AdminOnly>) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        let status = amm.status();
+        require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::WrongState);
+        amm.status = MarketStatus::Stopped as u8;
+        msg!("⏹️  Market STOPPED");
+        Ok(())
+    }
+
+    /// Migrate old position account (97 bytes) to new layout (901 bytes)
+    /// Reads vault balance from old layout, transfers to master wallet, closes old account
+    pub fn migrate_position_v1_to_v2(ctx: Context<MigratePosition>) -> Result<()> {
+        let position_info = ctx.accounts.old_position.to_account_info();
+        let data = position_info.data.borrow();
+
+        // Verify this is the old 97-byte layout
+        require!(data.len() == 97, ReaderError::BadParam);
+
+        // Old Position layout (97 bytes):
+        // discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + master_wallet(32) + vault_balance_e6(8) + vault_bump(1)
+
+        // Parse fields from old layout
+        let owner_bytes = &data[8..40];
+        let yes_shares_bytes = &data[40..48];
+        let no_shares_bytes = &data[48..56];
+        let master_wallet_bytes = &data[56..88];
+        let vault_balance_bytes = &data[88..96];
+        let vault_bump = data[96]; // Last byte
+
+        let owner = Pubkey::try_from(owner_bytes).map_err(|_| error!(ReaderError::BadParam))?;
+        let yes_shares_e6 = i64::from_le_bytes(yes_shares_bytes.try_into().unwrap());
+        let no_shares_e6 = i64::from_le_bytes(no_shares_bytes.try_into().unwrap());
+        let master_wallet = Pubkey::try_from(master_wallet_bytes).map_err(|_| error!(ReaderError::BadParam))?;
+        let vault_balance_e6 = i64::from_le_bytes(vault_balance_bytes.try_into().unwrap());
+
+        // Verify owner matches
+        require_keys_eq!(owner, ctx.accounts.owner.key(), ReaderError::NotOwner);
+        require_keys_eq!(master_wallet, ctx.accounts.master_wallet.key(), ReaderError::NotOwner);
+
+        msg!("📦 Migrating position for {}", owner);
+        msg!("   Old layout: 97 bytes");
+        msg!("   YES shares: {}", yes_shares_e6);
+        msg!("   NO shares: {}", no_shares_e6);
+        msg!("   Vault balance: {} e6 (${:.6})", vault_balance_e6, vault_balance_e6 as f64 / 1e6);
+        msg!("   Master wallet: {}", master_wallet);
+
+        // Warn if user has shares
+        if yes_shares_e6 != 0 || no_shares_e6 != 0 {
+            msg!("⚠️  WARNING: Position has shares! YES={} NO={}", yes_shares_e6, no_shares_e6);
+            msg!("   These shares will be LOST after migration!");
+        }
+
+        // Transfer vault balance to master wallet if any
+        if vault_balance_e6 > 0 {
+            let vault_lamports = (vault_balance_e6 as u64) * LAMPORTS_PER_E6;
+
+            msg!("💸 Transferring {} lamports ({:.6} XNT) from user_vault to master_wallet",
+                 vault_lamports, vault_balance_e6 as f64 / 1e6);
+
+            // Transfer from user_vault PDA to master_wallet
+            let pos_key = ctx.accounts.old_position.key();
+            let seeds: &[&[u8]] = &[
+                Position::USER_VAULT_SEED,
+                pos_key.as_ref(),
+                &[vault_bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.user_vault.to_account_info(),
+                    to: ctx.accounts.master_wallet.to_account_info(),
+                },
+                signer_seeds,
+            );
+            anchor_lang::system_program::transfer(cpi_ctx, vault_lamports)?;
+
+            msg!("✅ Vault balance transferred to master wallet");
+        } else {
+            msg!("   No vault balance to transfer");
+        }
+
+        // Close the old position account - return rent to owner
+        let position_lamports = position_info.lamports();
+
+        msg!("🗑️  Closing old position account, returning {} lamports rent to {}",
+             position_lamports, owner);
+
+        // Transfer lamports from position to owner
+        **position_info.try_borrow_mut_lamports()? = 0;
+        **ctx.accounts.owner.to_account_info().try_borrow_mut_lamports()? += position_lamports;
+
+        msg!("✅ Migration complete! User can now call init_position to create new 901-byte account");
+
+        Ok(())
+    }
+
+    /// Grow a 901-byte position to the current size so its tail fields (round, carry, IOU,
+    /// min_nonce) no longer share the legacy 100-nonce allocation. New bytes are zeroed: a position
+    /// holding 94+ nonces never had a tail written, so it starts at round 0 with no carry, IOU or
+    /// min_nonce. Permissionless and idempotent: the payer only covers the extra rent.
+    pub fn migrate_position_v2_to_v3(ctx: Context<GrowPosition>) -> Result<()> {
+        let pos_ai = ctx.accounts.position.to_account_info();
+        let old_len = pos_ai.data_len();
+        let grown = grow_account(
+            &pos_ai,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program,
+            Position::DISCRIMINATOR,
+            8 + Position::SPACE,
+        )?;
+        if grown {
+            msg!("📦 Position {} grown: {} -> {} bytes", pos_ai.key(), old_len, 8 + Position::SPACE);
+        } else {
+            msg!("Position already at the current layout ({} bytes)", old_len);
+        }
+        Ok(())
+    }
+
+    /// Grow a v6 AMM (152 bytes, laid out up to market_end_time) to the current layout in place,
+    /// so positions and vaults keyed by its address keep working. Appended fields start zeroed,
+    /// which is their default (insurance_bps gets init_amm's). round_id stays 0: the live round
+    /// is the one positions created before round stamping (round_id 0) are trading in.
+    /// Permissionless and idempotent: the payer only covers the extra rent.
+    pub fn migrate_amm(ctx: Context<MigrateAmm>) -> Result<()> {
+        let amm_ai = ctx.accounts.amm.to_account_info();
+        let old_len = amm_ai.data_len();
+        let grown = grow_account(
+            &amm_ai,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program,
+            Amm::DISCRIMINATOR,
+            8 + Amm::SPACE,
+        )?;
+        if !grown {
+            msg!("AMM already at the current layout ({} bytes)", old_len);
+            return Ok(());
+        }
+
+        let mut data = amm_ai.try_borrow_mut_data()?;
+        let mut amm = Amm::try_deserialize(&mut &data[..])?;
+        amm.insurance_bps = DEFAULT_INSURANCE_BPS;
+        amm.try_serialize(&mut &mut data[..])?;
+
+        msg!("📦 AMM migrated: {} -> {} bytes", old_len, 8 + Amm::SPACE);
+        Ok(())
+    }
+
+
+
+    // ---------- SETTLE (manual winner: 1=YES, 2=NO) ----------
+    pub fn settle_market(ctx: Context<SettleMarket>, winner: u8) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        require!(amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
+        require!(amm.winner != WINNER_VOID, ReaderError::MarketVoided);
+        require!(amm.winner == 0, ReaderError::AlreadySettled);
+        require!(winner == 1 || winner == 2, ReaderError::BadParam);
+
+        // Top the vault up to 1.0 per winning share from the insurance fund if it can
+        let w = if winner == 1 { amm.q_yes } else { amm.q_no };
+        draw_insurance(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, &ctx.accounts.system_program,
+                       w.max(0) - amm.vault_e6, ctx.bumps.insurance, INSURANCE_DRAW_SETTLE)?;
+        let pps_e6 = apply_settlement(amm, winner);
+        // Market stays in STOPPED state - users can redeem, then admin can reinit to PREMARKET
+
+        record_round_result(&mut ctx.accounts.round_result, amm, ctx.bumps.round_result)?;
+
+        msg!("✅ SETTLED winner={}  W={}  vault=${:.6}  pps={:.6} - Market stays STOPPED for redemptions",
+             winner, amm.w_total_e6, usd(amm.vault_e6), (pps_e6 as f64)/1_000_000.0);
+        Ok(())
+    }
+
+pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
+    let a = ctx.accounts;
+    let pay = Payout {
+        vault_sol: &a.vault_sol.to_account_info(),
+        user_vault: &a.user_vault.to_account_info(),
+        insurance: &a.insurance.to_account_info(),
+        insurance_bump: ctx.bumps.insurance,
+        sys: &a.system_program,
+    };
+    redeem_claim(&mut a.amm, &mut a.pos, &a.round_result.to_account_info(), &pay, "REDEEM")
+}
+
+    // ---------- ADMIN REDEEM (force redeem on behalf of user) ----------
+    pub fn admin_redeem(ctx: Context<AdminRedeem>) -> Result<()> {
+        // Only fee_dest can call this
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+
+        let a = ctx.accounts;
+        msg!("ADMIN_REDEEM user={}", a.user.key());
+        let pay = Payout {
+            vault_sol: &a.vault_sol.to_account_info(),
+            user_vault: &a.user_vault.to_account_info(),
+            insurance: &a.insurance.to_account_info(),
+            insurance_bump: ctx.bumps.insurance,
+            sys: &a.system_program,
+        };
+        redeem_claim(&mut a.amm, &mut a.pos, &a.round_result.to_account_info(), &pay, "ADMIN_REDEEM")
+    }
+
+    // ---------- CRANK REDEEM (permissionless batch redeem) ----------
+    /// Redeems every (pos, user_vault) pair in `remaining_accounts` whose claim is on `round_id`:
+    /// winners are paid into their user_vault, losing shares are wiped. The cranker earns
+    /// CRANK_BOUNTY_E6 out of each payout (only while the user_vault stays rent-exempt).
+    /// Positions with nothing to claim on `round_id` are skipped so a stale list can't fail the batch.
+    pub fn crank_redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankRedeem<'info>>,
+        round_id: u64,
+    ) -> Result<()> {
+        let rem = ctx.remaining_accounts;
+        require!(
+            !rem.is_empty() && rem.len() % 2 == 0 && rem.len() / 2 <= CRANK_MAX_POSITIONS,
+            ReaderError::BadCrankAccounts
+        );
+
+        let a = ctx.accounts;
+        let amm_key = a.amm.key();
+        let cranker = a.cranker.to_account_info();
+        let round_result = a.round_result.to_account_info();
+        let pay_base = Payout {
+            vault_sol: &a.vault_sol.to_account_info(),
+            user_vault: &cranker, // replaced per position
+            insurance: &a.insurance.to_account_info(),
+            insurance_bump: ctx.bumps.insurance,
+            sys: &a.system_program,
+        };
+        let rent_min = Rent::get()?.minimum_balance(0);
+
+        let (mut settled, mut paid, mut bounty_lamports) = (0u32, 0u32, 0u64);
+        for pair in rem.chunks_exact(2) {
+            let (pos_ai, user_vault) = (&pair[0], &pair[1]);
+            require!(pos_ai.is_writable && user_vault.is_writable, ReaderError::BadCrankAccounts);
+            let mut pos: Account<'info, Position> = Account::try_from(pos_ai)?;
+
+            // Same PDAs the Redeem context checks through its seeds constraints
+            let (pos_key, _) = Pubkey::find_program_address(
+                &[Position::SEED, amm_key.as_ref(), pos.owner.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(pos_ai.key(), pos_key, ReaderError::BadCrankAccounts);
+            let vault_bump = [pos.vault_bump];
+            let vault_seeds: &[&[u8]] = &[Position::USER_VAULT_SEED, pos_key.as_ref(), &vault_bump];
+            let vault_key = Pubkey::create_program_address(vault_seeds, &crate::ID)
+                .map_err(|_| error!(ReaderError::BadCrankAccounts))?;
+            require_keys_eq!(user_vault.key(), vault_key, ReaderError::BadCrankAccounts);
+
+            let has_shares = pos.has_carry() || pos.yes_shares_e6 != 0 || pos.no_shares_e6 != 0;
+            if pos.claim_round_id() != round_id || !has_shares {
+                continue;
+            }
+
+            let before_e6 = pos.vault_balance_e6;
+            let pay = Payout { user_vault, ..pay_base };
+            redeem_claim(&mut a.amm, &mut pos, &round_result, &pay, "CRANK_REDEEM")?;
+            settled += 1;
+
+            let paid_e6 = pos.vault_balance_e6.saturating_sub(before_e6);
+            if paid_e6 > 0 {
+                paid += 1;
+                let spare = lamports_to_e6(user_vault.lamports().saturating_sub(rent_min));
+                let bounty_e6 = CRANK_BOUNTY_E6.min(paid_e6).min(spare);
+                if bounty_e6 > 0 {
+                    transfer_sol_signed(&a.system_program, user_vault, &cranker, e6_to_lamports(bounty_e6), &[vault_seeds])?;
+                    pos.vault_balance_e6 = pos.vault_balance_e6.saturating_sub(bounty_e6);
+                    bounty_lamports = bounty_lamports.saturating_add(e6_to_lamports(bounty_e6));
+                }
+            }
+            pos.exit(&crate::ID)?;
+        }
+
+        emit!(CrankRedeemed {
+            round_id,
+            cranker: cranker.key(),
+            positions: settled,
+            paid,
+            bounty_lamports,
+        });
+        msg!("🧹 CRANK_REDEEM round={} settled={} paid={} bounty={} lamports → {}",
+             round_id, settled, paid, bounty_lamports, cranker.key());
+        Ok(())
+    }
+
+
+    // ---------- CLOSE AMM (new) ----------
+    pub fn close_amm(ctx: Context<CloseAmm>) -> Result<()> {
+        // Unclaimed winnings live only in the AMM's books; use reopen_amm to carry them instead
+        let amm = &ctx.accounts.amm;
+        require!(amm.liability_e6 <= 0 && amm.reserved_e6 <= 0 && amm.iou_e6 <= 0, ReaderError::LiabilitiesOutstanding);
+        msg!("🧹 AMM account closed to recipient {}", ctx.accounts.recipient.key());
+        Ok(())
+    }
+
+    // ---------- REOPEN AMM (in-place reinit that keeps unclaimed winnings redeemable) ----------
+    pub fn reopen_amm(ctx: Context<ReopenAmm>, b: i64, fee_bps: u16) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(b > 0, ReaderError::BadParam);
+
+        let vault_lamports = ctx.accounts.vault_sol.to_account_info().lamports();
+        let amm = &mut ctx.accounts.amm;
+        require!(amm.winner != 0, ReaderError::WrongState);
+
+        let prev_round = amm.round_id;
+        reset_round(amm, b, fee_bps, vault_lamports)?;
+
+        msg!("✅ REOPEN: round {} -> {} b={} fee_bps={} vault_e6={} reserved_e6={} ({} lamports in vault)",
+             prev_round, amm.round_id, b, fee_bps, amm.vault_e6, amm.reserved_e6, vault_lamports);
+        Ok(())
+    }
+
+    // ---------- NEW: SNAPSHOT the start BTC price ----------
+    pub fn snapshot_start(ctx: Context<SnapshotStart>) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        require!(amm.status() == MarketStatus::Premarket, ReaderError::WrongState);
+        require!(amm.start_price_e6 == 0, ReaderError::AlreadySnapshotted);
+
+        let OracleReading { price_e6, conf_e6, ts_ms: ts } = read_oracle(&ctx.accounts.oracle_state, amm, amm.asset())?;
+
+        amm.start_price_e6 = price_e6;
+        amm.start_ts = ts;
+
+        // Transition from PREMARKET to OPEN
+        amm.status = MarketStatus::Open as u8;
+
+        msg!("📸 SNAPSHOT start asset={} price=${:.6} ±{:.6} (ts={}) - Market now OPEN",
+             amm.asset(), (price_e6 as f64)/1e6, (conf_e6 as f64)/1e6, ts);
+        Ok(())
+    }
+
+    // ---------- SET MARKET END TIME (for trading lockout) ----------
+    pub fn set_market_end_slot(ctx: Context<AdminOnly>, market_end_slot: u64, market_end_time: i64) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        require!(market_end_time > 0, ReaderError::BadParam);
+
+        amm.market_end_slot = market_end_slot;  // Keep for backwards compatibility
+        amm.market_end_time = market_end_time;
+
+        msg!("⏰ Market end time set to: {} (unix timestamp)", market_end_time);
+        msg!("   Trading locks at: {} ({} seconds before close)", market_end_time - amm.lockout_secs(), amm.lockout_secs());
+        msg!("   Market end slot (legacy): {}", market_end_slot);
+        Ok(())
+    }
+
+    // ---------- NEW: settle by comparing current BTC to snapshot ----------
+    /// ge_wins_yes=true  => YES wins on tie (>=). false => YES only if strictly greater.
+    pub fn settle_by_oracle(ctx: Context<SettleByOracle>, ge_wins_yes: bool) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+
+        require!(amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
+        require!(amm.winner != WINNER_VOID, ReaderError::MarketVoided);
+        require!(amm.winner == 0, ReaderError::AlreadySettled);
+        require!(amm.start_price_e6 != 0, ReaderError::NotSnapshotted);
+
+        let OracleReading { price_e6: curr_e6, ts_ms: ts, .. } = read_oracle(&ctx.accounts.oracle_state, amm, amm.asset())?;
+
+        let start = amm.start_price_e6;
+        let winner = oracle_winner(curr_e6, start, ge_wins_yes);
+
+        amm.settle_price_e6 = curr_e6;
+        amm.settle_ts = ts;
+
+        // Top the vault up to 1.0 per winning share from the insurance fund if it can
+        let w = if winner == 1 { amm.q_yes } else { amm.q_no };
+        draw_insurance(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, &ctx.accounts.system_program,
+                       w.max(0) - amm.vault_e6, ctx.bumps.insurance, INSURANCE_DRAW_SETTLE)?;
+
+        // Reuse settlement math
+        let pps_e6 = apply_settlement(amm, winner);
+        // Market stays in STOPPED state - users can redeem, then admin can reinit to PREMARKET
+
+        record_round_result(&mut ctx.accounts.round_result, amm, ctx.bumps.round_result)?;
+
+        msg!(
+          "✅ SETTLED_BY_ORACLE winner={} start=${:.6}@{} curr=${:.6}@{}  W={}  vault=${:.6}  pps={:.6} - Market stays STOPPED for redemptions",
+          winner,
+          (start as f64)/1e6, amm.start_ts,
+          (curr_e6 as f64)/1e6, ts,
+          amm.w_total_e6,
+          (amm.vault_e6 as f64)/1e6,
+          (pps_e6 as f64)/1e6
+        );
+        Ok(())
+    }
+
+    // ---------- SET SETTLE GRACE (how long the settler has before the market becomes voidable) ----------
+    pub fn set_settle_grace(ctx: Context<AdminConfig>, settle_grace_secs: i64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(settle_grace_secs > 0, ReaderError::BadParam);
+
         let amm = &mut ctx.accounts.amm;
-        let status = amm.status();
-        require!(status == MarketStatus::Premarket || status == MarketStatus::Open, ReaderError::WrongState);
-        amm.status = MarketStatus::Stopped as u8;
-        msg!("⏹️  Market STOPPED");
+        amm.settle_grace_secs = settle_grace_secs;
+
+        msg!("⏳ Settle grace set to {} seconds after market end", settle_grace_secs);
         Ok(())
     }
 
-
-    pub fn wipe_position(ctx: Context<WipePosition>) -> Result<()> {
-        // simple admin guard: only the configured fee_dest can wipe positions
+    // ---------- SET CLAIM WINDOW (applies to rounds settled from now on) ----------
+    pub fn set_claim_window(ctx: Context<AdminConfig>, claim_window_secs: i64) -> Result<()> {
         require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(claim_window_secs > 0, ReaderError::BadParam);
 
-        let pos = &mut ctx.accounts.pos;
-        pos.yes_shares_e6 = 0;
-        pos.no_shares_e6  = 0;
+        let amm = &mut ctx.accounts.amm;
+        amm.claim_window_secs = claim_window_secs;
 
-        msg!("🧹 wiped position for {}", ctx.accounts.owner.key());
+        msg!("⏳ Claim window set to {} seconds after settlement", claim_window_secs);
         Ok(())
     }
 
-    /// Migrate old position account (97 bytes) to new layout (901 bytes)
-    /// Reads vault balance from old layout, transfers to master wallet, closes old account
-    pub fn migrate_position_v1_to_v2(ctx: Context<MigratePosition>) -> Result<()> {
-        let position_info = ctx.accounts.old_position.to_account_info();
-        let data = position_info.data.borrow();
+    // ---------- INSURANCE FUND ----------
+    pub fn set_insurance_bps(ctx: Context<AdminConfig>, insurance_bps: u16) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(insurance_bps <= 10_000, ReaderError::BadParam);
 
-        // Verify this is the old 97-byte layout
-        require!(data.len() == 97, ReaderError::BadParam);
+        let amm = &mut ctx.accounts.amm;
+        amm.insurance_bps = insurance_bps;
 
-        // Old Position layout (97 bytes):
-        // discriminator(8) + owner(32) + yes_shares(8) + no_shares(8) + master_wallet(32) + vault_balance_e6(8) + vault_bump(1)
+        msg!("🛟 Insurance slice set to {} bps of fees", insurance_bps);
+        Ok(())
+    }
 
-        // Parse fields from old layout
-        let owner_bytes = &data[8..40];
-        let yes_shares_bytes = &data[40..48];
-        let no_shares_bytes = &data[48..56];
-        let master_wallet_bytes = &data[56..88];
-        let vault_balance_bytes = &data[88..96];
-        let vault_bump = data[96]; // Last byte
+    /// Anyone may top up the fund directly (operator seeding, outside backstops).
+    pub fn fund_insurance(ctx: Context<FundInsurance>, amount_lamports: u64) -> Result<()> {
+        require!(amount_lamports > 0, ReaderError::BadParam);
 
-        let owner = Pubkey::try_from(owner_bytes).map_err(|_| error!(ReaderError::BadParam))?;
-        let yes_shares_e6 = i64::from_le_bytes(yes_shares_bytes.try_into().unwrap());
-        let no_shares_e6 = i64::from_le_bytes(no_shares_bytes.try_into().unwrap());
-        let master_wallet = Pubkey::try_from(master_wallet_bytes).map_err(|_| error!(ReaderError::BadParam))?;
-        let vault_balance_e6 = i64::from_le_bytes(vault_balance_bytes.try_into().unwrap());
+        let insurance = ctx.accounts.insurance.to_account_info();
+        transfer_sol(
+            &ctx.accounts.system_program,
+            &ctx.accounts.funder.to_account_info(),
+            &insurance,
+            amount_lamports,
+        )?;
 
-        // Verify owner matches
-        require_keys_eq!(owner, ctx.accounts.owner.key(), ReaderError::NotOwner);
-        require_keys_eq!(master_wallet, ctx.accounts.master_wallet.key(), ReaderError::NotOwner);
+        emit!(InsuranceTopUp {
+            round_id: ctx.accounts.amm.round_id,
+            amount_e6: lamports_to_e6(amount_lamports),
+            from_fees: false,
+            balance_lamports: insurance.lamports(),
+        });
+        msg!("🛟 Insurance funded with {} lamports by {} (balance {})",
+             amount_lamports, ctx.accounts.funder.key(), insurance.lamports());
+        Ok(())
+    }
 
-        msg!("📦 Migrating position for {}", owner);
-        msg!("   Old layout: 97 bytes");
-        msg!("   YES shares: {}", yes_shares_e6);
-        msg!("   NO shares: {}", no_shares_e6);
-        msg!("   Vault balance: {} e6 (${:.6})", vault_balance_e6, vault_balance_e6 as f64 / 1e6);
-        msg!("   Master wallet: {}", master_wallet);
+    // ---------- ORACLE ADAPTERS ----------
+    /// Select how oracle_state is parsed (1=triplet, 2=Pyth-style, 3=push). Only between rounds,
+    /// so a round's start and settle prices always come from the same adapter. Switching adapters
+    /// drops the pin: the old feed can't be read by the new adapter, so pin_oracle must run again.
+    pub fn set_oracle_kind(ctx: Context<AdminConfig>, kind: u8) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(
+            matches!(kind, ORACLE_KIND_TRIPLET | ORACLE_KIND_PYTH | ORACLE_KIND_PUSH),
+            ReaderError::BadParam
+        );
 
-        // Warn if user has shares
-        if yes_shares_e6 != 0 || no_shares_e6 != 0 {
-            msg!("⚠️  WARNING: Position has shares! YES={} NO={}", yes_shares_e6, no_shares_e6);
-            msg!("   These shares will be LOST after migration!");
+        let amm = &mut ctx.accounts.amm;
+        require!(amm.status() == MarketStatus::Premarket && amm.start_price_e6 == 0, ReaderError::WrongState);
+        if amm.oracle_kind() != kind {
+            amm.oracle_state = Pubkey::default();
+            amm.oracle_authority = Pubkey::default();
         }
+        amm.oracle_kind = kind;
 
-        // Transfer vault balance to master wallet if any
-        if vault_balance_e6 > 0 {
-            let vault_lamports = (vault_balance_e6 as u64) * LAMPORTS_PER_E6;
-
-            msg!("💸 Transferring {} lamports ({:.6} XNT) from user_vault to master_wallet",
-                 vault_lamports, vault_balance_e6 as f64 / 1e6);
-
-            // Transfer from user_vault PDA to master_wallet
-            let pos_key = ctx.accounts.old_position.key();
-            let seeds: &[&[u8]] = &[
-                Position::USER_VAULT_SEED,
-                pos_key.as_ref(),
-                &[vault_bump],
-            ];
-            let signer_seeds = &[&seeds[..]];
+        msg!("🔮 Oracle adapter set to {}", kind);
+        Ok(())
+    }
 
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.user_vault.to_account_info(),
-                    to: ctx.accounts.master_wallet.to_account_info(),
-                },
-                signer_seeds,
-            );
-            anchor_lang::system_program::transfer(cpi_ctx, vault_lamports)?;
+    /// Pin the market to one oracle account, and optionally to the update_authority that writes it.
+    /// The pin survives reopen_amm / roll_round; Pubkey::default() as authority skips that check
+    /// (not accepted for push feeds). Oracle reads fail until a feed is pinned, so a market with no
+    /// pin yet (e.g. one live when migrate_amm ran) may be pinned mid-round; re-pinning waits for
+    /// the next premarket.
+    pub fn pin_oracle(ctx: Context<PinOracle>, expected_authority: Pubkey) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
 
-            msg!("✅ Vault balance transferred to master wallet");
-        } else {
-            msg!("   No vault balance to transfer");
-        }
+        let amm = &mut ctx.accounts.amm;
+        require!(
+            amm.oracle_state == Pubkey::default()
+                || (amm.status() == MarketStatus::Premarket && amm.start_price_e6 == 0),
+            ReaderError::WrongState
+        );
+        amm.oracle_state = ctx.accounts.oracle_state.key();
+        amm.oracle_authority = expected_authority;
 
-        // Close the old position account - return rent to owner
-        let position_lamports = position_info.lamports();
+        // The pinned feed must load under the current adapter and authority
+        load_oracle(&ctx.accounts.oracle_state, amm, amm.asset())?;
 
-        msg!("🗑️  Closing old position account, returning {} lamports rent to {}",
-             position_lamports, owner);
+        msg!("📌 Oracle pinned: {} (authority {})", amm.oracle_state, amm.oracle_authority);
+        Ok(())
+    }
 
-        // Transfer lamports from position to owner
-        **position_info.try_borrow_mut_lamports()? = 0;
-        **ctx.accounts.owner.to_account_info().try_borrow_mut_lamports()? += position_lamports;
+    pub fn set_oracle_checks(ctx: Context<AdminConfig>, max_spread_bps: u16, max_age_secs: i64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        require!(max_spread_bps > 0 && max_spread_bps <= 10_000, ReaderError::BadParam);
+        require!(max_age_secs > 0, ReaderError::BadParam);
 
-        msg!("✅ Migration complete! User can now call init_position to create new 901-byte account");
+        let amm = &mut ctx.accounts.amm;
+        amm.oracle_max_spread_bps = max_spread_bps;
+        amm.oracle_max_age_secs = max_age_secs;
 
+        msg!("🔮 Oracle checks: spread <= {} bps, sources fresh within {}s (2 of 3)", max_spread_bps, max_age_secs);
         Ok(())
     }
 
+    pub fn init_push_oracle(ctx: Context<InitPushOracle>, asset: u8) -> Result<()> {
+        require!(matches!(asset, ASSET_BTC | ASSET_ETH | ASSET_SOL), ReaderError::BadParam);
 
+        let feed = &mut ctx.accounts.feed;
+        feed.authority = ctx.accounts.authority.key();
+        feed.asset = asset;
+        feed.price_e6 = 0;
+        feed.conf_e6 = 0;
+        feed.ts_ms = 0;
+        feed.bump = ctx.bumps.feed;
 
-    // ---------- SETTLE (manual winner: 1=YES, 2=NO) ----------
-    pub fn settle_market(ctx: Context<AdminOnly>, winner: u8) -> Result<()> {
-        let amm = &mut ctx.accounts.amm;
-        require!(amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
-        require!(winner == 1 || winner == 2, ReaderError::BadParam);
-        amm.winner = winner;
-
-        // Snapshot W (total winning shares at stop)
-        let w = if winner == 1 { amm.q_yes } else { amm.q_no };
-        amm.w_total_e6 = w.max(0);
+        msg!("🔮 PUSH ORACLE asset={} authority={} → {}", asset, feed.authority, feed.key());
+        Ok(())
+    }
 
-        // pps = min(1e6, floor(vault / W)) with exact integers
-        let pps_e6 = if amm.w_total_e6 <= 0 {
-            0
-        } else {
-            let num: i128 = (amm.vault_e6.max(0) as i128) * 1_000_000i128;
-            let den: i128 = amm.w_total_e6 as i128;
-            let floored: i64 = (num / den) as i64;
-            floored.min(1_000_000)
-        };
-        amm.pps_e6 = pps_e6;
-        // Market stays in STOPPED state - users can redeem, then admin can reinit to PREMARKET
+    /// Publish time may not go backwards or run ahead of the cluster clock.
+    pub fn push_price(ctx: Context<PushPrice>, price_e6: i64, conf_e6: i64, ts_ms: i64) -> Result<()> {
+        require!(price_e6 > 0 && conf_e6 >= 0, ReaderError::BadParam);
+        let now_ms = Clock::get()?.unix_timestamp.saturating_mul(1000);
+        let feed = &mut ctx.accounts.feed;
+        require!(ts_ms > feed.ts_ms && ts_ms <= now_ms.saturating_add(1000), ReaderError::BadParam);
 
-        msg!("✅ SETTLED winner={}  W={}  vault=${:.6}  pps={:.6} - Market stays STOPPED for redemptions",
-             winner, amm.w_total_e6, usd(amm.vault_e6), (pps_e6 as f64)/1_000_000.0);
+        feed.price_e6 = price_e6;
+        feed.conf_e6 = conf_e6;
+        feed.ts_ms = ts_ms;
         Ok(())
     }
 
-/// If true, we wipe (set to zero) the user's Position even when coverage/reserve makes pay=0.
-/// If false, we keep the Position intact when pay=0 so the user can try again later.
-const WIPE_ON_PAY_ZERO: bool = true;
+    // ---------- SWEEP UNCLAIMED (permissionless, once a round's claim deadline has passed) ----------
+    /// Moves the round's unpaid liability from vault_sol to the treasury PDA. Winners who show
+    /// up afterwards are paid from the treasury through `claim_late`.
+    pub fn sweep_unclaimed(ctx: Context<SweepUnclaimed>, round_id: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let rr = &mut ctx.accounts.round_result;
+        let amm = &mut ctx.accounts.amm;
 
-pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
-    let sys  = &ctx.accounts.system_program;
-
-    // Must be stopped (settlement values calculated)
-    require!(ctx.accounts.amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
-    // And winner must be determined
-    require!(ctx.accounts.amm.winner != 0, ReaderError::WrongState);
-
-    // ---- read-only views
-    let pos_ro = &ctx.accounts.pos;
-    let amm_ro = &ctx.accounts.amm;
-
-    // winning side balance for this user
-    let (win_sh_e6, _lose_sh_e6) = match amm_ro.winner {
-        1 => (pos_ro.yes_shares_e6, pos_ro.no_shares_e6),
-        2 => (pos_ro.no_shares_e6,  pos_ro.yes_shares_e6),
-        _ => (0, 0),
-    };
+        require!(rr.winner != 0 && rr.claim_deadline > 0, ReaderError::WrongState);
+        require!(now >= rr.claim_deadline, ReaderError::ClaimWindowOpen);
 
-    // nothing to do → wipe anyway (so we don't carry state into next run) and return
-    if win_sh_e6 <= 0 {
-        let pos_mut = &mut ctx.accounts.pos;
-        pos_mut.yes_shares_e6 = 0;
-        pos_mut.no_shares_e6  = 0;
-        msg!("No winning shares to redeem; position wiped.");
-        return Ok(());
-    }
+        // The unpaid part sits in this round's liability, or in the reserve once carried.
+        // A sweep capped by vault lamports leaves the rest for a later call.
+        let current = round_id == amm.round_id;
+        let unpaid_e6 = rr.liability_e6
+            .saturating_sub(rr.redeemed_e6)
+            .saturating_sub(rr.iou_e6)
+            .saturating_sub(rr.swept_e6);
+        let mirror_e6 = if current { amm.liability_e6 } else { amm.reserved_e6 };
+        let sweep_e6 = unpaid_e6.min(mirror_e6).max(0);
 
-    // Theoretical payout at snapshot pps (bounded by mirror)
-    let w_snap  = amm_ro.w_total_e6.max(0);
-    let win_clip = if win_sh_e6 < 0 { 0 } else { win_sh_e6.min(w_snap) };
-    let theoretical_e6: i64 =
-        ((win_clip as i128) * (amm_ro.pps_e6 as i128) / 1_000_000i128) as i64;
+        // Lamports availability bound (keep >= 1 SOL)
+        let vault_ai = ctx.accounts.vault_sol.to_account_info();
+        let available_lamports = vault_ai.lamports().saturating_sub(MIN_VAULT_LAMPORTS);
+        let sweep_lamports = e6_to_lamports(sweep_e6).min(available_lamports);
+        require!(sweep_lamports > 0, ReaderError::NothingToSweep);
 
-    // Mirror coverage bound (accounting units)
-    let mirror_bound_e6 = theoretical_e6.min(amm_ro.vault_e6.max(0));
+        // claim_late never pays below the treasury's rent-exempt floor, so the first sweep
+        // funds that floor out of the vault's 1 SOL reserve rather than out of swept winnings
+        let treasury_ai = ctx.accounts.treasury.to_account_info();
+        let rent_gap = Rent::get()?.minimum_balance(0).saturating_sub(treasury_ai.lamports());
 
-    // Lamports availability bound (keep >= 1 SOL)
-    let vault_ai = &ctx.accounts.vault_sol.to_account_info();
-    let vault_lamports_now = vault_ai.lamports();
-    let available_lamports = vault_lamports_now.saturating_sub(MIN_VAULT_LAMPORTS);
-    let mirror_bound_lamports = e6_to_lamports(mirror_bound_e6);
-    let pay_lamports = mirror_bound_lamports.min(available_lamports);
-
-    if pay_lamports == 0 {
-        msg!("⚠️  Reserve/coverage bound: pay=0 (vault={}, keep_reserve={})",
-             vault_lamports_now, MIN_VAULT_LAMPORTS);
-
-        // ---- wipe position here if you want a clean slate next run
-        if WIPE_ON_PAY_ZERO {
-            let pos_mut = &mut ctx.accounts.pos;
-            pos_mut.yes_shares_e6 = 0;
-            pos_mut.no_shares_e6  = 0;
-            msg!("Position wiped despite zero payout (WIPE_ON_PAY_ZERO=true).");
+        let amm_key = amm.key();
+        let seeds: &[&[u8]] = &[
+            Amm::VAULT_SOL_SEED,
+            amm_key.as_ref(),
+            core::slice::from_ref(&amm.vault_sol_bump),
+        ];
+        transfer_sol_signed(
+            &ctx.accounts.system_program,
+            &vault_ai,
+            &treasury_ai,
+            sweep_lamports.saturating_add(rent_gap),
+            &[seeds],
+        )?;
+
+        let swept_e6 = lamports_to_e6(sweep_lamports);
+        if current {
+            amm.liability_e6 = amm.liability_e6.saturating_sub(swept_e6).max(0);
+            amm.vault_e6 = amm.vault_e6.saturating_sub(swept_e6);
         } else {
-            msg!("Leaving position intact (WIPE_ON_PAY_ZERO=false).");
+            amm.reserved_e6 = amm.reserved_e6.saturating_sub(swept_e6).max(0);
         }
-        return Ok(());
-    }
-
-    // Convert actual lamports paid back to e6 for mirror accounting
-    let pay_e6_effective = lamports_to_e6(pay_lamports);
-
-    // Pay to user_vault PDA (not session wallet)
-    let amm_key = ctx.accounts.amm.key();
-    let seeds: &[&[u8]] = &[
-        Amm::VAULT_SOL_SEED,
-        amm_key.as_ref(),
-        core::slice::from_ref(&ctx.accounts.amm.vault_sol_bump),
-    ];
-    transfer_sol_signed(
-        sys,
-        &ctx.accounts.vault_sol.to_account_info(),
-        &ctx.accounts.user_vault.to_account_info(),
-        pay_lamports,
-        &[seeds],
-    )?;
+        rr.swept_e6 = rr.swept_e6.saturating_add(swept_e6);
+
+        emit!(UnclaimedSwept {
+            round_id,
+            swept_e6,
+            liability_e6: rr.liability_e6,
+            redeemed_e6: rr.redeemed_e6,
+            treasury: ctx.accounts.treasury.key(),
+            swept_at: now,
+        });
 
-    // ---- mutate mirrors and WIPE position
-    let amm_mut = &mut ctx.accounts.amm;
-    amm_mut.vault_e6 = amm_mut.vault_e6.saturating_sub(pay_e6_effective);
+        msg!("🧺 SWEPT round={} unclaimed={} of owed={} (redeemed={}) → treasury {}",
+             round_id, swept_e6, rr.liability_e6, rr.redeemed_e6, ctx.accounts.treasury.key());
+        Ok(())
+    }
 
-    let pos_mut = &mut ctx.accounts.pos;
-    pos_mut.yes_shares_e6 = 0;
-    pos_mut.no_shares_e6  = 0;
-    // Update user vault balance tracking
-    pos_mut.vault_balance_e6 = pos_mut.vault_balance_e6.saturating_add(pay_e6_effective);
+    // ---------- CLAIM LATE (winnings of a swept round, paid from the treasury) ----------
+    pub fn claim_late(ctx: Context<ClaimLate>) -> Result<()> {
+        let amm = &ctx.accounts.amm;
+        let pos = &mut ctx.accounts.pos;
+        let rr = &mut ctx.accounts.round_result;
 
-    let kept = vault_ai.lamports(); // after transfer
-    msg!(
-        "💸 REDEEM pay={} lamports ({:.9} SOL) to user_vault; kept_reserve={} lamports; pps={:.6}, winner={}; vault_balance={} e6",
-        pay_lamports,
-        (pay_lamports as f64)/1e9,
-        kept.min(MIN_VAULT_LAMPORTS),
-        (amm_mut.pps_e6 as f64)/1_000_000.0,
-        amm_mut.winner,
-        pos_mut.vault_balance_e6
-    );
-    Ok(())
-}
+        let claim = pending_claim(pos, amm);
+        require!(rr.round_id == claim.round_id, ReaderError::WrongState);
+        require!(rr.swept_e6 > 0, ReaderError::NotSwept);
 
-    // ---------- ADMIN REDEEM (force redeem on behalf of user) ----------
-    pub fn admin_redeem(ctx: Context<AdminRedeem>) -> Result<()> {
-        let sys = &ctx.accounts.system_program;
+        let owed_e6 = claim_payout_e6(rr, &claim);
+        if owed_e6 <= 0 {
+            clear_claim(pos, &claim, amm.round_id);
+            msg!("CLAIM_LATE: no winning shares in round {}; claim wiped.", claim.round_id);
+            return Ok(());
+        }
 
-        // Only fee_dest can call this
-        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        // Bounded by what was swept for this round, and by the treasury staying rent-exempt
+        let left_e6 = rr.swept_e6.saturating_sub(rr.late_paid_e6).max(0);
+        let treasury_ai = ctx.accounts.treasury.to_account_info();
+        let available_lamports = treasury_ai.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+        let pay_lamports = e6_to_lamports(owed_e6.min(left_e6)).min(available_lamports);
 
-        // Must be stopped (settlement values calculated)
-        require!(ctx.accounts.amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
-        // And winner must be determined
-        require!(ctx.accounts.amm.winner != 0, ReaderError::WrongState);
+        if pay_lamports == 0 {
+            msg!("⚠️  CLAIM_LATE: nothing left to pay for round {} (treasury={} lamports)",
+                 claim.round_id, treasury_ai.lamports());
+            msg!("Leaving position intact.");
+            return Ok(());
+        }
 
-        // ---- read-only views
-        let pos_ro = &ctx.accounts.pos;
-        let amm_ro = &ctx.accounts.amm;
+        let amm_key = amm.key();
+        let seeds: &[&[u8]] = &[
+            Amm::TREASURY_SEED,
+            amm_key.as_ref(),
+            core::slice::from_ref(&ctx.bumps.treasury),
+        ];
+        transfer_sol_signed(
+            &ctx.accounts.system_program,
+            &treasury_ai,
+            &ctx.accounts.user_vault.to_account_info(),
+            pay_lamports,
+            &[seeds],
+        )?;
 
-        // winning side balance for this user
-        let (win_sh_e6, _lose_sh_e6) = match amm_ro.winner {
-            1 => (pos_ro.yes_shares_e6, pos_ro.no_shares_e6),
-            2 => (pos_ro.no_shares_e6,  pos_ro.yes_shares_e6),
-            _ => (0, 0),
-        };
+        let pay_e6_effective = lamports_to_e6(pay_lamports);
+        rr.late_paid_e6 = rr.late_paid_e6.saturating_add(pay_e6_effective);
+        clear_claim(pos, &claim, amm.round_id);
+        pos.vault_balance_e6 = pos.vault_balance_e6.saturating_add(pay_e6_effective);
 
-        // nothing to do → wipe anyway and return
-        if win_sh_e6 <= 0 {
-            let pos_mut = &mut ctx.accounts.pos;
-            pos_mut.yes_shares_e6 = 0;
-            pos_mut.no_shares_e6  = 0;
-            msg!("ADMIN_REDEEM: No winning shares; position wiped.");
-            return Ok(());
-        }
+        msg!("💸 CLAIM_LATE round={} pay={} lamports ({:.9} SOL) from treasury; vault_balance={} e6",
+             claim.round_id, pay_lamports, (pay_lamports as f64)/1e9, pos.vault_balance_e6);
+        Ok(())
+    }
 
-        // Theoretical payout at snapshot pps (bounded by mirror)
-        let w_snap = amm_ro.w_total_e6.max(0);
-        let win_clip = if win_sh_e6 < 0 { 0 } else { win_sh_e6.min(w_snap) };
-        let theoretical_e6: i64 =
-            ((win_clip as i128) * (amm_ro.pps_e6 as i128) / 1_000_000i128) as i64;
+    // ---------- CLAIM IOU (redeem shortfall, once the vault has been topped up) ----------
+    /// Pays from vault lamports not backing the live round (vault_e6), carried winnings
+    /// (reserved_e6) or the outstanding IOU total (iou_e6), so a top-up is the only thing that
+    /// makes IOUs payable.
+    pub fn claim_iou(ctx: Context<ClaimIou>) -> Result<()> {
+        let pos = &mut ctx.accounts.pos;
+        require!(pos.iou_e6 > 0, ReaderError::NoIou);
 
-        // Mirror coverage bound
-        let mirror_bound_e6 = theoretical_e6.min(amm_ro.vault_e6.max(0));
+        let amm = &mut ctx.accounts.amm;
+        let vault_ai = ctx.accounts.vault_sol.to_account_info();
+        let vault_lamports_now = vault_ai.lamports();
+        // book_claim takes IOUs off the mirrors without any lamports leaving the vault; left in,
+        // they would read as a top-up and let claim_iou pay out of the live round's funds
+        let surplus_e6 = lamports_to_e6(vault_lamports_now)
+            .saturating_sub(amm.vault_e6.max(0))
+            .saturating_sub(amm.reserved_e6.max(0))
+            .saturating_sub(amm.iou_e6.max(0))
+            .max(0);
+        let pay_e6 = pos.iou_e6.min(amm.iou_e6.max(0)).min(surplus_e6);
 
         // Lamports availability bound (keep >= 1 SOL)
-        let vault_ai = &ctx.accounts.vault_sol.to_account_info();
-        let vault_lamports_now = vault_ai.lamports();
         let available_lamports = vault_lamports_now.saturating_sub(MIN_VAULT_LAMPORTS);
-        let mirror_bound_lamports = e6_to_lamports(mirror_bound_e6);
-        let pay_lamports = mirror_bound_lamports.min(available_lamports);
-
+        let pay_lamports = e6_to_lamports(pay_e6).min(available_lamports);
         if pay_lamports == 0 {
-            msg!("⚠️  ADMIN_REDEEM: Reserve/coverage bound: pay=0 (vault={}, keep_reserve={})",
-                 vault_lamports_now, MIN_VAULT_LAMPORTS);
-
-            if WIPE_ON_PAY_ZERO {
-                let pos_mut = &mut ctx.accounts.pos;
-                pos_mut.yes_shares_e6 = 0;
-                pos_mut.no_shares_e6  = 0;
-                msg!("Position wiped despite zero payout.");
-            }
+            msg!("⚠️  CLAIM_IOU: vault not topped up yet (iou={} e6, surplus={} e6, vault={} lamports)",
+                 pos.iou_e6, surplus_e6, vault_lamports_now);
             return Ok(());
         }
 
-        // Convert actual lamports paid back to e6 for mirror accounting
-        let pay_e6_effective = lamports_to_e6(pay_lamports);
-
-        // Pay to user_vault PDA (not session wallet)
-        let amm_key = ctx.accounts.amm.key();
+        let amm_key = amm.key();
         let seeds: &[&[u8]] = &[
             Amm::VAULT_SOL_SEED,
             amm_key.as_ref(),
-            core::slice::from_ref(&ctx.accounts.amm.vault_sol_bump),
+            core::slice::from_ref(&amm.vault_sol_bump),
         ];
         transfer_sol_signed(
-            sys,
-            &ctx.accounts.vault_sol.to_account_info(),
+            &ctx.accounts.system_program,
+            &vault_ai,
             &ctx.accounts.user_vault.to_account_info(),
             pay_lamports,
             &[seeds],
         )?;
 
-        // ---- mutate mirrors and WIPE position
-        let amm_mut = &mut ctx.accounts.amm;
-        amm_mut.vault_e6 = amm_mut.vault_e6.saturating_sub(pay_e6_effective);
-
-        let pos_mut = &mut ctx.accounts.pos;
-        pos_mut.yes_shares_e6 = 0;
-        pos_mut.no_shares_e6  = 0;
-        // Update user vault balance tracking
-        pos_mut.vault_balance_e6 = pos_mut.vault_balance_e6.saturating_add(pay_e6_effective);
+        let pay_e6_effective = lamports_to_e6(pay_lamports);
+        amm.iou_e6 = amm.iou_e6.saturating_sub(pay_e6_effective).max(0);
+        pos.iou_e6 = pos.iou_e6.saturating_sub(pay_e6_effective).max(0);
+        pos.vault_balance_e6 = pos.vault_balance_e6.saturating_add(pay_e6_effective);
 
-        let kept = vault_ai.lamports();
-        msg!(
-            "💸 ADMIN_REDEEM user={} pay={} lamports ({:.9} SOL) to user_vault; kept_reserve={} lamports; vault_balance={} e6",
-            ctx.accounts.user.key(),
-            pay_lamports,
-            (pay_lamports as f64)/1e9,
-            kept.min(MIN_VAULT_LAMPORTS),
-            pos_mut.vault_balance_e6
-        );
+        msg!("💸 CLAIM_IOU round={} pay={} lamports ({:.9} SOL); iou left={} e6; vault_balance={} e6",
+             pos.iou_round_id, pay_lamports, (pay_lamports as f64)/1e9, pos.iou_e6, pos.vault_balance_e6);
         Ok(())
     }
 
+    // ---------- VOID (permissionless, once the settle grace period has passed with no winner) ----------
+    /// Every outstanding share (YES or NO) becomes redeemable at
+    /// pps = min(1e6, floor(vault / (qYes + qNo))) through the normal `redeem` path, so the
+    /// whole vault goes back to the holders pro rata.
+    pub fn void_market(ctx: Context<VoidMarket>) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        require!(amm.winner == 0, ReaderError::WrongState);
+        require!(amm.market_end_time > 0, ReaderError::WrongState);
 
-    // ---------- CLOSE AMM (new) ----------
-    pub fn close_amm(ctx: Context<CloseAmm>) -> Result<()> {
-        msg!("🧹 AMM account closed to recipient {}", ctx.accounts.recipient.key());
-        Ok(())
-    }
+        let now = Clock::get()?.unix_timestamp;
+        let grace = if amm.settle_grace_secs > 0 { amm.settle_grace_secs } else { DEFAULT_SETTLE_GRACE_SECS };
+        let voidable_at = amm.market_end_time.saturating_add(grace);
+        require!(now >= voidable_at, ReaderError::SettleGraceActive);
 
-    // ---------- NEW: SNAPSHOT the start BTC price ----------
-    pub fn snapshot_start(ctx: Context<SnapshotStart>) -> Result<()> {
-        let amm = &mut ctx.accounts.amm;
-        require!(amm.status() == MarketStatus::Premarket, ReaderError::WrongState);
-        require!(amm.start_price_e6 == 0, ReaderError::AlreadySnapshotted);
+        amm.status = MarketStatus::Stopped as u8;
+        let pps_e6 = apply_void(amm);
 
-        let (price_e6, ts) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-        assert_fresh(ts)?;
+        record_round_result(&mut ctx.accounts.round_result, amm, ctx.bumps.round_result)?;
 
-        amm.start_price_e6 = price_e6;
-        amm.start_ts = ts;
+        emit!(MarketVoided {
+            market_end_time: amm.market_end_time,
+            voided_at: now,
+            w_total_e6: amm.w_total_e6,
+            pps_e6,
+            vault_e6: amm.vault_e6,
+        });
 
-        // Transition from PREMARKET to OPEN
-        amm.status = MarketStatus::Open as u8;
+        msg!("🚫 VOIDED: no settlement {}s after end ({}), W={} vault=${:.6} refund_pps={:.6}",
+             grace, amm.market_end_time, amm.w_total_e6, usd(amm.vault_e6), (pps_e6 as f64)/1e6);
+        Ok(())
+    }
 
-        msg!("📸 SNAPSHOT start BTC=${:.6} (ts={}) - Market now OPEN", (price_e6 as f64)/1e6, ts);
+    // ---------- SERIES: template for recurring rounds ----------
+    pub fn init_series(ctx: Context<InitSeries>, params: SeriesParams) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.amm.fee_dest, ReaderError::NotOwner);
+        params.validate()?;
+        read_oracle(&ctx.accounts.oracle_state, &ctx.accounts.amm, params.asset)?;
+
+        let series = &mut ctx.accounts.series;
+        series.bump = ctx.bumps.series;
+        series.authority = ctx.accounts.admin.key();
+        series.oracle_state = ctx.accounts.oracle_state.key();
+        series.params = params;
+        series.rounds_opened = 0;
+
+        msg!("🔁 SERIES asset={} duration={}s results={}s b={} fee_bps={} lockout={}s ge_wins_yes={} oracle={}",
+             params.asset, params.duration_secs, params.results_secs, params.b, params.fee_bps,
+             params.lockout_secs, params.ge_wins_yes, series.oracle_state);
         Ok(())
     }
 
-    // ---------- SET MARKET END TIME (for trading lockout) ----------
-    pub fn set_market_end_slot(ctx: Context<AdminOnly>, market_end_slot: u64, market_end_time: i64) -> Result<()> {
-        let amm = &mut ctx.accounts.amm;
-        require!(market_end_time > 0, ReaderError::BadParam);
+    /// Takes effect from the next round roll_round opens.
+    pub fn update_series(ctx: Context<UpdateSeries>, params: SeriesParams) -> Result<()> {
+        params.validate()?;
+        read_oracle(&ctx.accounts.oracle_state, &ctx.accounts.amm, params.asset)?;
 
-        amm.market_end_slot = market_end_slot;  // Keep for backwards compatibility
-        amm.market_end_time = market_end_time;
+        let series = &mut ctx.accounts.series;
+        series.oracle_state = ctx.accounts.oracle_state.key();
+        series.params = params;
 
-        msg!("⏰ Market end time set to: {} (unix timestamp)", market_end_time);
-        msg!("   Trading locks at: {} (45 seconds before close)", market_end_time - TRADING_LOCKOUT_SECONDS);
-        msg!("   Market end slot (legacy): {}", market_end_slot);
+        msg!("🔁 SERIES updated: asset={} duration={}s results={}s b={} fee_bps={} lockout={}s ge_wins_yes={} oracle={}",
+             params.asset, params.duration_secs, params.results_secs, params.b, params.fee_bps,
+             params.lockout_secs, params.ge_wins_yes, series.oracle_state);
         Ok(())
     }
 
-    // ---------- NEW: settle by comparing current BTC to snapshot ----------
-    /// ge_wins_yes=true  => YES wins on tie (>=). false => YES only if strictly greater.
-    pub fn settle_by_oracle(ctx: Context<SettleByOracle>, ge_wins_yes: bool) -> Result<()> {
+    // ---------- ROLL ROUND (permissionless crank for a series) ----------
+    /// Advances the series by whatever is due:
+    ///  - fresh market (init_amm only): snapshot start and schedule the end,
+    ///  - ended and unsettled: stop and settle by oracle with the series tie rule,
+    ///  - settled and `results_secs` elapsed: open the next round in place.
+    /// With `results_secs == 0` settle and open happen in the same call.
+    pub fn roll_round(ctx: Context<RollRound>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let p = ctx.accounts.series.params;
         let amm = &mut ctx.accounts.amm;
 
-        require!(amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
-        require!(amm.start_price_e6 != 0, ReaderError::NotSnapshotted);
-
-        let (curr_e6, ts) = read_btc_price_e6(&ctx.accounts.oracle_state)?;
-        assert_fresh(ts)?;
+        // 1. Fresh market: nothing to settle yet, just start the clock
+        if amm.winner == 0 && amm.market_end_time == 0 {
+            require!(amm.status() != MarketStatus::Stopped, ReaderError::WrongState);
+            if amm.start_price_e6 == 0 {
+                let OracleReading { price_e6, ts_ms: ts, .. } = read_oracle(&ctx.accounts.oracle_state, amm, p.asset)?;
+                amm.asset = p.asset;
+                amm.start_price_e6 = price_e6;
+                amm.start_ts = ts;
+            }
+            amm.lockout_secs = p.lockout_secs;
+            amm.market_end_time = now.saturating_add(p.duration_secs);
+            amm.status = MarketStatus::Open as u8;
 
-        let start = amm.start_price_e6;
-        let winner = if ge_wins_yes {
-            if curr_e6 >= start { 1 } else { 2 }
-        } else {
-            if curr_e6 >  start { 1 } else { 2 }
-        };
+            emit_round_opened(amm);
+            msg!("🔁 ROUND {} started: start=${:.6} ends at {}",
+                 amm.round_id, (amm.start_price_e6 as f64)/1e6, amm.market_end_time);
+            return Ok(());
+        }
 
-        amm.settle_price_e6 = curr_e6;
-        amm.settle_ts = ts;
+        // 2. Ended and unsettled: stop and settle by oracle
+        let mut settled_now = false;
+        if amm.winner == 0 {
+            require!(now >= amm.market_end_time, ReaderError::RoundNotReady);
+            require!(amm.start_price_e6 != 0, ReaderError::NotSnapshotted);
+
+            let OracleReading { price_e6: curr_e6, ts_ms: ts, .. } = read_oracle(&ctx.accounts.oracle_state, amm, amm.asset())?;
+
+            amm.status = MarketStatus::Stopped as u8;
+            amm.settle_price_e6 = curr_e6;
+            amm.settle_ts = ts;
+            let winner = oracle_winner(curr_e6, amm.start_price_e6, p.ge_wins_yes);
+            let w = if winner == 1 { amm.q_yes } else { amm.q_no };
+            draw_insurance(amm, &ctx.accounts.insurance, &ctx.accounts.vault_sol, &ctx.accounts.system_program,
+                           w.max(0) - amm.vault_e6, ctx.bumps.insurance, INSURANCE_DRAW_SETTLE)?;
+            let pps_e6 = apply_settlement(amm, winner);
+            settled_now = true;
+
+            msg!("✅ ROLL_SETTLED round={} winner={} start=${:.6} curr=${:.6} W={} pps={:.6}",
+                 amm.round_id, winner, (amm.start_price_e6 as f64)/1e6, (curr_e6 as f64)/1e6,
+                 amm.w_total_e6, (pps_e6 as f64)/1e6);
+        }
+        if ctx.accounts.round_result.settled_at == 0 {
+            record_round_result(&mut ctx.accounts.round_result, amm, ctx.bumps.round_result)?;
+        }
 
-        // Reuse settlement math
-        require!(winner == 1 || winner == 2, ReaderError::BadParam);
-        amm.winner = winner;
-        let w = if winner == 1 { amm.q_yes } else { amm.q_no };
-        amm.w_total_e6 = w.max(0);
+        // 3. Results window over: open the next round
+        let opens_at = ctx.accounts.round_result.settled_at.saturating_add(p.results_secs);
+        if now < opens_at {
+            require!(settled_now, ReaderError::RoundNotReady);
+            msg!("   Next round opens at {}", opens_at);
+            return Ok(());
+        }
 
-        let pps_e6 = if amm.w_total_e6 <= 0 {
-            0
-        } else {
-            let num: i128 = (amm.vault_e6.max(0) as i128) * 1_000_000i128;
-            let den: i128 = amm.w_total_e6 as i128;
-            let floored: i64 = (num / den) as i64;
-            floored.min(1_000_000)
-        };
-        amm.pps_e6 = pps_e6;
-        // Market stays in STOPPED state - users can redeem, then admin can reinit to PREMARKET
+        let OracleReading { price_e6, ts_ms: ts, .. } = read_oracle(&ctx.accounts.oracle_state, amm, p.asset)?;
+        let prev_round = amm.round_id;
+        let vault_lamports = ctx.accounts.vault_sol.to_account_info().lamports();
+        open_next_round(amm, &p, vault_lamports, price_e6, ts, now)?;
 
-        msg!(
-          "✅ SETTLED_BY_ORACLE winner={} start=${:.6}@{} curr=${:.6}@{}  W={}  vault=${:.6}  pps={:.6} - Market stays STOPPED for redemptions",
-          winner,
-          (start as f64)/1e6, amm.start_ts,
-          (curr_e6 as f64)/1e6, ts,
-          amm.w_total_e6,
-          (amm.vault_e6 as f64)/1e6,
-          (pps_e6 as f64)/1e6
-        );
+        let series = &mut ctx.accounts.series;
+        series.rounds_opened = series.rounds_opened.saturating_add(1);
+
+        emit_round_opened(amm);
+        msg!("🔁 ROUND {} -> {} (#{}) start=${:.6} ends at {} vault_e6={}",
+             prev_round, amm.round_id, series.rounds_opened, (price_e6 as f64)/1e6,
+             amm.market_end_time, amm.vault_e6);
         Ok(())
     }
 
@@ -2095,6 +3430,7 @@ pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
         ctx: Context<ExecuteLimitOrder>,
         order: LimitOrder,
         signature: [u8; 64],
+        verify_ix_index: u16,
     ) -> Result<()> {
         let amm = &ctx.accounts.amm;
         let position = &mut ctx.accounts.position;
@@ -2105,7 +3441,7 @@ pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
         // === VALIDATION PHASE ===
 
         // 1. Verify Ed25519 signature using instructions sysvar
-        verify_ed25519_signature(&order, &signature, &ctx.accounts.instructions.to_account_info())?;
+        verify_ed25519_signature(&order, &signature, &ctx.accounts.instructions.to_account_info(), verify_ix_index)?;
         msg!("✅ Signature verified");
 
         // 2. Verify order matches this market
@@ -2114,296 +3450,199 @@ pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
         // 3. Verify order owner matches position owner
         require_keys_eq!(order.user, position.owner, ReaderError::WrongUser);
 
-        // 4. Check order not expired
-        require!(
-            order.expiry_ts > clock.unix_timestamp,
-            ReaderError::OrderExpired
-        );
-        msg!("⏱️  Order valid until {}", order.expiry_ts);
-
-        // 5. Check nonce not already used
-        require!(
-            !position.used_nonces.contains(&order.nonce),
-            ReaderError::NonceAlreadyUsed
-        );
+        // Shares left over from an earlier round are parked for redemption, not traded
+        sync_position_round(position, amm)?;
 
-        // 6. Check market is open (allow both Premarket and Open)
+        // 4. Market is open (Premarket or Open) and outside the trading lockout
         let status = amm.status();
         require!(
             status == MarketStatus::Premarket || status == MarketStatus::Open,
             ReaderError::MarketClosed
         );
+        check_trading_lockout(amm, &ctx.accounts.oracle_state)?;
 
-        // === PRICE CHECK PHASE ===
-
-        // Calculate current price for this action/side
-        let current_price = calculate_avg_price_for_one_share(order.action, order.side, amm)?;
-
-        msg!("💰 Current price: {} | Limit: {}", current_price, order.limit_price_e6);
-
-        // Verify price condition is favorable
-        let price_ok = match order.action {
-            1 => { // BUY: current price must be <= limit price
-                current_price <= order.limit_price_e6
-            }
-            2 => { // SELL: current price must be >= limit price
-                current_price >= order.limit_price_e6
-            }
-            _ => return err!(ReaderError::InvalidAction),
-        };
+        // 5. Conditional orders: the underlying must have crossed the trigger
+        check_order_trigger(&order, amm, &ctx.accounts.oracle_state)?;
 
-        require!(price_ok, ReaderError::PriceConditionNotMet);
-        msg!("✅ Price condition satisfied");
+        // 6. Expiry, nonce, price condition and fill size
+        let fill = &mut ctx.accounts.order_fill;
+        let nonce_fill = if order.group_id != 0 { Some(read_order_fill(&ctx.accounts.nonce_fill)?) } else { None };
+        let executable_shares = plan_limit_fill(
+            amm, position, fill, nonce_fill.as_ref().unwrap_or(fill), &order, clock.unix_timestamp,
+        )?;
+        if !fill.is_open() {
+            fill.open(position.key(), order.fill_id(), order.expiry_ts, ctx.accounts.keeper.key(), ctx.bumps.order_fill);
+        }
 
         // === EXECUTION PHASE ===
 
-        // Build guards for partial fill logic (reuse existing code)
-        let guards = AdvancedGuardConfig {
-            price_limit_e6: order.limit_price_e6,
-            max_slippage_bps: 0,
-            quote_price_e6: current_price,
-            quote_timestamp: clock.unix_timestamp,
-            max_total_cost_e6: order.max_cost_e6,
-            allow_partial: true,
-            min_fill_shares_e6: (order.shares_e6 as i128 * order.min_fill_bps as i128 / 10000) as i64,
+        let a = ctx.accounts;
+        let accts = LimitFillAccounts {
+            vault_sol: &a.vault_sol.to_account_info(),
+            fee_dest: &a.fee_dest.to_account_info(),
+            insurance: &a.insurance.to_account_info(),
+            insurance_bump: ctx.bumps.insurance,
+            keeper: &a.keeper.to_account_info(),
+            sys: &a.system_program,
         };
-
-        // Find max executable shares (uses Newton-Raphson or binary search)
-        let executable_shares = find_max_executable_shares(
-            order.action,
-            order.side,
-            order.shares_e6,
-            &guards,
-            amm,
+        apply_limit_fill(
+            &mut a.amm,
+            &mut a.position,
+            &a.user_vault,
+            &mut a.order_fill,
+            &accts,
+            &order,
+            executable_shares,
         )?;
 
-        msg!("📊 Executing {} of {} shares", executable_shares, order.shares_e6);
-
-        // Reject orders that would execute 0 shares
-        require!(executable_shares > 0, ReaderError::MinFillNotMet);
-
-        // === TRADE EXECUTION PHASE ===
-
-        let (net_e6, dq_e6, avg_price_e6) = match (order.action, order.side) {
-            (1, 1) => {
-                // BUY YES: Calculate spend needed for executable shares
-                let spend_e6 = lmsr_buy_yes_for_shares(&amm, executable_shares)?;
-                let (dq, avg_h) = lmsr_buy_yes(&mut ctx.accounts.amm, spend_e6)?;
-                let avg_price = (spend_e6 as f64 / dq) as i64;
-
-                // Update position
-                position.yes_shares_e6 = position.yes_shares_e6.saturating_add(dq.round() as i64);
-
-                msg!("✅ BUY YES executed: {} shares @ ${:.6}", dq.round() as i64, avg_h);
-                (spend_e6, dq.round() as i64, avg_price)
-            },
-            (1, 2) => {
-                // BUY NO: Calculate spend needed for executable shares
-                let spend_e6 = lmsr_buy_no_for_shares(&amm, executable_shares)?;
-                let (dq, avg_h) = lmsr_buy_no(&mut ctx.accounts.amm, spend_e6)?;
-                let avg_price = (spend_e6 as f64 / dq) as i64;
-
-                // Update position
-                position.no_shares_e6 = position.no_shares_e6.saturating_add(dq.round() as i64);
-
-                msg!("✅ BUY NO executed: {} shares @ ${:.6}", dq.round() as i64, avg_h);
-                (spend_e6, dq.round() as i64, avg_price)
-            },
-            (2, 1) => {
-                // SELL YES: Check user has enough shares
-                require!(position.yes_shares_e6 >= executable_shares, ReaderError::InsufficientShares);
-
-                let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_yes(&mut ctx.accounts.amm, executable_shares)?;
-                let avg_price = (proceeds_e6 as f64 / sold_e6) as i64;
-
-                // Update position
-                position.yes_shares_e6 = position.yes_shares_e6.saturating_sub(sold_e6.round() as i64);
-
-                msg!("✅ SELL YES executed: {} shares @ ${:.6}", sold_e6.round() as i64, avg_h);
-                (proceeds_e6, sold_e6.round() as i64, avg_price)
-            },
-            (2, 2) => {
-                // SELL NO: Check user has enough shares
-                require!(position.no_shares_e6 >= executable_shares, ReaderError::InsufficientShares);
-
-                let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_no(&mut ctx.accounts.amm, executable_shares)?;
-                let avg_price = (proceeds_e6 as f64 / sold_e6) as i64;
-
-                // Update position
-                position.no_shares_e6 = position.no_shares_e6.saturating_sub(sold_e6.round() as i64);
-
-                msg!("✅ SELL NO executed: {} shares @ ${:.6}", sold_e6.round() as i64, avg_h);
-                (proceeds_e6, sold_e6.round() as i64, avg_price)
-            },
-            _ => return err!(ReaderError::InvalidAction),
-        };
-
-        msg!("📊 Trade completed: net={} dq={} avg_price={:.6}", net_e6, dq_e6, avg_price_e6 as f64 / 1e6);
+        msg!("✅ Limit order executed successfully!");
+        Ok(())
+    }
 
-        // === FUND TRANSFER PHASE ===
+    // ---------- EXECUTE LIMIT ORDERS (batch) ----------
+    /// Fill several signed limit orders in one transaction.
+    ///
+    /// All signatures live in one Ed25519 instruction at `verify_ix_index` (one entry per order).
+    /// `remaining_accounts` holds (position, user_vault, order_fill, nonce_fill) per order, in the
+    /// same order as `orders`. Orders fill in price-priority order: the limit furthest through the
+    /// current price first. An order that can't fill (expired, cancelled, filled, price moved
+    /// past its limit, trigger not met) is skipped instead of failing the batch.
+    pub fn execute_limit_orders<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteLimitOrders<'info>>,
+        orders: Vec<LimitOrder>,
+        signatures: Vec<[u8; 64]>,
+        verify_ix_index: u16,
+    ) -> Result<()> {
+        let rem = ctx.remaining_accounts;
+        require!(
+            !orders.is_empty() && orders.len() <= LIMIT_BATCH_MAX_ORDERS && signatures.len() == orders.len(),
+            ReaderError::BadLimitBatch
+        );
+        require!(rem.len() == orders.len() * 4, ReaderError::BadLimitBatch);
 
-        let amm = &mut ctx.accounts.amm;
-        let position = &mut ctx.accounts.position;
+        let a = ctx.accounts;
+        let amm_key = a.amm.key();
+        let now = Clock::get()?.unix_timestamp;
 
-        // Calculate protocol fee (deducted from gross amount)
-        let protocol_fee_e6 = (net_e6.abs() as i128 * amm.fee_bps as i128 / 10_000) as i64;
-        let net_after_protocol_fee_e6 = if order.action == 1 {
-            net_e6 + protocol_fee_e6  // BUY: add fee to total cost
-        } else {
-            net_e6 - protocol_fee_e6  // SELL: subtract fee from proceeds
+        // === VALIDATION PHASE (any failure rejects the whole batch) ===
+        verify_ed25519_batch(&orders, &signatures, &a.instructions.to_account_info(), verify_ix_index)?;
+        for order in &orders {
+            require_keys_eq!(order.market, amm_key, ReaderError::WrongMarket);
+        }
+        let status = a.amm.status();
+        require!(
+            status == MarketStatus::Premarket || status == MarketStatus::Open,
+            ReaderError::MarketClosed
+        );
+        check_trading_lockout(&a.amm, &a.oracle_state)?;
+        msg!("✅ {} signatures verified", orders.len());
+
+        // === PRIORITY ===
+        // Surplus of each limit over the current price of its action/side, best first
+        // (stable sort: equal surplus keeps submission order)
+        let mut queue: Vec<(usize, i64)> = Vec::with_capacity(orders.len());
+        for (i, order) in orders.iter().enumerate() {
+            let price = calculate_avg_price_for_one_share(order.action, order.side, &a.amm)?;
+            let surplus = if order.action == 1 {
+                order.limit_price_e6.saturating_sub(price)
+            } else {
+                price.saturating_sub(order.limit_price_e6)
+            };
+            queue.push((i, surplus));
+        }
+        queue.sort_by(|x, y| y.1.cmp(&x.1));
+
+        let keeper = a.keeper.to_account_info();
+        let accts = LimitFillAccounts {
+            vault_sol: &a.vault_sol.to_account_info(),
+            fee_dest: &a.fee_dest.to_account_info(),
+            insurance: &a.insurance.to_account_info(),
+            insurance_bump: ctx.bumps.insurance,
+            keeper: &keeper,
+            sys: &a.system_program,
         };
 
-        let net_lamports = (net_after_protocol_fee_e6.abs() as u64) * LAMPORTS_PER_E6;
-        let protocol_fee_lamports = (protocol_fee_e6 as u64) * LAMPORTS_PER_E6;
-
-        if order.action == 1 {
-            // === BUY ORDER: Transfer from user_vault to vault_sol ===
-
-            // Check user has sufficient balance in vault
+        // === EXECUTION PHASE ===
+        let (mut filled, mut skipped) = (0u32, 0u32);
+        for (i, _) in queue {
+            let order = &orders[i];
+            let (pos_ai, user_vault, fill_ai, nonce_ai) = (&rem[4 * i], &rem[4 * i + 1], &rem[4 * i + 2], &rem[4 * i + 3]);
             require!(
-                position.vault_balance_e6 >= net_after_protocol_fee_e6,
-                ReaderError::InsufficientBalance
+                pos_ai.is_writable && user_vault.is_writable && fill_ai.is_writable,
+                ReaderError::BadLimitBatch
             );
 
-            // Transfer from user_vault PDA to vault_sol PDA (signed transfer)
-            let pos_key = position.key();
-            let seeds: &[&[u8]] = &[
-                Position::USER_VAULT_SEED,
-                pos_key.as_ref(),
-                &[position.vault_bump],
-            ];
-            let signer_seeds = &[&seeds[..]];
-
-            transfer_sol_signed(
-                &ctx.accounts.system_program,
-                &ctx.accounts.user_vault,
-                &ctx.accounts.vault_sol,
-                net_lamports,
-                signer_seeds,
-            )?;
-
-            // Update accounting
-            position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(net_after_protocol_fee_e6);
-            amm.vault_e6 = amm.vault_e6.saturating_add(net_after_protocol_fee_e6);
-
-            // Transfer protocol fee to fee_dest
-            if protocol_fee_lamports > 0 {
-                transfer_sol_signed(
-                    &ctx.accounts.system_program,
-                    &ctx.accounts.user_vault,
-                    &ctx.accounts.fee_dest.to_account_info(),
-                    protocol_fee_lamports,
-                    signer_seeds,
-                )?;
-
-                position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(protocol_fee_e6);
-            }
-
-            msg!("💰 BUY: Transferred {} lamports from user_vault to vault_sol", net_lamports);
-            msg!("💰 Protocol fee: {} lamports to fee_dest", protocol_fee_lamports);
-        } else {
-            // === SELL ORDER: Transfer from vault_sol to user_vault ===
-
-            // Get actual vault_sol balance
-            let vault_sol_actual = ctx.accounts.vault_sol.lamports();
-            let vault_sol_actual_e6 = (vault_sol_actual / LAMPORTS_PER_E6) as i64;
-
-            // Check vault has sufficient coverage
-            require!(vault_sol_actual_e6 >= net_after_protocol_fee_e6, ReaderError::NoCoverage);
-
-            // Transfer from vault_sol PDA to user_vault PDA (signed transfer)
-            let amm_key = amm.key();
-            let seeds: &[&[u8]] = &[
-                Amm::VAULT_SOL_SEED,
-                amm_key.as_ref(),
-                &[amm.vault_sol_bump],
-            ];
-            let signer_seeds = &[&seeds[..]];
-
-            transfer_sol_signed(
-                &ctx.accounts.system_program,
-                &ctx.accounts.vault_sol,
-                &ctx.accounts.user_vault,
-                net_lamports,
-                signer_seeds,
-            )?;
-
-            // Update accounting
-            position.vault_balance_e6 = position.vault_balance_e6.saturating_add(net_after_protocol_fee_e6);
-            amm.vault_e6 = amm.vault_e6.saturating_sub(net_after_protocol_fee_e6);
-
-            // Transfer protocol fee to fee_dest
-            if protocol_fee_lamports > 0 {
-                transfer_sol_signed(
-                    &ctx.accounts.system_program,
-                    &ctx.accounts.vault_sol,
-                    &ctx.accounts.fee_dest.to_account_info(),
-                    protocol_fee_lamports,
-                    signer_seeds,
-                )?;
-
-                amm.vault_e6 = amm.vault_e6.saturating_sub(protocol_fee_e6);
-            }
-
-            msg!("💰 SELL: Transferred {} lamports from vault_sol to user_vault", net_lamports);
-            msg!("💰 Protocol fee: {} lamports to fee_dest", protocol_fee_lamports);
-        }
-
-        // === FEE PAYMENT PHASE ===
-
-        // Calculate keeper fee from the net amount
-        let keeper_fee_e6 = (net_e6.abs() as i128 * order.keeper_fee_bps as i128 / 10_000) as i64;
-        let keeper_fee_lamports = (keeper_fee_e6 as u64) * LAMPORTS_PER_E6;
-
-        // Transfer keeper fee from user_vault to keeper (using PDA signing)
-        if keeper_fee_lamports > 0 {
-            // Use user_vault PDA (which we control) instead of user's wallet
-            let pos_key = position.key();
-            let seeds: &[&[u8]] = &[
-                Position::USER_VAULT_SEED,
-                pos_key.as_ref(),
-                &[position.vault_bump],
-            ];
-            let signer_seeds = &[&seeds[..]];
-
-            transfer_sol_signed(
-                &ctx.accounts.system_program,
-                &ctx.accounts.user_vault,
-                &ctx.accounts.keeper.to_account_info(),
-                keeper_fee_lamports,
-                signer_seeds,
-            )?;
+            // Same PDAs the ExecuteLimitOrder context checks through its seeds constraints
+            let mut pos: Account<'info, Position> = Account::try_from(pos_ai)?;
+            let (pos_key, _) = Pubkey::find_program_address(
+                &[Position::SEED, amm_key.as_ref(), order.user.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(pos_ai.key(), pos_key, ReaderError::BadLimitBatch);
+            require_keys_eq!(pos.owner, order.user, ReaderError::WrongUser);
+            let vault_key = Pubkey::create_program_address(
+                &[Position::USER_VAULT_SEED, pos_key.as_ref(), &[pos.vault_bump]],
+                &crate::ID,
+            ).map_err(|_| error!(ReaderError::BadLimitBatch))?;
+            require_keys_eq!(user_vault.key(), vault_key, ReaderError::BadLimitBatch);
+            let (fill_key, fill_bump) = Pubkey::find_program_address(
+                &[order.fill_seed(), pos_key.as_ref(), &order.fill_id().to_le_bytes()],
+                &crate::ID,
+            );
+            require_keys_eq!(fill_ai.key(), fill_key, ReaderError::BadLimitBatch);
+            let (nonce_key, _) = Pubkey::find_program_address(
+                &[OrderFill::SEED, pos_key.as_ref(), &order.nonce.to_le_bytes()],
+                &crate::ID,
+            );
+            require_keys_eq!(nonce_ai.key(), nonce_key, ReaderError::BadLimitBatch);
 
-            // Update vault balance
-            position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(keeper_fee_e6);
+            // Pre-trade checks have no side effects until the fill state is created, so an
+            // order failing them is dropped (its in-memory position changes are discarded)
+            let existing = if fill_ai.owner == &crate::ID {
+                Some(Account::<'info, OrderFill>::try_from(fill_ai)?)
+            } else {
+                None
+            };
+            let planned = sync_position_round(&mut pos, &a.amm).and_then(|_| {
+                check_order_trigger(order, &a.amm, &a.oracle_state)?;
+                let fresh = OrderFill::default();
+                let fill = existing.as_deref().unwrap_or(&fresh);
+                let nonce_fill = if order.group_id != 0 { Some(read_order_fill(nonce_ai)?) } else { None };
+                plan_limit_fill(&a.amm, &pos, fill, nonce_fill.as_ref().unwrap_or(fill), order, now)
+            });
+            let executable_shares = match planned {
+                Ok(shares) => shares,
+                Err(e) => {
+                    msg!("⏭️  Order {} of {} skipped: {}", order.nonce, order.user, e);
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            let mut fill = match existing {
+                Some(fill) => fill,
+                None => {
+                    let seeds: &[&[u8]] = &[order.fill_seed(), pos_key.as_ref(), &order.fill_id().to_le_bytes(), &[fill_bump]];
+                    create_pda_account(fill_ai, &keeper, &a.system_program, 8 + OrderFill::SPACE, seeds)?;
+                    let mut fill = Account::<'info, OrderFill>::try_from_unchecked(fill_ai)?;
+                    fill.open(pos_key, order.fill_id(), order.expiry_ts, keeper.key(), fill_bump);
+                    fill
+                }
+            };
 
-            msg!("💸 Keeper fee paid: {} lamports to {}", keeper_fee_lamports, ctx.accounts.keeper.key);
+            apply_limit_fill(&mut a.amm, &mut pos, user_vault, &mut fill, &accts, order, executable_shares)?;
+            pos.exit(&crate::ID)?;
+            fill.exit(&crate::ID)?;
+            filled += 1;
         }
 
-        // === CLEANUP PHASE ===
-
-        // Mark nonce as used (replay protection with rolling window)
-        // Remove oldest nonce BEFORE adding new one to stay at or below MAX_NONCES
-        if position.used_nonces.len() >= Position::MAX_NONCES {
-            position.used_nonces.remove(0);
-        }
-        position.used_nonces.push(order.nonce);
-
-        // Emit event
-        emit!(LimitOrderExecuted {
-            user: order.user,
-            keeper: *ctx.accounts.keeper.key,
-            action: order.action,
-            side: order.side,
-            shares_requested: order.shares_e6,
-            shares_executed: executable_shares,
-            limit_price: order.limit_price_e6,
-            execution_price: avg_price_e6,
-            keeper_fee_bps: order.keeper_fee_bps,
-            nonce: order.nonce,
+        emit!(LimitOrdersBatchExecuted {
+            keeper: keeper.key(),
+            orders: orders.len() as u32,
+            filled,
+            skipped,
         });
-
-        msg!("✅ Limit order executed successfully!");
+        msg!("📦 Batch: {} filled, {} skipped of {}", filled, skipped, orders.len());
         Ok(())
     }
 
@@ -2414,17 +3653,19 @@ pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
     ) -> Result<()> {
         let position = &mut ctx.accounts.position;
 
-        // Add nonce to used list (prevents execution)
+        // Mark the nonce's OrderFill state cancelled (stops any further fills; an OCO order fills
+        // through its group's state, but the fill path checks this one too). An order that never
+        // filled has no known expiry, so its cancel state is never closable.
         require!(
             !position.used_nonces.contains(&nonce),
             ReaderError::NonceAlreadyUsed
         );
-
-        // Add nonce with rolling window cleanup
-        if position.used_nonces.len() >= Position::MAX_NONCES {
-            position.used_nonces.remove(0);
+        let fill = &mut ctx.accounts.order_fill;
+        if !fill.is_open() {
+            fill.open(position.key(), nonce, i64::MAX, ctx.accounts.owner.key(), ctx.bumps.order_fill);
         }
-        position.used_nonces.push(nonce);
+        require!(!fill.cancelled, ReaderError::NonceAlreadyUsed);
+        fill.cancelled = true;
 
         emit!(OrderNonceCancelled {
             user: position.owner,
@@ -2434,6 +3675,52 @@ pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
         msg!("✅ Order nonce {} cancelled for user {}", nonce, position.owner);
         Ok(())
     }
+
+    /// Cancel every order of an OCO group: they share the group's OrderFill state.
+    pub fn cancel_order_group(ctx: Context<CancelOrderGroup>, group_id: u64) -> Result<()> {
+        require!(group_id != 0, ReaderError::BadParam);
+        let position = &ctx.accounts.position;
+        let fill = &mut ctx.accounts.order_fill;
+        if !fill.is_open() {
+            fill.open(position.key(), group_id, i64::MAX, ctx.accounts.owner.key(), ctx.bumps.order_fill);
+        }
+        require!(!fill.cancelled, ReaderError::NonceAlreadyUsed);
+        fill.cancelled = true;
+
+        emit!(OrderGroupCancelled {
+            user: position.owner,
+            group_id,
+        });
+
+        msg!("✅ Order group {} cancelled for user {}", group_id, position.owner);
+        Ok(())
+    }
+
+    /// Void every resting order signed with a nonce below `min_nonce` in one transaction.
+    /// The watermark only moves up; clients sign with microsecond timestamps, so passing
+    /// the current time in µs cancels everything signed so far.
+    pub fn cancel_all_orders(ctx: Context<CancelAllOrders>, min_nonce: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        require!(min_nonce > position.min_nonce, ReaderError::BadParam);
+        position.min_nonce = min_nonce;
+
+        emit!(AllOrdersCancelled {
+            user: position.owner,
+            min_nonce,
+        });
+
+        msg!("✅ All orders with nonce < {} cancelled for user {}", min_nonce, position.owner);
+        Ok(())
+    }
+
+    /// Permissionless: once its order has expired a replay guard has nothing left to protect,
+    /// so its rent goes back to the payer.
+    pub fn close_order_fill(ctx: Context<CloseOrderFill>) -> Result<()> {
+        let fill = &ctx.accounts.order_fill;
+        require!(Clock::get()?.unix_timestamp >= fill.expiry_ts, ReaderError::OrderNotExpired);
+        msg!("🧹 Order fill guard closed: position={} nonce={}", fill.position, fill.nonce);
+        Ok(())
+    }
 }
 
 // ============================== Helpers & LMSR math ==============================
@@ -2845,6 +4132,18 @@ fn binary_search_range(
     Ok(best)
 }
 
+/// Shares a reduce-only trade may fill: enough to bring net exposure (yes - no) to zero without
+/// flipping it. A SELL trims the larger side, a BUY tops up the smaller one.
+fn reducible_shares(action: u8, side: u8, pos: &Position) -> i64 {
+    let (own, other) = if side == 1 {
+        (pos.yes_shares_e6, pos.no_shares_e6)
+    } else {
+        (pos.no_shares_e6, pos.yes_shares_e6)
+    };
+    let excess = if action == 2 { own.saturating_sub(other) } else { other.saturating_sub(own) };
+    excess.max(0)
+}
+
 /// Validate advanced guards and return shares to execute
 /// Returns the number of shares to execute (may be less than requested if partial fills enabled)
 fn validate_advanced_guards(
@@ -2863,57 +4162,414 @@ fn validate_advanced_guards(
         );
     }
 
-    // 2. Validate guard configuration
-    // (min_fill_shares_e6 = 0 is allowed, means no minimum enforced)
+    // 2. Validate guard configuration
+    // (min_fill_shares_e6 = 0 is allowed, means no minimum enforced)
+
+    // 3. Try full execution first
+    if shares_pass_guards(amount_e6, action, side, guards, amm)? {
+        msg!("✅ ADVANCED GUARDS: Full execution allowed for {} shares", amount_e6);
+        return Ok(amount_e6);
+    }
+
+    // 4. If partial fills not allowed, reject
+    if !guards.allow_partial {
+        msg!("❌ ADVANCED GUARDS: Full execution failed, partial not allowed");
+        if guards.has_price_limit() {
+            return err!(ReaderError::PriceLimitExceeded);
+        } else if guards.has_slippage_guard() {
+            return err!(ReaderError::SlippageExceeded);
+        } else if guards.has_cost_limit() {
+            return err!(ReaderError::CostExceedsLimit);
+        } else {
+            return err!(ReaderError::InvalidGuardConfig);
+        }
+    }
+
+    // 5. Binary search for max executable shares
+    msg!("🔍 ADVANCED GUARDS: Searching for partial fill...");
+    let executable = find_max_executable_shares(action, side, amount_e6, guards, amm)?;
+
+    // 6. Check if any shares can be executed
+    if executable == 0 {
+        msg!("❌ ADVANCED GUARDS: No shares can be executed within guard constraints");
+        if guards.has_price_limit() {
+            return err!(ReaderError::PriceLimitExceeded);
+        } else if guards.has_slippage_guard() {
+            return err!(ReaderError::SlippageExceeded);
+        } else if guards.has_cost_limit() {
+            return err!(ReaderError::CostExceedsLimit);
+        } else {
+            return err!(ReaderError::InvalidGuardConfig);
+        }
+    }
+
+    // 7. Check minimum fill requirement (only if min_fill > 0)
+    if guards.min_fill_shares_e6 > 0 && executable < guards.min_fill_shares_e6 {
+        msg!("❌ ADVANCED GUARDS: Max executable {} below min fill {}",
+             executable, guards.min_fill_shares_e6);
+        return err!(ReaderError::MinFillNotMet);
+    }
+
+    msg!("✅ ADVANCED GUARDS: Partial execution allowed: {}/{} shares",
+         executable, amount_e6);
+    Ok(executable)
+}
+
+// ---- round helpers ----
+
+/// Stamp a position whose shares were minted in an earlier round with the current one,
+/// parking those shares so they stay redeemable against their round's result.
+/// Returns true if the position was stale.
+fn sync_position_round(pos: &mut Position, amm: &Amm) -> Result<bool> {
+    if pos.round_id == amm.round_id {
+        return Ok(false);
+    }
+    if pos.yes_shares_e6 != 0 || pos.no_shares_e6 != 0 {
+        // One parking slot: an older unclaimed round has to be redeemed first
+        require!(!pos.has_carry(), ReaderError::UnclaimedCarry);
+        msg!("♻️  Stale position (round {} → {}): parking YES={} NO={} for redemption",
+             pos.round_id, amm.round_id, pos.yes_shares_e6, pos.no_shares_e6);
+        pos.carry_round_id = pos.round_id;
+        pos.carry_yes_e6 = pos.yes_shares_e6;
+        pos.carry_no_e6 = pos.no_shares_e6;
+    }
+    pos.yes_shares_e6 = 0;
+    pos.no_shares_e6 = 0;
+    pos.round_id = amm.round_id;
+    Ok(true)
+}
+
+/// Move what this round still owes its winners into the carried reserve before the AMM is
+/// reset, and rebuild vault_e6 from the vault's lamports minus everything still reserved or owed.
+fn carry_liabilities(amm: &mut Amm, vault_lamports: u64) {
+    amm.reserved_e6 = amm.reserved_e6.saturating_add(amm.liability_e6.max(0));
+    amm.liability_e6 = 0;
+    amm.vault_e6 = lamports_to_e6(vault_lamports)
+        .saturating_sub(amm.reserved_e6)
+        .saturating_sub(amm.iou_e6.max(0))
+        .max(0);
+}
+
+fn load_round_result(ai: &AccountInfo) -> Result<Option<RoundResult>> {
+    if ai.data_is_empty() {
+        return Ok(None);
+    }
+    require_keys_eq!(*ai.owner, crate::ID, ReaderError::WrongOwner);
+    let data = ai.try_borrow_data()?;
+    Ok(Some(RoundResult::try_deserialize(&mut &data[..])?))
+}
+
+fn store_round_result(ai: &AccountInfo, rr: &RoundResult) -> Result<()> {
+    let mut data = ai.try_borrow_mut_data()?;
+    rr.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// The shares the next redeem settles: the parked carry if there is one, else the position's own.
+struct Claim {
+    carry: bool,
+    earlier: bool,   // claim is on a round before the AMM's current one
+    round_id: u64,
+    yes_e6: i64,
+    no_e6: i64,
+}
+
+fn pending_claim(pos: &Position, amm: &Amm) -> Claim {
+    let carry = pos.has_carry();
+    let (round_id, yes_e6, no_e6) = if carry {
+        (pos.carry_round_id, pos.carry_yes_e6, pos.carry_no_e6)
+    } else {
+        (pos.round_id, pos.yes_shares_e6, pos.no_shares_e6)
+    };
+    Claim { carry, earlier: carry || pos.round_id != amm.round_id, round_id, yes_e6, no_e6 }
+}
+
+/// Zero the claimed shares; a stale position is restamped with the current round.
+fn clear_claim(pos: &mut Position, claim: &Claim, current_round: u64) {
+    if claim.carry {
+        pos.carry_round_id = 0;
+        pos.carry_yes_e6 = 0;
+        pos.carry_no_e6 = 0;
+    } else {
+        pos.yes_shares_e6 = 0;
+        pos.no_shares_e6 = 0;
+        pos.round_id = current_round;
+    }
+}
+
+/// Theoretical payout of a claim at the round's snapshot pps (winning shares bounded by W).
+fn claim_payout_e6(rr: &RoundResult, claim: &Claim) -> i64 {
+    let win_sh_e6 = match rr.winner {
+        1 => claim.yes_e6,
+        2 => claim.no_e6,
+        WINNER_VOID => claim.yes_e6.max(0) + claim.no_e6.max(0),
+        _ => 0,
+    };
+    if win_sh_e6 <= 0 {
+        return 0;
+    }
+    let win_clip = win_sh_e6.min(rr.w_total_e6.max(0));
+    ((win_clip as i128) * (rr.pps_e6 as i128) / 1_000_000i128) as i64
+}
+
+/// Take a settled claim off the mirror that covered it (vault + liability for the current round,
+/// reserve for earlier ones); the unpaid part moves to the IOU total, which claim_iou keeps out
+/// of the surplus it pays from.
+fn book_claim(amm: &mut Amm, earlier: bool, paid_e6: i64, iou_e6: i64) {
+    if earlier {
+        let booked = paid_e6.saturating_add(iou_e6).min(amm.reserved_e6.max(0));
+        amm.reserved_e6 = amm.reserved_e6.saturating_sub(booked);
+    } else {
+        let booked = paid_e6.saturating_add(iou_e6).min(amm.vault_e6.max(0));
+        amm.vault_e6 = amm.vault_e6.saturating_sub(booked);
+        amm.liability_e6 = amm.liability_e6.saturating_sub(booked).max(0);
+    }
+    amm.iou_e6 = amm.iou_e6.saturating_add(iou_e6);
+}
+
+/// Lamport accounts a redemption moves funds between.
+#[derive(Clone, Copy)]
+struct Payout<'a, 'info> {
+    vault_sol: &'a AccountInfo<'info>,
+    user_vault: &'a AccountInfo<'info>,
+    insurance: &'a AccountInfo<'info>,
+    insurance_bump: u8,
+    sys: &'a Program<'info, System>,
+}
+
+/// Shared body of `redeem` / `admin_redeem`: pays one round's claim into the user vault.
+///
+/// The claim is the parked carry if there is one, else the position's own shares. Claims on the
+/// current round are bounded by vault_e6; claims on an earlier round by that round's unpaid
+/// liability and the carried reserve. Any shortfall is recorded as an IOU on the position.
+/// A round with no RoundResult never settled, so its shares are simply dropped.
+fn redeem_claim<'info>(
+    amm: &mut Account<'info, Amm>,
+    pos: &mut Account<'info, Position>,
+    round_result: &AccountInfo<'info>,
+    pay: &Payout<'_, 'info>,
+    tag: &str,
+) -> Result<()> {
+    let Payout { vault_sol, user_vault, insurance, insurance_bump, sys } = *pay;
+    let claim = pending_claim(pos, amm);
+    let (claim_round, earlier) = (claim.round_id, claim.earlier);
+
+    if !earlier {
+        // Must be stopped (settlement values calculated) and winner determined
+        require!(amm.status() == MarketStatus::Stopped, ReaderError::WrongState);
+        require!(amm.winner != 0, ReaderError::WrongState);
+    }
+
+    let Some(mut rr) = load_round_result(round_result)? else {
+        require!(earlier, ReaderError::WrongState);
+        clear_claim(pos, &claim, amm.round_id);
+        msg!("{}: round {} never settled; shares dropped.", tag, claim_round);
+        return Ok(());
+    };
+    require!(rr.round_id == claim_round && rr.winner != 0, ReaderError::WrongState);
+    // Past the claim deadline the unpaid liability moved to the treasury
+    require!(rr.swept_e6 == 0, ReaderError::ClaimSwept);
+
+    // nothing to do → wipe losing shares and return
+    let theoretical_e6 = claim_payout_e6(&rr, &claim);
+    if theoretical_e6 <= 0 {
+        clear_claim(pos, &claim, amm.round_id);
+        msg!("{}: no winning shares in round {}; claim wiped.", tag, claim_round);
+        return Ok(());
+    }
+
+    // Coverage bound (accounting units): live vault for this round, ring-fenced reserve for earlier ones
+    let cover_e6 = if earlier {
+        rr.liability_e6.saturating_sub(rr.redeemed_e6).saturating_sub(rr.iou_e6).min(amm.reserved_e6)
+    } else {
+        amm.vault_e6
+    };
+    let mirror_bound_e6 = theoretical_e6.min(cover_e6.max(0));
+
+    // Lamports availability bound (keep >= 1 SOL); the insurance fund backs any gap
+    let want_lamports = e6_to_lamports(mirror_bound_e6);
+    let gap_lamports = want_lamports.saturating_sub(vault_sol.lamports().saturating_sub(MIN_VAULT_LAMPORTS));
+    if gap_lamports > 0 {
+        draw_insurance(amm, insurance, vault_sol, sys, lamports_to_e6(gap_lamports), insurance_bump, INSURANCE_DRAW_REDEEM)?;
+    }
+    let vault_lamports_now = vault_sol.lamports();
+    let available_lamports = vault_lamports_now.saturating_sub(MIN_VAULT_LAMPORTS);
+    let pay_lamports = want_lamports.min(available_lamports);
+
+    // Convert actual lamports paid back to e6 for mirror accounting
+    let pay_e6_effective = lamports_to_e6(pay_lamports);
+    if pay_lamports > 0 {
+        // Pay to user_vault PDA (not session wallet)
+        let amm_key = amm.key();
+        let seeds: &[&[u8]] = &[
+            Amm::VAULT_SOL_SEED,
+            amm_key.as_ref(),
+            core::slice::from_ref(&amm.vault_sol_bump),
+        ];
+        transfer_sol_signed(sys, vault_sol, user_vault, pay_lamports, &[seeds])?;
+    }
+
+    // Whatever the bounds held back becomes an IOU, payable by claim_iou once the vault is topped up
+    let shortfall_e6 = theoretical_e6.saturating_sub(pay_e6_effective).max(0);
+
+    // ---- mutate mirrors and clear the claim
+    book_claim(amm, earlier, pay_e6_effective, shortfall_e6);
+    rr.redeemed_e6 = rr.redeemed_e6.saturating_add(pay_e6_effective);
+    rr.iou_e6 = rr.iou_e6.saturating_add(shortfall_e6);
+    store_round_result(round_result, &rr)?;
+
+    if shortfall_e6 > 0 {
+        pos.iou_e6 = pos.iou_e6.saturating_add(shortfall_e6);
+        pos.iou_round_id = claim_round;
+        msg!("⚠️  {}: Reserve/coverage bound: short by {} e6 (vault={}, keep_reserve={}); IOU now {} e6",
+             tag, shortfall_e6, vault_lamports_now, MIN_VAULT_LAMPORTS, pos.iou_e6);
+    }
+
+    clear_claim(pos, &claim, amm.round_id);
+    // Update user vault balance tracking
+    pos.vault_balance_e6 = pos.vault_balance_e6.saturating_add(pay_e6_effective);
+
+    msg!(
+        "💸 {} round={}{} pay={} lamports ({:.9} SOL) to user_vault; kept_reserve={} lamports; pps={:.6}, winner={}; vault_balance={} e6",
+        tag,
+        claim_round,
+        if earlier { " (carried)" } else { "" },
+        pay_lamports,
+        (pay_lamports as f64)/1e9,
+        vault_sol.lamports().min(MIN_VAULT_LAMPORTS),
+        (rr.pps_e6 as f64)/1_000_000.0,
+        rr.winner,
+        pos.vault_balance_e6
+    );
+    Ok(())
+}
+
+/// Persist the settled round's outcome and stats so they survive close_amm/init_amm.
+fn record_round_result(rr: &mut RoundResult, amm: &Amm, bump: u8) -> Result<()> {
+    rr.round_id = amm.round_id;
+    rr.winner = amm.winner;
+    rr.bump = bump;
+    rr.start_price_e6 = amm.start_price_e6;
+    rr.start_ts = amm.start_ts;
+    rr.settle_price_e6 = amm.settle_price_e6;
+    rr.settle_ts = amm.settle_ts;
+    rr.settled_at = Clock::get()?.unix_timestamp;
+    rr.w_total_e6 = amm.w_total_e6;
+    rr.pps_e6 = amm.pps_e6;
+    rr.fees_e6 = amm.fees;
+    rr.volume_e6 = amm.volume_e6;
+    rr.liability_e6 = amm.liability_e6;
+    rr.redeemed_e6 = 0;
+    rr.claim_deadline = rr.settled_at.saturating_add(amm.claim_window_secs());
+    rr.swept_e6 = 0;
+    rr.late_paid_e6 = 0;
+    rr.iou_e6 = 0;
+
+    msg!("📜 Round {} recorded: winner={} pps={} W={} owed={} fees={} volume={}",
+         rr.round_id, rr.winner, rr.pps_e6, rr.w_total_e6, rr.liability_e6, rr.fees_e6, rr.volume_e6);
+    Ok(())
+}
 
-    // 3. Try full execution first
-    if shares_pass_guards(amount_e6, action, side, guards, amm)? {
-        msg!("✅ ADVANCED GUARDS: Full execution allowed for {} shares", amount_e6);
-        return Ok(amount_e6);
-    }
+/// YES wins if the settle price beat the start (or tied, when `ge_wins_yes`).
+fn oracle_winner(curr_e6: i64, start_e6: i64, ge_wins_yes: bool) -> u8 {
+    let yes = if ge_wins_yes { curr_e6 >= start_e6 } else { curr_e6 > start_e6 };
+    if yes { 1 } else { 2 }
+}
 
-    // 4. If partial fills not allowed, reject
-    if !guards.allow_partial {
-        msg!("❌ ADVANCED GUARDS: Full execution failed, partial not allowed");
-        if guards.has_price_limit() {
-            return err!(ReaderError::PriceLimitExceeded);
-        } else if guards.has_slippage_guard() {
-            return err!(ReaderError::SlippageExceeded);
-        } else if guards.has_cost_limit() {
-            return err!(ReaderError::CostExceedsLimit);
-        } else {
-            return err!(ReaderError::InvalidGuardConfig);
-        }
-    }
+/// Set winner and snapshot W (total winning shares at stop) and
+/// pps = min(1e6, floor(vault / W)) with exact integers. Returns pps.
+fn apply_settlement(amm: &mut Amm, winner: u8) -> i64 {
+    amm.winner = winner;
+    let w = if winner == 1 { amm.q_yes } else { amm.q_no };
+    amm.w_total_e6 = w.max(0);
+
+    let pps_e6 = payout_pps_e6(amm.vault_e6, amm.w_total_e6);
+    amm.pps_e6 = pps_e6;
+    amm.liability_e6 = owed_e6(amm.w_total_e6, pps_e6);
+    pps_e6
+}
 
-    // 5. Binary search for max executable shares
-    msg!("🔍 ADVANCED GUARDS: Searching for partial fill...");
-    let executable = find_max_executable_shares(action, side, amount_e6, guards, amm)?;
+/// Void: every share, YES or NO, counts toward W and is refunded at the same pps.
+fn apply_void(amm: &mut Amm) -> i64 {
+    amm.winner = WINNER_VOID;
+    amm.w_total_e6 = amm.q_yes.max(0).saturating_add(amm.q_no.max(0));
 
-    // 6. Check if any shares can be executed
-    if executable == 0 {
-        msg!("❌ ADVANCED GUARDS: No shares can be executed within guard constraints");
-        if guards.has_price_limit() {
-            return err!(ReaderError::PriceLimitExceeded);
-        } else if guards.has_slippage_guard() {
-            return err!(ReaderError::SlippageExceeded);
-        } else if guards.has_cost_limit() {
-            return err!(ReaderError::CostExceedsLimit);
-        } else {
-            return err!(ReaderError::InvalidGuardConfig);
-        }
-    }
+    let pps_e6 = payout_pps_e6(amm.vault_e6, amm.w_total_e6);
+    amm.pps_e6 = pps_e6;
+    amm.liability_e6 = owed_e6(amm.w_total_e6, pps_e6);
+    pps_e6
+}
 
-    // 7. Check minimum fill requirement (only if min_fill > 0)
-    if guards.min_fill_shares_e6 > 0 && executable < guards.min_fill_shares_e6 {
-        msg!("❌ ADVANCED GUARDS: Max executable {} below min fill {}",
-             executable, guards.min_fill_shares_e6);
-        return err!(ReaderError::MinFillNotMet);
+/// Payout per share: min(1e6, floor(vault / W)) with exact integers (0 when nothing is owed).
+fn payout_pps_e6(vault_e6: i64, w_total_e6: i64) -> i64 {
+    if w_total_e6 <= 0 {
+        return 0;
     }
+    let num: i128 = (vault_e6.max(0) as i128) * 1_000_000i128;
+    ((num / w_total_e6 as i128) as i64).min(1_000_000)
+}
 
-    msg!("✅ ADVANCED GUARDS: Partial execution allowed: {}/{} shares",
-         executable, amount_e6);
-    Ok(executable)
+/// Total owed to winners: W * pps (1e6).
+#[inline]
+fn owed_e6(w_total_e6: i64, pps_e6: i64) -> i64 {
+    ((w_total_e6.max(0) as i128) * (pps_e6.max(0) as i128) / 1_000_000i128) as i64
+}
+
+/// Reset the singleton AMM in place for a new round (PREMARKET, fresh round_id).
+/// Unclaimed winnings carry over into `reserved_e6`; positions from the previous round
+/// park their shares on their next touch via the new round_id.
+fn reset_round(amm: &mut Amm, b: i64, fee_bps: u16, vault_lamports: u64) -> Result<()> {
+    carry_liabilities(amm, vault_lamports);
+
+    amm.b = b;
+    amm.fee_bps = fee_bps;
+    amm.q_yes = 0;
+    amm.q_no = 0;
+    amm.fees = 0;
+    amm.volume_e6 = 0;
+
+    amm.status = MarketStatus::Premarket as u8;
+    amm.winner = 0;
+    amm.w_total_e6 = 0;
+    amm.pps_e6 = 0;
+
+    amm.start_price_e6 = 0;
+    amm.start_ts = 0;
+    amm.settle_price_e6 = 0;
+    amm.settle_ts = 0;
+
+    amm.market_end_slot = 0;
+    amm.market_end_time = 0;
+    amm.round_id = amm.round_id.checked_add(1).ok_or(ReaderError::BadParam)?;
+    Ok(())
+}
+
+/// Open the next round of a series: reset in place, then go straight to OPEN from the template.
+fn open_next_round(amm: &mut Amm, p: &SeriesParams, vault_lamports: u64, price_e6: i64, ts: i64, now: i64) -> Result<()> {
+    reset_round(amm, p.b, p.fee_bps, vault_lamports)?;
+
+    amm.status = MarketStatus::Open as u8;
+    amm.start_price_e6 = price_e6;
+    amm.start_ts = ts;
+    amm.market_end_time = now.saturating_add(p.duration_secs);
+    amm.asset = p.asset;
+    amm.lockout_secs = p.lockout_secs;
+    Ok(())
+}
+
+fn emit_round_opened(amm: &Amm) {
+    emit!(RoundOpened {
+        round_id: amm.round_id,
+        asset: amm.asset(),
+        start_price_e6: amm.start_price_e6,
+        start_ts: amm.start_ts,
+        market_end_time: amm.market_end_time,
+    });
+}
+
+#[inline]
+fn record_volume(amm: &mut Amm, net_e6: i64) {
+    amm.volume_e6 = amm.volume_e6.saturating_add(net_e6.max(0));
 }
 
 // ---- logging helpers ----
@@ -3096,173 +4752,897 @@ fn lmsr_buy_no_for_shares(amm: &Amm, desired_shares_e6: i64) -> Result<i64> {
     Ok(spend_e6)
 }
 
-// ============================== ORACLE helpers ==============================
+// ============================== ORACLE helpers ==============================
+
+const ORACLE_MAX_AGE_SECS: i64 = 90; // adjust to your feed cadence
+const ORACLE_MAX_FUTURE_MS: i64 = 5_000; // tolerated clock skew between publishers and the cluster
+
+fn median3(a: i64, b: i64, c: i64) -> i64 {
+    let mut v = [a,b,c];
+    v.sort();
+    v[1]
+}
+
+// Bytes layout we actually have (same as your JS):
+//  [8]  anchor discriminator (skip)
+//  [32] update_authority pubkey
+//  [48] btc:  param1, param2, param3 (i64) | ts1, ts2, ts3 (i64)
+//  [48] eth:  same layout
+//  [48] sol:  same layout
+//  [1]  decimals (u8)
+//  [1]  bump     (u8)
+
+fn read_i64_le(slice: &[u8]) -> i64 {
+    let mut arr = [0u8; 8];
+    arr.copy_from_slice(slice);
+    i64::from_le_bytes(arr)
+}
+
+fn median3_i64(a: i64, b: i64, c: i64) -> i64 {
+    let mut v = [a, b, c];
+    v.sort();
+    v[1]
+}
+
+/// One oracle read, normalised across adapters and checked by `check_oracle_sample`.
+#[derive(Clone, Copy, Debug)]
+struct OracleReading {
+    price_e6: i64,
+    conf_e6: i64,   // confidence / spread around price_e6 (1e6)
+    ts_ms: i64,     // publish time of the price used, unix milliseconds
+}
+
+/// Raw sources as parsed from the account: three (price, ts) pairs for the triplet oracle,
+/// a single one (plus the publisher's confidence) for Pyth-style and push feeds.
+#[derive(Clone, Copy, Debug)]
+struct OracleSample {
+    sources: [(i64, i64); 3], // (price_e6, ts_ms), each price paired with its own timestamp
+    n: usize,
+    conf_e6: i64,             // single-source feeds only: ± confidence (1e6)
+}
+
+impl OracleSample {
+    fn single(price_e6: i64, conf_e6: i64, ts_ms: i64) -> Self {
+        OracleSample { sources: [(price_e6, ts_ms); 3], n: 1, conf_e6 }
+    }
+
+    /// Median source timestamp, unchecked (trading lockout only needs the oracle's clock).
+    fn ts_ms(&self) -> i64 {
+        if self.n == 1 { return self.sources[0].1; }
+        let [a, b, c] = self.sources;
+        median3_i64(a.1, b.1, c.1)
+    }
+}
+
+/// Per-market sanity limits for `check_oracle_sample`.
+#[derive(Clone, Copy, Debug)]
+struct OracleChecks {
+    max_spread_bps: u16, // max-min of the fresh sources (or 2x confidence) relative to price
+    max_age_ms: i64,     // a source older than this is not fresh
+}
+
+/// Validate a sample and reduce it to one price.
+///
+/// A source counts only if its price is positive and its own timestamp is within max_age_ms of
+/// now (and at most ORACLE_MAX_FUTURE_MS ahead of the cluster clock). Multi-source feeds need 2 of 3 such sources (OracleQuorum); a
+/// single-source feed needs its one (StaleOracle). The price is the median of the fresh sources,
+/// carried with that source's timestamp; with two, their mean at the older timestamp.
+/// The fresh sources' max-min spread (2x confidence for single-source feeds) must stay within
+/// max_spread_bps of the price (OracleSpreadTooWide).
+fn check_oracle_sample(sample: &OracleSample, checks: OracleChecks, now_ms: i64) -> Result<OracleReading> {
+    let mut fresh = [(0i64, 0i64); 3];
+    let mut k = 0;
+    for &(price, ts) in &sample.sources[..sample.n] {
+        let age = now_ms.saturating_sub(ts);
+        if price > 0 && (-ORACLE_MAX_FUTURE_MS..=checks.max_age_ms).contains(&age) {
+            fresh[k] = (price, ts);
+            k += 1;
+        }
+    }
+    if sample.n == 1 {
+        require!(k == 1, ReaderError::StaleOracle);
+    } else {
+        require!(k >= 2, ReaderError::OracleQuorum);
+    }
+
+    let fresh = &mut fresh[..k];
+    fresh.sort_by_key(|&(price, _)| price);
+    let (price_e6, ts_ms) = match k {
+        3 => fresh[1],
+        2 => (((fresh[0].0 as i128 + fresh[1].0 as i128) / 2) as i64, fresh[0].1.min(fresh[1].1)),
+        _ => fresh[0],
+    };
+    let spread_e6 = if k == 1 {
+        sample.conf_e6.max(0).saturating_mul(2)
+    } else {
+        fresh[k - 1].0 - fresh[0].0
+    };
+
+    let spread_bps = (spread_e6 as i128) * 10_000 / (price_e6 as i128);
+    require!(spread_bps <= checks.max_spread_bps as i128, ReaderError::OracleSpreadTooWide);
+
+    let conf_e6 = if k == 1 { sample.conf_e6 } else { spread_e6 };
+    Ok(OracleReading { price_e6, conf_e6, ts_ms })
+}
+
+/// Scale a raw `value * 10^expo` price into 1e6 fixed point (i128 math, clamped to i64).
+fn scale_to_e6(value: i128, expo: i32) -> i64 {
+    let shift = 6 + expo;
+    let v = if shift >= 0 {
+        value.saturating_mul(10i128.pow(shift.unsigned_abs().min(30)))
+    } else {
+        value / 10i128.pow(shift.unsigned_abs().min(30))
+    };
+    v.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Triplet oracle (ORACLE_PROGRAM_ID): the asset's three sources, each with its own timestamp.
+fn parse_triplet_oracle(data: &[u8], asset: u8) -> Result<OracleSample> {
+    require!(data.len() >= 8 + 32 + 48*3 + 2, ReaderError::DataTooSmall);
+
+    let d = &data[8..]; // skip discriminator
+    let mut o: usize = 0;
+
+    // update_authority
+    o += 32;
+
+    // skip to the asset's triplet (btc, eth, sol: 48 bytes each)
+    let slot = match asset {
+        ASSET_BTC => 0,
+        ASSET_ETH => 1,
+        ASSET_SOL => 2,
+        _ => return err!(ReaderError::BadParam),
+    };
+    o += 48 * slot;
+
+    // triplet: param1..3, ts1..3
+    let p1 = read_i64_le(&d[o..o+8]); o += 8;
+    let p2 = read_i64_le(&d[o..o+8]); o += 8;
+    let p3 = read_i64_le(&d[o..o+8]); o += 8;
+    let t1 = read_i64_le(&d[o..o+8]); o += 8;
+    let t2 = read_i64_le(&d[o..o+8]); o += 8;
+    let t3 = read_i64_le(&d[o..o+8]); o += 8;
+
+    // skip the remaining triplets
+    o += 48 * (2 - slot);
+
+    // decimals (u8); bump follows
+    let decimals = d[o] as i32;
+
+    let e6 = |p: i64| scale_to_e6(p as i128, -decimals);
+    Ok(OracleSample {
+        sources: [(e6(p1), t1), (e6(p2), t2), (e6(p3), t3)],
+        n: 3,
+        conf_e6: 0,
+    })
+}
+
+// Pyth v2 price account offsets (little endian)
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_ATYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_OFF_ATYPE: usize = 8;
+const PYTH_OFF_EXPO: usize = 20;
+const PYTH_OFF_TIMESTAMP: usize = 96;
+const PYTH_OFF_AGG_PRICE: usize = 208;
+const PYTH_OFF_AGG_CONF: usize = 216;
+const PYTH_OFF_AGG_STATUS: usize = 224;
+const PYTH_MIN_LEN: usize = 240;
+
+fn read_u32_le(slice: &[u8]) -> u32 {
+    let mut arr = [0u8; 4];
+    arr.copy_from_slice(slice);
+    u32::from_le_bytes(arr)
+}
+
+/// Pyth-style price account: aggregate price/conf scaled by `expo`; only a Trading aggregate is accepted.
+fn parse_pyth_price(data: &[u8]) -> Result<OracleSample> {
+    require!(data.len() >= PYTH_MIN_LEN, ReaderError::DataTooSmall);
+    require!(read_u32_le(&data[0..4]) == PYTH_MAGIC, ReaderError::DeserializeFail);
+    require!(read_u32_le(&data[PYTH_OFF_ATYPE..PYTH_OFF_ATYPE+4]) == PYTH_ATYPE_PRICE, ReaderError::DeserializeFail);
+    require!(
+        read_u32_le(&data[PYTH_OFF_AGG_STATUS..PYTH_OFF_AGG_STATUS+4]) == PYTH_STATUS_TRADING,
+        ReaderError::StaleOracle
+    );
+
+    let expo = read_u32_le(&data[PYTH_OFF_EXPO..PYTH_OFF_EXPO+4]) as i32;
+    let price = read_i64_le(&data[PYTH_OFF_AGG_PRICE..PYTH_OFF_AGG_PRICE+8]);
+    let conf = read_i64_le(&data[PYTH_OFF_AGG_CONF..PYTH_OFF_AGG_CONF+8]) as u64;
+    let ts_secs = read_i64_le(&data[PYTH_OFF_TIMESTAMP..PYTH_OFF_TIMESTAMP+8]);
+
+    Ok(OracleSample::single(
+        scale_to_e6(price as i128, expo),
+        scale_to_e6(conf as i128, expo),
+        ts_secs.saturating_mul(1000),
+    ))
+}
+
+/// PushOracle account (Anchor layout, discriminator checked).
+fn parse_push_oracle(data: &[u8], asset: u8) -> Result<OracleSample> {
+    let feed = PushOracle::try_deserialize(&mut &data[..])
+        .map_err(|_| error!(ReaderError::DeserializeFail))?;
+    require!(feed.asset == asset, ReaderError::BadParam);
+    Ok(OracleSample::single(feed.price_e6, feed.conf_e6, feed.ts_ms))
+}
+
+/// Who may write the feed: the triplet account's update_authority, the PushOracle authority.
+/// Pyth-style accounts are written by their publishers and have none.
+fn oracle_update_authority(data: &[u8], kind: u8) -> Option<Pubkey> {
+    match kind {
+        ORACLE_KIND_TRIPLET if data.len() >= 8 + 32 => Pubkey::try_from(&data[8..40]).ok(),
+        ORACLE_KIND_PUSH => PushOracle::try_deserialize(&mut &data[..]).ok().map(|f| f.authority),
+        _ => None,
+    }
+}
+
+/// Parse `asset` from `oracle_ai` through the market's adapter. Only the pinned account is read
+/// (and, when set, only with its pinned update_authority); its owner must match the adapter.
+///
+/// An unpinned market reads nothing: every adapter-owned account would pass the owner check, and
+/// anyone can create a push feed. Push feeds must also be pinned with their authority.
+fn load_oracle(oracle_ai: &AccountInfo, amm: &Amm, asset: u8) -> Result<OracleSample> {
+    require!(amm.oracle_state != Pubkey::default(), ReaderError::WrongOracle);
+    require_keys_eq!(oracle_ai.key(), amm.oracle_state, ReaderError::WrongOracle);
+    let kind = amm.oracle_kind();
+    if kind == ORACLE_KIND_PUSH {
+        require!(amm.oracle_authority != Pubkey::default(), ReaderError::WrongOracleAuthority);
+    }
+    let owner = match kind {
+        ORACLE_KIND_TRIPLET => ORACLE_PROGRAM_ID,
+        ORACLE_KIND_PYTH => PYTH_PROGRAM_ID,
+        ORACLE_KIND_PUSH => crate::ID,
+        _ => return err!(ReaderError::BadParam),
+    };
+    require_keys_eq!(*oracle_ai.owner, owner, ReaderError::WrongOwner);
+
+    let data = oracle_ai.try_borrow_data()?;
+    if amm.oracle_authority != Pubkey::default() {
+        require!(
+            oracle_update_authority(&data, kind) == Some(amm.oracle_authority),
+            ReaderError::WrongOracleAuthority
+        );
+    }
+    match kind {
+        ORACLE_KIND_TRIPLET => parse_triplet_oracle(&data, asset),
+        ORACLE_KIND_PYTH => parse_pyth_price(&data),
+        _ => parse_push_oracle(&data, asset),
+    }
+}
+
+/// Trading lockout: trading stops `lockout_secs` before market_end_time, measured on the later of
+/// the cluster clock and the oracle's clock so a stalled feed can't hold trading open. Inside
+/// NEAR_EXPIRY_WINDOW_SECS of the end the oracle must also be recent, so nobody trades against a
+/// frozen price while the outcome is already known elsewhere.
+fn check_trading_lockout(amm: &Amm, oracle_ai: &AccountInfo) -> Result<()> {
+    if amm.market_end_time <= 0 {
+        return Ok(());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    let oracle_ts = load_oracle(oracle_ai, amm, amm.asset())?.ts_ms() / 1000;
+    let ts = now.max(oracle_ts);
+
+    let lockout_start_time = amm.market_end_time - amm.lockout_secs();
+    if ts >= lockout_start_time {
+        msg!("LOCKED: ts={} (clock={} oracle={}) lockout={} end={}",
+             ts, now, oracle_ts, lockout_start_time, amm.market_end_time);
+        return err!(ReaderError::TradingLocked);
+    }
+    if ts >= amm.market_end_time - NEAR_EXPIRY_WINDOW_SECS {
+        require!(now - oracle_ts <= NEAR_EXPIRY_MAX_ORACLE_AGE_SECS, ReaderError::OracleStaleNearExpiry);
+    }
+    Ok(())
+}
+
+/// Checked price for `asset` under the market's adapter and sanity limits.
+fn read_oracle(oracle_ai: &AccountInfo, amm: &Amm, asset: u8) -> Result<OracleReading> {
+    let sample = load_oracle(oracle_ai, amm, asset)?;
+    let now_ms = Clock::get()?.unix_timestamp.saturating_mul(1000);
+    check_oracle_sample(&sample, amm.oracle_checks(), now_ms)
+}
+
+
+// ============================== Limit Order helpers ==============================
+
+/// Bytes a user signs for `order`: a Solana off-chain message whose application domain is this
+/// program's id, signed by `order.user`, with the ASCII body `"limit_order v<version> " ||
+/// hex(borsh(order))` (lowercase hex), so wallets can display what they sign.
+///
+/// The fixed `\xffsolana offchain` prefix can never parse as a transaction, and the program id plus
+/// version keep a signature from being replayed against a redeploy or a changed order layout.
+fn limit_order_message(order: &LimitOrder) -> Result<Vec<u8>> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let order_bytes = order.try_to_vec().map_err(|_| ReaderError::BadParam)?;
+    let mut body = Vec::with_capacity(LIMIT_ORDER_TAG.len() + 6 + 2 * order_bytes.len());
+    body.extend_from_slice(LIMIT_ORDER_TAG);
+    body.extend_from_slice(format!(" v{} ", LIMIT_ORDER_VERSION).as_bytes());
+    for b in &order_bytes {
+        body.push(HEX[(b >> 4) as usize]);
+        body.push(HEX[(b & 0x0f) as usize]);
+    }
+
+    let mut msg = Vec::with_capacity(OFFCHAIN_SIGNING_DOMAIN.len() + 1 + 32 + 1 + 1 + 32 + 2 + body.len());
+    msg.extend_from_slice(OFFCHAIN_SIGNING_DOMAIN);
+    msg.push(OFFCHAIN_HEADER_VERSION);
+    msg.extend_from_slice(crate::ID.as_ref());          // application domain
+    msg.push(OFFCHAIN_FORMAT_ASCII);
+    msg.push(1);                                         // signer count
+    msg.extend_from_slice(order.user.as_ref());
+    msg.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&body);
+    Ok(msg)
+}
+
+/// Verify Ed25519 signature for limit order using Solana's native Ed25519 sysvar
+///
+/// The signature must be pre-verified by an Ed25519Program instruction earlier in the transaction,
+/// at `verify_ix_index`. This function checks that the verification covered exactly this order.
+fn verify_ed25519_signature(
+    order: &LimitOrder,
+    signature: &[u8; 64],
+    instructions_sysvar: &AccountInfo,
+    verify_ix_index: u16,
+) -> Result<()> {
+    // Signed envelope around the Borsh-encoded order (must match client-side encoding)
+    let message = limit_order_message(order)?;
+
+    // Ed25519 instruction data format:
+    // [num_signatures: u8][padding: u8][signature_offset: u16][signature_instruction_index: u16]
+    // [public_key_offset: u16][public_key_instruction_index: u16][message_data_offset: u16]
+    // [message_data_size: u16][message_instruction_index: u16][public_key: 32 bytes][signature: 64 bytes][message: variable]
+    let ed25519_ix = load_ed25519_ix(instructions_sysvar, verify_ix_index)?;
+    let entries = ed25519_entries(&ed25519_ix.data)?;
+
+    // A single-order fill expects exactly one signature
+    require!(entries.len() == 1, ReaderError::InvalidSignature);
+    let entry = &entries[0];
+    require!(entry.pubkey == order.user.to_bytes(), ReaderError::InvalidSignature);
+    require!(entry.signature == signature, ReaderError::InvalidSignature);
+    require!(entry.message == message.as_slice(), ReaderError::InvalidSignature);
+
+    msg!("✅ Ed25519 signature verified via sysvar");
+
+    Ok(())
+}
+
+/// Load the Ed25519Program instruction at `index`, which must come before the current one.
+fn load_ed25519_ix(instructions_sysvar: &AccountInfo, index: u16) -> Result<anchor_lang::solana_program::instruction::Instruction> {
+    let current_index = anchor_lang::solana_program::sysvar::instructions::load_current_index_checked(
+        instructions_sysvar
+    )?;
+    require!(index < current_index, ReaderError::InvalidSignature);
+
+    let ix = anchor_lang::solana_program::sysvar::instructions::load_instruction_at_checked(
+        index as usize,
+        instructions_sysvar
+    )?;
+    require_keys_eq!(ix.program_id, ED25519_PROGRAM_ID, ReaderError::InvalidSignature);
+    Ok(ix)
+}
+
+/// One signature checked by an Ed25519 verify instruction.
+struct Ed25519Entry<'a> {
+    pubkey: &'a [u8],
+    signature: &'a [u8],
+    message: &'a [u8],
+}
+
+/// Parse every signature entry of an Ed25519 verify instruction's data.
+///
+/// Each entry is a 14-byte offsets record after the 2-byte count; every offset must point into
+/// this same instruction's data (instruction index `u16::MAX`) and stay inside it.
+fn ed25519_entries(data: &[u8]) -> Result<Vec<Ed25519Entry<'_>>> {
+    let count = *data.first().ok_or_else(|| error!(ReaderError::InvalidSignature))? as usize;
+    require!(count > 0 && data.len() >= 2 + count * 14, ReaderError::InvalidSignature);
+    let slice = |offset: u16, len: usize| {
+        data.get(offset as usize..offset as usize + len)
+            .ok_or_else(|| error!(ReaderError::InvalidSignature))
+    };
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        // [sig_offset, sig_ix, pubkey_offset, pubkey_ix, msg_offset, msg_size, msg_ix]
+        let rec = data.get(2 + i * 14..2 + (i + 1) * 14).ok_or_else(|| error!(ReaderError::InvalidSignature))?;
+        let field = |k: usize| u16::from_le_bytes([rec[2 * k], rec[2 * k + 1]]);
+        require!(
+            field(1) == u16::MAX && field(3) == u16::MAX && field(6) == u16::MAX,
+            ReaderError::InvalidSignature
+        );
+        entries.push(Ed25519Entry {
+            signature: slice(field(0), 64)?,
+            pubkey: slice(field(2), 32)?,
+            message: slice(field(4), field(5) as usize)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// Verify a batch of orders against the Ed25519 instruction at `verify_ix_index`: it must hold
+/// exactly one entry per order, each matching that order's user, signature and message.
+fn verify_ed25519_batch(
+    orders: &[LimitOrder],
+    signatures: &[[u8; 64]],
+    instructions_sysvar: &AccountInfo,
+    verify_ix_index: u16,
+) -> Result<()> {
+    let ed25519_ix = load_ed25519_ix(instructions_sysvar, verify_ix_index)?;
+    let entries = ed25519_entries(&ed25519_ix.data)?;
+    require!(entries.len() == orders.len(), ReaderError::InvalidSignature);
+
+    let mut used = vec![false; entries.len()];
+    for (order, signature) in orders.iter().zip(signatures) {
+        let message = limit_order_message(order)?;
+        let pubkey = order.user.to_bytes();
+        let hit = entries.iter().enumerate().position(|(j, e)| {
+            !used[j] && e.pubkey == pubkey && e.signature == signature && e.message == message.as_slice()
+        });
+        match hit {
+            Some(j) => used[j] = true,
+            None => return err!(ReaderError::InvalidSignature),
+        }
+    }
+    Ok(())
+}
+
+/// Grow a program account to `new_len` in place (zero-filled), topping its rent up from `payer`.
+/// Returns false if it is already at least that large. Accounts whose layout grew are passed
+/// unchecked, so their owner and discriminator are checked here.
+fn grow_account<'info>(
+    target: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &Program<'info, System>,
+    discriminator: &[u8],
+    new_len: usize,
+) -> Result<bool> {
+    require_keys_eq!(*target.owner, crate::ID, ReaderError::BadParam);
+    require!(target.data_len() >= 8 && target.try_borrow_data()?[..8] == *discriminator, ReaderError::BadParam);
+    if target.data_len() >= new_len {
+        return Ok(false);
+    }
+    let rent = Rent::get()?.minimum_balance(new_len);
+    transfer_sol(sys, payer, target, rent.saturating_sub(target.lamports()))?;
+    target.resize(new_len)?;
+    Ok(true)
+}
+
+/// Create a program-owned PDA passed through `remaining_accounts` (what an `init` constraint does
+/// for declared accounts), including an address someone already sent lamports to.
+fn create_pda_account<'info>(
+    target: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let current = target.lamports();
+    if current == 0 {
+        let ix = system_instruction::create_account(payer.key, target.key, rent, space as u64, &crate::ID);
+        invoke_signed(&ix, &[payer.clone(), target.clone(), sys.to_account_info()], &[seeds])?;
+    } else {
+        transfer_sol(sys, payer, target, rent.saturating_sub(current))?;
+        let ix = system_instruction::allocate(target.key, space as u64);
+        invoke_signed(&ix, &[target.clone(), sys.to_account_info()], &[seeds])?;
+        let ix = system_instruction::assign(target.key, &crate::ID);
+        invoke_signed(&ix, &[target.clone(), sys.to_account_info()], &[seeds])?;
+    }
+    Ok(())
+}
+
+#[inline] fn is_exit_trigger(kind: u8) -> bool {
+    kind == TRIGGER_STOP_LOSS || kind == TRIGGER_TAKE_PROFIT
+}
+
+/// Conditional orders fill only once their trigger price is crossed: the underlying for oracle
+/// triggers (read from the market's pinned feed, so key, owner, pinned authority and freshness
+/// are checked), the order's own side price for stop-loss / take-profit exits (which must be
+/// SELLs). Markets without a pinned feed fill no conditional orders.
+fn check_order_trigger(order: &LimitOrder, amm: &Amm, oracle_ai: &AccountInfo) -> Result<()> {
+    if order.trigger_kind == TRIGGER_NONE {
+        return Ok(());
+    }
+    require!(amm.oracle_state != Pubkey::default(), ReaderError::WrongOracle);
+    require!(order.trigger_price_e6 > 0, ReaderError::BadTrigger);
+
+    let (observed, at_or_above, what) = match order.trigger_kind {
+        TRIGGER_ORACLE_ABOVE | TRIGGER_ORACLE_BELOW => {
+            let OracleReading { price_e6, .. } = read_oracle(oracle_ai, amm, amm.asset())?;
+            (price_e6, order.trigger_kind == TRIGGER_ORACLE_ABOVE, "underlying")
+        }
+        TRIGGER_STOP_LOSS | TRIGGER_TAKE_PROFIT => {
+            require!(order.action == 2, ReaderError::BadTrigger);
+            let price = calculate_avg_price_for_one_share(order.action, order.side, amm)?;
+            (price, order.trigger_kind == TRIGGER_TAKE_PROFIT, "share price")
+        }
+        _ => return err!(ReaderError::BadTrigger),
+    };
+
+    let hit = if at_or_above {
+        observed >= order.trigger_price_e6
+    } else {
+        observed <= order.trigger_price_e6
+    };
+    msg!("🎯 Trigger: {}={} {} {} → {}",
+         what, observed, if at_or_above { ">=" } else { "<=" },
+         order.trigger_price_e6, if hit { "hit" } else { "not yet" });
+    require!(hit, ReaderError::TriggerNotMet);
+    Ok(())
+}
+
+/// Checks a signed order against the position and market and sizes its next fill.
+///
+/// No side effects: a batch can drop an order that fails here. `fill` is the order's state, or a
+/// default one before its first fill.
+/// Order state at `ai`, or a fresh one if nothing has touched that id yet.
+fn read_order_fill(ai: &AccountInfo) -> Result<OrderFill> {
+    if ai.owner == &crate::ID {
+        OrderFill::try_deserialize(&mut &ai.try_borrow_data()?[..])
+    } else {
+        Ok(OrderFill::default())
+    }
+}
+
+/// `nonce_fill` is the order's own per-nonce state: `fill` itself, unless the order is in an
+/// OCO group (then only cancel_order_nonce writes it).
+fn plan_limit_fill(
+    amm: &Amm,
+    position: &Position,
+    fill: &OrderFill,
+    nonce_fill: &OrderFill,
+    order: &LimitOrder,
+    now: i64,
+) -> Result<i64> {
+    // Order not expired
+    require!(order.expiry_ts > now, ReaderError::OrderExpired);
+    msg!("⏱️  Order valid until {}", order.expiry_ts);
 
-const ORACLE_MAX_AGE_SECS: i64 = 90; // adjust to your feed cadence
+    // Nonce not cancelled (min_nonce) and not used up: its OrderFill state must not be cancelled
+    // or fully filled (legacy list for older fills)
+    require!(order.nonce >= position.min_nonce, ReaderError::NonceBelowMinimum);
+    require!(
+        !position.used_nonces.contains(&order.nonce),
+        ReaderError::NonceAlreadyUsed
+    );
+    require!(!fill.cancelled && !nonce_fill.cancelled, ReaderError::NonceAlreadyUsed);
+    if order.group_id != 0 && fill.is_open() {
+        // OCO: only the order that filled first may keep filling. Siblings must share its expiry,
+        // so once the group's state is closable none of them can fill any more.
+        require!(fill.filled_by == 0 || fill.filled_by == order.nonce, ReaderError::OcoSiblingFilled);
+        require!(fill.expiry_ts == order.expiry_ts, ReaderError::OcoGroupMismatch);
+    }
+    let mut remaining_shares = order.shares_e6.saturating_sub(fill.filled_shares_e6);
+    require!(remaining_shares > 0, ReaderError::OrderFullyFilled);
+    if is_exit_trigger(order.trigger_kind) {
+        // Stop-loss / take-profit only close what the position holds on that side
+        let held = if order.side == 1 { position.yes_shares_e6 } else { position.no_shares_e6 };
+        remaining_shares = remaining_shares.min(held);
+        require!(remaining_shares > 0, ReaderError::NothingToReduce);
+    }
+    if order.reduce_only {
+        // Reduce-only: never grow net exposure
+        remaining_shares = remaining_shares.min(reducible_shares(order.action, order.side, position));
+        require!(remaining_shares > 0, ReaderError::NothingToReduce);
+    }
+    if order.is_sliced() {
+        // TWAP: a partly filled slice keeps the rest of its size; a new slice starts at least
+        // slice_interval_secs after the fill that closed the previous one
+        require!(order.slice_interval_secs >= 0, ReaderError::BadParam);
+        if fill.slice_filled_e6 == 0 && fill.slices_done > 0 {
+            let next_ts = fill.last_fill_ts.saturating_add(order.slice_interval_secs);
+            require!(now >= next_ts, ReaderError::TwapSliceNotDue);
+        }
+        let slice_left = order.slice_size_e6().saturating_sub(fill.slice_filled_e6);
+        remaining_shares = remaining_shares.min(slice_left);
+        msg!("⏳ TWAP slice {} of {} ({} shares)", fill.slices_done + 1, order.slice_count, remaining_shares);
+    }
+    if fill.fills > 0 {
+        msg!("🧩 Resuming order: {} of {} shares filled over {} fill(s)",
+             fill.filled_shares_e6, order.shares_e6, fill.fills);
+    }
 
-fn median3(a: i64, b: i64, c: i64) -> i64 {
-    let mut v = [a,b,c];
-    v.sort();
-    v[1]
-}
+    // Calculate current price for this action/side
+    let current_price = calculate_avg_price_for_one_share(order.action, order.side, amm)?;
 
-// Bytes layout we actually have (same as your JS):
-//  [8]  anchor discriminator (skip)
-//  [32] update_authority pubkey
-//  [48] btc:  param1, param2, param3 (i64) | ts1, ts2, ts3 (i64)
-//  [48] eth:  (skip)
-//  [48] sol:  (skip)
-//  [1]  decimals (u8)
-//  [1]  bump     (u8)
+    msg!("💰 Current price: {} | Limit: {}", current_price, order.limit_price_e6);
 
-fn read_i64_le(slice: &[u8]) -> i64 {
-    let mut arr = [0u8; 8];
-    arr.copy_from_slice(slice);
-    i64::from_le_bytes(arr)
-}
+    // Verify price condition is favorable
+    let price_ok = match order.action {
+        1 => { // BUY: current price must be <= limit price
+            current_price <= order.limit_price_e6
+        }
+        2 => { // SELL: current price must be >= limit price
+            current_price >= order.limit_price_e6
+        }
+        _ => return err!(ReaderError::InvalidAction),
+    };
 
-fn median3_i64(a: i64, b: i64, c: i64) -> i64 {
-    let mut v = [a, b, c];
-    v.sort();
-    v[1]
-}
+    require!(price_ok, ReaderError::PriceConditionNotMet);
+    msg!("✅ Price condition satisfied");
+
+    // Build guards for partial fill logic (reuse existing code). Each fill is bounded by what is
+    // left of the order: remaining shares, remaining cost budget, and min_fill_bps of the
+    // order size (or the whole remainder, if that is smaller). For TWAP orders min_fill_bps
+    // applies to one slice.
+    let min_fill_e6 = (order.slice_size_e6() as i128 * order.min_fill_bps as i128 / 10000) as i64;
+    let cost_budget_e6 = if order.action == 1 && order.max_cost_e6 > 0 {
+        let left = order.max_cost_e6.saturating_sub(fill.cost_e6);
+        require!(left > 0, ReaderError::OrderFullyFilled);
+        left
+    } else {
+        order.max_cost_e6
+    };
+    let guards = AdvancedGuardConfig {
+        price_limit_e6: order.limit_price_e6,
+        max_slippage_bps: 0,
+        quote_price_e6: current_price,
+        quote_timestamp: now,
+        max_total_cost_e6: cost_budget_e6,
+        allow_partial: true,
+        min_fill_shares_e6: min_fill_e6.min(remaining_shares),
+        reduce_only: false, // already applied to remaining_shares
+    };
 
-/// Manual parser (no Borsh). Returns (price_e6, ts_used).
-fn read_btc_price_e6(oracle_ai: &AccountInfo) -> Result<(i64, i64)> {
-    require_keys_eq!(*oracle_ai.owner, ORACLE_PROGRAM_ID, ReaderError::WrongOwner);
+    // Find max executable shares (uses Newton-Raphson or binary search)
+    let executable_shares = find_max_executable_shares(
+        order.action,
+        order.side,
+        remaining_shares,
+        &guards,
+        amm,
+    )?;
 
-    let data = oracle_ai.try_borrow_data()?;
-    require!(data.len() >= 8 + 32 + 48*3 + 2, ReaderError::DataTooSmall);
+    msg!("📊 Executing {} of {} remaining shares", executable_shares, remaining_shares);
 
-    let d = &data[8..]; // skip discriminator
-    let mut o: usize = 0;
+    // Reject orders that would execute 0 shares
+    require!(executable_shares > 0, ReaderError::MinFillNotMet);
+    Ok(executable_shares)
+}
 
-    // update_authority
-    o += 32;
+/// Lamport accounts a keeper fill moves funds between (the user_vault is per order).
+#[derive(Clone, Copy)]
+struct LimitFillAccounts<'a, 'info> {
+    vault_sol: &'a AccountInfo<'info>,
+    fee_dest: &'a AccountInfo<'info>,
+    insurance: &'a AccountInfo<'info>,
+    insurance_bump: u8,
+    keeper: &'a AccountInfo<'info>,
+    sys: &'a Program<'info, System>,
+}
 
-    // btc triplet: param1..3, ts1..3
-    let p1 = read_i64_le(&d[o..o+8]); o += 8;
-    let p2 = read_i64_le(&d[o..o+8]); o += 8;
-    let p3 = read_i64_le(&d[o..o+8]); o += 8;
-    let t1 = read_i64_le(&d[o..o+8]); o += 8;
-    let t2 = read_i64_le(&d[o..o+8]); o += 8;
-    let t3 = read_i64_le(&d[o..o+8]); o += 8;
+/// Trades `executable_shares` of a planned order for the position, moves the funds, pays the
+/// keeper and records the fill on the order's state.
+fn apply_limit_fill<'info>(
+    amm: &mut Account<'info, Amm>,
+    position: &mut Account<'info, Position>,
+    user_vault: &AccountInfo<'info>,
+    fill: &mut OrderFill,
+    accts: &LimitFillAccounts<'_, 'info>,
+    order: &LimitOrder,
+    executable_shares: i64,
+) -> Result<()> {
+    // === TRADE EXECUTION PHASE ===
+
+    let (net_e6, dq_e6, avg_price_e6) = match (order.action, order.side) {
+        (1, 1) => {
+            // BUY YES: Calculate spend needed for executable shares
+            let spend_e6 = lmsr_buy_yes_for_shares(amm, executable_shares)?;
+            let (dq, avg_h) = lmsr_buy_yes(amm, spend_e6)?;
+            let avg_price = (spend_e6 as f64 / dq) as i64;
+
+            // Update position
+            position.yes_shares_e6 = position.yes_shares_e6.saturating_add(dq.round() as i64);
+
+            msg!("✅ BUY YES executed: {} shares @ ${:.6}", dq.round() as i64, avg_h);
+            (spend_e6, dq.round() as i64, avg_price)
+        },
+        (1, 2) => {
+            // BUY NO: Calculate spend needed for executable shares
+            let spend_e6 = lmsr_buy_no_for_shares(amm, executable_shares)?;
+            let (dq, avg_h) = lmsr_buy_no(amm, spend_e6)?;
+            let avg_price = (spend_e6 as f64 / dq) as i64;
+
+            // Update position
+            position.no_shares_e6 = position.no_shares_e6.saturating_add(dq.round() as i64);
+
+            msg!("✅ BUY NO executed: {} shares @ ${:.6}", dq.round() as i64, avg_h);
+            (spend_e6, dq.round() as i64, avg_price)
+        },
+        (2, 1) => {
+            // SELL YES: Check user has enough shares
+            require!(position.yes_shares_e6 >= executable_shares, ReaderError::InsufficientShares);
+
+            let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_yes(amm, executable_shares)?;
+            let avg_price = (proceeds_e6 as f64 / sold_e6) as i64;
+
+            // Update position
+            position.yes_shares_e6 = position.yes_shares_e6.saturating_sub(sold_e6.round() as i64);
+
+            msg!("✅ SELL YES executed: {} shares @ ${:.6}", sold_e6.round() as i64, avg_h);
+            (proceeds_e6, sold_e6.round() as i64, avg_price)
+        },
+        (2, 2) => {
+            // SELL NO: Check user has enough shares
+            require!(position.no_shares_e6 >= executable_shares, ReaderError::InsufficientShares);
+
+            let (proceeds_e6, avg_h, sold_e6) = lmsr_sell_no(amm, executable_shares)?;
+            let avg_price = (proceeds_e6 as f64 / sold_e6) as i64;
+
+            // Update position
+            position.no_shares_e6 = position.no_shares_e6.saturating_sub(sold_e6.round() as i64);
+
+            msg!("✅ SELL NO executed: {} shares @ ${:.6}", sold_e6.round() as i64, avg_h);
+            (proceeds_e6, sold_e6.round() as i64, avg_price)
+        },
+        _ => return err!(ReaderError::InvalidAction),
+    };
 
-    // skip eth + sol (48 + 48)
-    o += 96;
+    msg!("📊 Trade completed: net={} dq={} avg_price={:.6}", net_e6, dq_e6, avg_price_e6 as f64 / 1e6);
 
-    // decimals (u8) + bump (u8)
-    let decimals = d[o] as u32; o += 1;
-    /* bump */ o += 1;
+    // === FUND TRANSFER PHASE ===
 
-    // robust median
-    let p_raw = median3_i64(p1, p2, p3);
-    let ts    = median3_i64(t1, t2, t3);
+    record_volume(amm, net_e6);
 
-    // Convert p_raw (10^decimals) -> 1e6 fixed
-    // Use i128 to avoid overflow, then clamp to i64.
-    let pow = 10i128.pow(decimals);
-    let num = (p_raw as i128) * 1_000_000i128;
-    let price_e6_i128 = if pow > 0 { num / pow } else { num }; // decimals should be >0, but guard anyway
-    let price_e6 = price_e6_i128.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    // Calculate protocol fee (deducted from gross amount)
+    let protocol_fee_e6 = (net_e6.abs() as i128 * amm.fee_bps as i128 / 10_000) as i64;
+    let net_after_protocol_fee_e6 = if order.action == 1 {
+        net_e6 + protocol_fee_e6  // BUY: add fee to total cost
+    } else {
+        net_e6 - protocol_fee_e6  // SELL: subtract fee from proceeds
+    };
 
-    // (Optional) debug line you can keep until confident:
-    // msg!("DBG oracle: p_raw={} dec={} -> price=${:.6}", p_raw, decimals, (price_e6 as f64)/1e6);
+    let net_lamports = (net_after_protocol_fee_e6.abs() as u64) * LAMPORTS_PER_E6;
+    let protocol_fee_lamports = (protocol_fee_e6 as u64) * LAMPORTS_PER_E6;
 
-    Ok((price_e6, ts))
-}
+    if order.action == 1 {
+        // === BUY ORDER: Transfer from user_vault to vault_sol ===
 
+        // Check user has sufficient balance in vault
+        require!(
+            position.vault_balance_e6 >= net_after_protocol_fee_e6,
+            ReaderError::InsufficientBalance
+        );
 
-fn assert_fresh(ts: i64) -> Result<()> {
-    let now = Clock::get()?.unix_timestamp;
-    require!(now - ts <= ORACLE_MAX_AGE_SECS, ReaderError::StaleOracle);
-    Ok(())
-}
+        // Transfer from user_vault PDA to vault_sol PDA (signed transfer)
+        let pos_key = position.key();
+        let seeds: &[&[u8]] = &[
+            Position::USER_VAULT_SEED,
+            pos_key.as_ref(),
+            &[position.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
 
-// ============================== Limit Order helpers ==============================
+        transfer_sol_signed(
+            accts.sys,
+            user_vault,
+            accts.vault_sol,
+            net_lamports,
+            signer_seeds,
+        )?;
 
-/// Verify Ed25519 signature for limit order using Solana's native Ed25519 sysvar
-///
-/// The signature must be pre-verified using the Ed25519Program instruction before calling this.
-/// This function checks that the verification was done correctly.
-fn verify_ed25519_signature(order: &LimitOrder, signature: &[u8; 64], instructions_sysvar: &AccountInfo) -> Result<()> {
-    // Serialize order to bytes using Borsh (must match client-side encoding)
-    let message = order.try_to_vec().map_err(|_| ReaderError::BadParam)?;
+        // Update accounting
+        position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(net_after_protocol_fee_e6);
+        amm.vault_e6 = amm.vault_e6.saturating_add(net_after_protocol_fee_e6);
+
+        // Transfer protocol fee to fee_dest (insurance slice to the insurance fund)
+        if protocol_fee_lamports > 0 {
+            pay_fee(
+                amm,
+                accts.sys,
+                user_vault,
+                accts.fee_dest,
+                accts.insurance,
+                protocol_fee_e6,
+                signer_seeds,
+            )?;
 
-    // Get user's public key bytes
-    let pubkey_bytes = order.user.to_bytes();
+            position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(protocol_fee_e6);
+        }
 
-    // Load and verify the Ed25519 instruction from sysvar
-    let instruction_sysvar_account_info = instructions_sysvar;
-    let current_index = anchor_lang::solana_program::sysvar::instructions::load_current_index_checked(
-        instruction_sysvar_account_info
-    )?;
+        msg!("💰 BUY: Transferred {} lamports from user_vault to vault_sol", net_lamports);
+        msg!("💰 Protocol fee: {} lamports to fee_dest", protocol_fee_lamports);
+    } else {
+        // === SELL ORDER: Transfer from vault_sol to user_vault ===
 
-    // The Ed25519 verify instruction should be at current_index - 1
-    let ed25519_ix_index = (current_index as usize).saturating_sub(1);
+        // Check vault has sufficient coverage (proceeds + protocol fee), drawing on insurance if short
+        ensure_sell_coverage(
+            amm,
+            accts.insurance,
+            accts.vault_sol,
+            accts.sys,
+            net_after_protocol_fee_e6 + protocol_fee_e6,
+            accts.insurance_bump,
+        )?;
 
-    let ed25519_ix = anchor_lang::solana_program::sysvar::instructions::load_instruction_at_checked(
-        ed25519_ix_index,
-        instruction_sysvar_account_info
-    )?;
+        // Transfer from vault_sol PDA to user_vault PDA (signed transfer)
+        let amm_key = amm.key();
+        let seeds: &[&[u8]] = &[
+            Amm::VAULT_SOL_SEED,
+            amm_key.as_ref(),
+            &[amm.vault_sol_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
 
-    // Verify this is an Ed25519 instruction
-    // Ed25519 program ID: Ed25519SigVerify111111111111111111111111111
-    const ED25519_PROGRAM_ID: Pubkey = pubkey!("Ed25519SigVerify111111111111111111111111111");
-    require_keys_eq!(
-        ed25519_ix.program_id,
-        ED25519_PROGRAM_ID,
-        ReaderError::InvalidSignature
-    );
+        transfer_sol_signed(
+            accts.sys,
+            accts.vault_sol,
+            user_vault,
+            net_lamports,
+            signer_seeds,
+        )?;
 
-    // Ed25519 instruction data format:
-    // [num_signatures: u8][padding: u8][signature_offset: u16][signature_instruction_index: u16]
-    // [public_key_offset: u16][public_key_instruction_index: u16][message_data_offset: u16]
-    // [message_data_size: u16][message_instruction_index: u16][public_key: 32 bytes][signature: 64 bytes][message: variable]
+        // Update accounting
+        position.vault_balance_e6 = position.vault_balance_e6.saturating_add(net_after_protocol_fee_e6);
+        amm.vault_e6 = amm.vault_e6.saturating_sub(net_after_protocol_fee_e6);
+
+        // Transfer protocol fee to fee_dest (insurance slice to the insurance fund)
+        if protocol_fee_lamports > 0 {
+            pay_fee(
+                amm,
+                accts.sys,
+                accts.vault_sol,
+                accts.fee_dest,
+                accts.insurance,
+                protocol_fee_e6,
+                signer_seeds,
+            )?;
 
-    // For a single signature verification, we expect:
-    // - num_signatures = 1
-    // - The public key, signature, and message embedded in the instruction data
+            amm.vault_e6 = amm.vault_e6.saturating_sub(protocol_fee_e6);
+        }
 
-    require!(
-        ed25519_ix.data.len() >= 114, // minimum: 14 header + 32 pubkey + 64 sig + 4 for offsets
-        ReaderError::InvalidSignature
-    );
+        msg!("💰 SELL: Transferred {} lamports from vault_sol to user_vault", net_lamports);
+        msg!("💰 Protocol fee: {} lamports to fee_dest", protocol_fee_lamports);
+    }
 
-    require!(
-        ed25519_ix.data[0] == 1, // num_signatures must be 1
-        ReaderError::InvalidSignature
-    );
+    // === FEE PAYMENT PHASE ===
 
-    // Read offsets from the instruction header
-    let pubkey_offset = u16::from_le_bytes([ed25519_ix.data[6], ed25519_ix.data[7]]) as usize;
-    let sig_offset = u16::from_le_bytes([ed25519_ix.data[2], ed25519_ix.data[3]]) as usize;
-    let msg_offset = u16::from_le_bytes([ed25519_ix.data[10], ed25519_ix.data[11]]) as usize;
-    let msg_size = u16::from_le_bytes([ed25519_ix.data[12], ed25519_ix.data[13]]) as usize;
+    // Calculate keeper fee from the net amount
+    let keeper_fee_e6 = (net_e6.abs() as i128 * order.keeper_fee_bps as i128 / 10_000) as i64;
+    let keeper_fee_lamports = (keeper_fee_e6 as u64) * LAMPORTS_PER_E6;
 
-    // Extract the public key from the instruction (using offset)
-    let ix_pubkey = &ed25519_ix.data[pubkey_offset..pubkey_offset + 32];
-    require!(
-        ix_pubkey == pubkey_bytes,
-        ReaderError::InvalidSignature
-    );
+    // Transfer keeper fee from user_vault to keeper (using PDA signing)
+    if keeper_fee_lamports > 0 {
+        // Use user_vault PDA (which we control) instead of user's wallet
+        let pos_key = position.key();
+        let seeds: &[&[u8]] = &[
+            Position::USER_VAULT_SEED,
+            pos_key.as_ref(),
+            &[position.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
 
-    // Extract the signature from the instruction (using offset)
-    let ix_signature = &ed25519_ix.data[sig_offset..sig_offset + 64];
-    require!(
-        ix_signature == signature,
-        ReaderError::InvalidSignature
-    );
+        transfer_sol_signed(
+            accts.sys,
+            user_vault,
+            accts.keeper,
+            keeper_fee_lamports,
+            signer_seeds,
+        )?;
 
-    // Extract and verify the message from the instruction (using offset)
-    let ix_message = &ed25519_ix.data[msg_offset..msg_offset + msg_size];
-    require!(
-        ix_message == message.as_slice(),
-        ReaderError::InvalidSignature
-    );
+        // Update vault balance
+        position.vault_balance_e6 = position.vault_balance_e6.saturating_sub(keeper_fee_e6);
 
-    msg!("✅ Ed25519 signature verified via sysvar");
+        msg!("💸 Keeper fee paid: {} lamports to {}", keeper_fee_lamports, accts.keeper.key);
+    }
 
+    // === CLEANUP PHASE ===
+
+    // Record the fill; the nonce stays usable until the order is filled or cancelled
+    fill.record(order, dq_e6, net_e6.abs(), Clock::get()?.unix_timestamp);
+
+    emit!(LimitOrderExecuted {
+        user: order.user,
+        keeper: *accts.keeper.key,
+        action: order.action,
+        side: order.side,
+        shares_requested: order.shares_e6,
+        shares_executed: executable_shares,
+        limit_price: order.limit_price_e6,
+        execution_price: avg_price_e6,
+        keeper_fee_bps: order.keeper_fee_bps,
+        nonce: order.nonce,
+        total_filled: fill.filled_shares_e6,
+        group_id: order.group_id,
+        fills: fill.fills,
+    });
     Ok(())
 }
 
@@ -3300,6 +5680,103 @@ fn execute_trade_internal(
 
 // ============================== SOL helpers ==============================
 
+/// Route a fee: `insurance_bps` of it to the insurance fund, the rest to fee_dest.
+/// The slice stays with fee_dest while it couldn't make the fund rent-exempt.
+fn pay_fee<'info>(
+    amm: &Account<'info, Amm>,
+    sys: &Program<'info, System>,
+    from: &AccountInfo<'info>,
+    fee_dest: &AccountInfo<'info>,
+    insurance: &AccountInfo<'info>,
+    fee_e6: i64,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let fee_lamports = e6_to_lamports(fee_e6);
+    let mut cut_lamports = e6_to_lamports(
+        ((fee_e6.max(0) as i128) * (amm.insurance_bps as i128) / 10_000) as i64,
+    );
+    if insurance.lamports().saturating_add(cut_lamports) < Rent::get()?.minimum_balance(0) {
+        cut_lamports = 0;
+    }
+
+    transfer_sol_signed(sys, from, fee_dest, fee_lamports.saturating_sub(cut_lamports), seeds)?;
+    if cut_lamports > 0 {
+        transfer_sol_signed(sys, from, insurance, cut_lamports, seeds)?;
+        emit!(InsuranceTopUp {
+            round_id: amm.round_id,
+            amount_e6: lamports_to_e6(cut_lamports),
+            from_fees: true,
+            balance_lamports: insurance.lamports(),
+        });
+    }
+    Ok(())
+}
+
+/// Move up to `need_e6` from the insurance fund into vault_sol (the fund stays rent-exempt).
+/// SELL and SETTLE draws add real coverage, so they also credit vault_e6; REDEEM draws only
+/// restore lamports the mirror already counts. Returns the amount drawn (1e6).
+fn draw_insurance<'info>(
+    amm: &mut Account<'info, Amm>,
+    insurance: &AccountInfo<'info>,
+    vault_sol: &AccountInfo<'info>,
+    sys: &Program<'info, System>,
+    need_e6: i64,
+    insurance_bump: u8,
+    reason: u8,
+) -> Result<i64> {
+    if need_e6 <= 0 {
+        return Ok(0);
+    }
+    let available = insurance.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    let draw_lamports = e6_to_lamports(need_e6).min(available);
+    if draw_lamports == 0 {
+        return Ok(0);
+    }
+
+    let amm_key = amm.key();
+    let seeds: &[&[u8]] = &[
+        Amm::INSURANCE_SEED,
+        amm_key.as_ref(),
+        core::slice::from_ref(&insurance_bump),
+    ];
+    transfer_sol_signed(sys, insurance, vault_sol, draw_lamports, &[seeds])?;
+
+    let drawn_e6 = lamports_to_e6(draw_lamports);
+    if reason != INSURANCE_DRAW_REDEEM {
+        amm.vault_e6 = amm.vault_e6.saturating_add(drawn_e6);
+    }
+    emit!(InsuranceDraw {
+        round_id: amm.round_id,
+        reason,
+        needed_e6: need_e6,
+        drawn_e6,
+        balance_lamports: insurance.lamports(),
+    });
+    msg!("🛟 INSURANCE draw reason={} need={} drawn={}", reason, need_e6, drawn_e6);
+    Ok(drawn_e6)
+}
+
+/// Require vault_sol to cover `proceeds_e6` out of lamports owed to no one yet: carried winnings
+/// (reserved_e6) and outstanding IOUs (iou_e6) stay put. A shortfall is drawn from insurance first.
+fn ensure_sell_coverage<'info>(
+    amm: &mut Account<'info, Amm>,
+    insurance: &AccountInfo<'info>,
+    vault_sol: &AccountInfo<'info>,
+    sys: &Program<'info, System>,
+    proceeds_e6: i64,
+    insurance_bump: u8,
+) -> Result<()> {
+    let available_e6 = lamports_to_e6(vault_sol.lamports())
+        .saturating_sub(amm.reserved_e6.max(0))
+        .saturating_sub(amm.iou_e6.max(0));
+    msg!("   Coverage check: available={:.6} XNT (reserved={:.6}, iou={:.6}), needed={:.6} XNT",
+         usd(available_e6), usd(amm.reserved_e6), usd(amm.iou_e6), usd(proceeds_e6));
+
+    let drawn_e6 = draw_insurance(amm, insurance, vault_sol, sys, proceeds_e6 - available_e6, insurance_bump, INSURANCE_DRAW_SELL)?;
+    require!(available_e6 + drawn_e6 >= proceeds_e6, ReaderError::NoCoverage);
+    Ok(())
+}
+
 /// Keep at least 1 SOL in the vault at all times.
 const MIN_VAULT_LAMPORTS: u64 = 1_000_000_000; // 1 SOL
 
@@ -3397,5 +5874,566 @@ pub enum ReaderError {
     WrongUser,
     #[msg("invalid action (must be 1=BUY or 2=SELL)")]
     InvalidAction,
+
+    // Settlement timeout
+    #[msg("settlement grace period has not elapsed")]
+    SettleGraceActive,
+    #[msg("market was voided")]
+    MarketVoided,
+
+    // Round history
+    #[msg("market already settled")]
+    AlreadySettled,
+
+    // Series
+    #[msg("nothing to roll yet: round still running or results window open")]
+    RoundNotReady,
+
+    // Unclaimed winnings
+    #[msg("position holds unredeemed shares from an earlier round; redeem them first")]
+    UnclaimedCarry,
+    #[msg("unclaimed winnings outstanding; use reopen_amm instead of close_amm")]
+    LiabilitiesOutstanding,
+
+    // Claim deadline
+    #[msg("claim window has not closed yet")]
+    ClaimWindowOpen,
+    #[msg("round's unclaimed winnings were swept to the treasury; use claim_late")]
+    ClaimSwept,
+    #[msg("round has not been swept; use redeem")]
+    NotSwept,
+    #[msg("nothing to sweep")]
+    NothingToSweep,
+
+    // Shortfall IOUs
+    #[msg("position has no IOU to claim")]
+    NoIou,
+
+    // Redemption crank
+    #[msg("crank_redeem expects 1..=16 (position, user_vault) account pairs")]
+    BadCrankAccounts,
+
+    // Oracle sanity checks
+    #[msg("oracle sources disagree by more than the allowed spread")]
+    OracleSpreadTooWide,
+    #[msg("fewer than 2 of 3 oracle sources are fresh")]
+    OracleQuorum,
+
+    // Pinned oracle
+    #[msg("oracle_state is not the market's pinned feed")]
+    WrongOracle,
+    #[msg("oracle update_authority does not match the pinned authority")]
+    WrongOracleAuthority,
+
+    // Lockout clock
+    #[msg("oracle too stale to trade this close to market end")]
+    OracleStaleNearExpiry,
+
+    // Order replay guards
+    #[msg("order has not expired yet")]
+    OrderNotExpired,
+    #[msg("order nonce is below the position's cancel-all watermark")]
+    NonceBelowMinimum,
+
+    // Multi-fill orders
+    #[msg("order is already fully filled")]
+    OrderFullyFilled,
+
+    // Batch keeper fills
+    #[msg("bad batch: order/signature counts or (position, user_vault, order_fill) accounts")]
+    BadLimitBatch,
+
+    // Conditional orders
+    #[msg("unknown order trigger kind or bad trigger price")]
+    BadTrigger,
+    #[msg("order trigger condition not met")]
+    TriggerNotMet,
+
+    // Stop-loss / take-profit, OCO groups and reduce-only
+    #[msg("nothing left to reduce: the trade would grow net exposure")]
+    NothingToReduce,
+    #[msg("another order in this OCO group already filled")]
+    OcoSiblingFilled,
+    #[msg("OCO group orders must share one expiry")]
+    OcoGroupMismatch,
+
+    // TWAP orders
+    #[msg("next TWAP slice is not due yet")]
+    TwapSliceNotDue,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- oracle adapter fixtures ----------
+
+    /// Triplet account bytes: discriminator, update_authority, btc/eth/sol (p1..p3, t1..t3), decimals, bump.
+    fn triplet_bytes(authority: Pubkey, triplets: [[i64; 6]; 3], decimals: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(authority.as_ref());
+        for t in triplets {
+            for v in t {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        data.push(decimals);
+        data.push(255);
+        data
+    }
+
+    /// Pyth v2 price account bytes with only the fields parse_pyth_price reads filled in.
+    fn pyth_bytes(expo: i32, price: i64, conf: u64, ts_secs: i64, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; PYTH_MIN_LEN];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[PYTH_OFF_ATYPE..PYTH_OFF_ATYPE+4].copy_from_slice(&PYTH_ATYPE_PRICE.to_le_bytes());
+        data[PYTH_OFF_EXPO..PYTH_OFF_EXPO+4].copy_from_slice(&expo.to_le_bytes());
+        data[PYTH_OFF_TIMESTAMP..PYTH_OFF_TIMESTAMP+8].copy_from_slice(&ts_secs.to_le_bytes());
+        data[PYTH_OFF_AGG_PRICE..PYTH_OFF_AGG_PRICE+8].copy_from_slice(&price.to_le_bytes());
+        data[PYTH_OFF_AGG_CONF..PYTH_OFF_AGG_CONF+8].copy_from_slice(&conf.to_le_bytes());
+        data[PYTH_OFF_AGG_STATUS..PYTH_OFF_AGG_STATUS+4].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    fn push_bytes(authority: Pubkey, asset: u8, price_e6: i64, conf_e6: i64, ts_ms: i64) -> Vec<u8> {
+        let feed = PushOracle { authority, asset, price_e6, conf_e6, ts_ms, bump: 254 };
+        let mut data = Vec::new();
+        feed.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn triplet_reads_the_asset_slot() {
+        let authority = Pubkey::new_unique();
+        let data = triplet_bytes(
+            authority,
+            [
+                [1, 2, 3, 4, 5, 6],
+                [350_000_000_000, 350_100_000_000, 349_900_000_000, 1_000, 2_000, 3_000],
+                [7, 8, 9, 10, 11, 12],
+            ],
+            8,
+        );
+        let s = parse_triplet_oracle(&data, ASSET_ETH).unwrap();
+        assert_eq!(s.n, 3);
+        assert_eq!(s.sources, [(3_500_000_000, 1_000), (3_501_000_000, 2_000), (3_499_000_000, 3_000)]);
+        assert_eq!(oracle_update_authority(&data, ORACLE_KIND_TRIPLET), Some(authority));
+    }
+
+    #[test]
+    fn triplet_rejects_short_account_and_unknown_asset() {
+        let data = triplet_bytes(Pubkey::new_unique(), [[0; 6]; 3], 6);
+        assert_eq!(parse_triplet_oracle(&data[..data.len() - 1], ASSET_BTC).unwrap_err(), ReaderError::DataTooSmall.into());
+        assert_eq!(parse_triplet_oracle(&data, 4).unwrap_err(), ReaderError::BadParam.into());
+    }
+
+    #[test]
+    fn pyth_scales_the_trading_aggregate() {
+        let data = pyth_bytes(-8, 6_500_012_345_678, 2_500_000_000, 1_700_000_000, PYTH_STATUS_TRADING);
+        let s = parse_pyth_price(&data).unwrap();
+        assert_eq!(s.n, 1);
+        assert_eq!(s.sources[0], (65_000_123_456, 1_700_000_000_000));
+        assert_eq!(s.conf_e6, 25_000_000);
+        assert_eq!(oracle_update_authority(&data, ORACLE_KIND_PYTH), None);
+    }
+
+    #[test]
+    fn pyth_rejects_bad_accounts() {
+        let good = pyth_bytes(-8, 1, 0, 0, PYTH_STATUS_TRADING);
+        assert_eq!(parse_pyth_price(&good[..PYTH_MIN_LEN - 1]).unwrap_err(), ReaderError::DataTooSmall.into());
+
+        let mut bad_magic = good.clone();
+        bad_magic[0] ^= 1;
+        assert_eq!(parse_pyth_price(&bad_magic).unwrap_err(), ReaderError::DeserializeFail.into());
+
+        let mut not_price = good.clone();
+        not_price[PYTH_OFF_ATYPE] = 2;
+        assert_eq!(parse_pyth_price(&not_price).unwrap_err(), ReaderError::DeserializeFail.into());
+
+        let halted = pyth_bytes(-8, 1, 0, 0, 2);
+        assert_eq!(parse_pyth_price(&halted).unwrap_err(), ReaderError::StaleOracle.into());
+    }
+
+    #[test]
+    fn push_feed_checks_discriminator_and_asset() {
+        let authority = Pubkey::new_unique();
+        let data = push_bytes(authority, ASSET_SOL, 150_250_000, 50_000, 1_700_000_000_123);
+        let s = parse_push_oracle(&data, ASSET_SOL).unwrap();
+        assert_eq!(s.sources[0], (150_250_000, 1_700_000_000_123));
+        assert_eq!(s.conf_e6, 50_000);
+        assert_eq!(oracle_update_authority(&data, ORACLE_KIND_PUSH), Some(authority));
+
+        assert_eq!(parse_push_oracle(&data, ASSET_BTC).unwrap_err(), ReaderError::BadParam.into());
+        let mut foreign = data.clone();
+        foreign[0] ^= 1;
+        assert_eq!(parse_push_oracle(&foreign, ASSET_SOL).unwrap_err(), ReaderError::DeserializeFail.into());
+        assert_eq!(oracle_update_authority(&foreign, ORACLE_KIND_PUSH), None);
+    }
+
+    #[test]
+    fn scale_to_e6_shifts_and_clamps() {
+        assert_eq!(scale_to_e6(123_456_789, -6), 123_456_789);
+        assert_eq!(scale_to_e6(12_345_678_901, -8), 123_456_789);
+        assert_eq!(scale_to_e6(-12_345_678_901, -8), -123_456_789);
+        assert_eq!(scale_to_e6(42, 0), 42_000_000);
+        assert_eq!(scale_to_e6(42, 2), 4_200_000_000);
+        assert_eq!(scale_to_e6(i64::MAX as i128, 3), i64::MAX);
+        assert_eq!(scale_to_e6(i64::MIN as i128, 3), i64::MIN);
+        assert_eq!(scale_to_e6(i64::MAX as i128, -40), 0);
+    }
+
+    // ---------- mock_oracle ----------
+
+    #[cfg(feature = "mock-oracle")]
+    #[test]
+    fn mock_oracle_owns_triplet_feeds() {
+        assert_eq!(ORACLE_PROGRAM_ID, mock_oracle::ID);
+    }
+
+    #[test]
+    fn mock_oracle_account_loads_through_the_triplet_adapter() {
+        let authority = Pubkey::new_unique();
+        let now = 1_700_000_000_000;
+        let btc = mock_oracle::Triplet {
+            param1: 65_000_000_000, param2: 65_010_000_000, param3: 64_990_000_000,
+            ts1: now - 1_000, ts2: now - 2_000, ts3: now,
+        };
+        let state = mock_oracle::OracleState {
+            update_authority: authority,
+            btc,
+            eth: mock_oracle::Triplet::default(),
+            sol: mock_oracle::Triplet::default(),
+            decimals: 6,
+            bump: 254,
+        };
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let ai = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &ORACLE_PROGRAM_ID, false, 0);
+        let mut amm = open_amm();
+        amm.oracle_kind = ORACLE_KIND_TRIPLET;
+        amm.oracle_state = key;
+        amm.oracle_authority = authority;
+
+        let sample = load_oracle(&ai, &amm, ASSET_BTC).unwrap();
+        let r = check_oracle_sample(&sample, amm.oracle_checks(), now).unwrap();
+        assert_eq!((r.price_e6, r.ts_ms), (65_000_000_000, now - 1_000)); // the median source
+
+        amm.oracle_authority = Pubkey::new_unique();
+        assert_eq!(load_oracle(&ai, &amm, ASSET_BTC).unwrap_err(), ReaderError::WrongOracleAuthority.into());
+    }
+
+    // ---------- check_oracle_sample ----------
+
+    const NOW_MS: i64 = 1_700_000_000_000;
+    const CHECKS: OracleChecks = OracleChecks { max_spread_bps: 100, max_age_ms: 90_000 };
+
+    fn triplet(sources: [(i64, i64); 3]) -> OracleSample {
+        OracleSample { sources, n: 3, conf_e6: 0 }
+    }
+
+    #[test]
+    fn three_fresh_sources_give_the_median() {
+        let r = check_oracle_sample(
+            &triplet([(100_200_000, NOW_MS - 3_000), (100_000_000, NOW_MS - 1_000), (100_100_000, NOW_MS - 2_000)]),
+            CHECKS,
+            NOW_MS,
+        ).unwrap();
+        assert_eq!((r.price_e6, r.ts_ms, r.conf_e6), (100_100_000, NOW_MS - 2_000, 200_000));
+    }
+
+    #[test]
+    fn two_fresh_sources_give_the_mean_at_the_older_timestamp() {
+        let r = check_oracle_sample(
+            &triplet([(100_000_000, NOW_MS - 1_000), (100_100_001, NOW_MS - 5_000), (1, NOW_MS - 90_001)]),
+            CHECKS,
+            NOW_MS,
+        ).unwrap();
+        assert_eq!((r.price_e6, r.ts_ms), (100_050_000, NOW_MS - 5_000));
+
+        // The mean of two prices near i64::MAX must not overflow
+        let top = i64::MAX - 1;
+        let r = check_oracle_sample(&triplet([(top, NOW_MS), (top - 2, NOW_MS), (0, NOW_MS)]), CHECKS, NOW_MS).unwrap();
+        assert_eq!(r.price_e6, top - 1);
+    }
+
+    #[test]
+    fn quorum_needs_two_fresh_positive_sources() {
+        let stale = NOW_MS - CHECKS.max_age_ms - 1;
+        let future = NOW_MS + ORACLE_MAX_FUTURE_MS + 1;
+        for sources in [
+            [(100_000_000, NOW_MS), (100_000_000, stale), (100_000_000, stale)],
+            [(100_000_000, NOW_MS), (100_000_000, future), (100_000_000, stale)],
+            [(100_000_000, NOW_MS), (0, NOW_MS), (-5, NOW_MS)],
+        ] {
+            assert_eq!(check_oracle_sample(&triplet(sources), CHECKS, NOW_MS).unwrap_err(), ReaderError::OracleQuorum.into());
+        }
+
+        // Exactly max_age old and up to the tolerated clock skew ahead still count
+        let edge = [(100_000_000, NOW_MS - CHECKS.max_age_ms), (100_000_000, NOW_MS + ORACLE_MAX_FUTURE_MS), (0, 0)];
+        assert!(check_oracle_sample(&triplet(edge), CHECKS, NOW_MS).is_ok());
+    }
+
+    #[test]
+    fn spread_between_fresh_sources_is_bounded() {
+        // 1% of 100.0 is the limit; the stale outlier does not count toward the spread
+        let at_limit = [(100_000_000, NOW_MS), (101_000_000, NOW_MS), (200_000_000, NOW_MS - 100_000)];
+        assert!(check_oracle_sample(&triplet(at_limit), CHECKS, NOW_MS).is_ok());
+
+        let too_wide = [(100_000_000, NOW_MS), (101_100_000, NOW_MS), (100_500_000, NOW_MS)];
+        assert_eq!(check_oracle_sample(&triplet(too_wide), CHECKS, NOW_MS).unwrap_err(), ReaderError::OracleSpreadTooWide.into());
+    }
+
+    #[test]
+    fn single_source_feeds_check_age_and_confidence() {
+        let ok = OracleSample::single(100_000_000, 500_000, NOW_MS - 1_000);
+        let r = check_oracle_sample(&ok, CHECKS, NOW_MS).unwrap();
+        assert_eq!((r.price_e6, r.conf_e6, r.ts_ms), (100_000_000, 500_000, NOW_MS - 1_000));
+
+        let stale = OracleSample::single(100_000_000, 0, NOW_MS - CHECKS.max_age_ms - 1);
+        assert_eq!(check_oracle_sample(&stale, CHECKS, NOW_MS).unwrap_err(), ReaderError::StaleOracle.into());
+        let zero = OracleSample::single(0, 0, NOW_MS);
+        assert_eq!(check_oracle_sample(&zero, CHECKS, NOW_MS).unwrap_err(), ReaderError::StaleOracle.into());
+
+        // 2x confidence is the spread
+        let wide = OracleSample::single(100_000_000, 505_000, NOW_MS);
+        assert_eq!(check_oracle_sample(&wide, CHECKS, NOW_MS).unwrap_err(), ReaderError::OracleSpreadTooWide.into());
+    }
+
+    // ---------- limit order planning ----------
+
+    /// Open market at even odds: zeroed accounts with only what plan_limit_fill reads set.
+    fn open_amm() -> Amm {
+        let mut amm = Amm::deserialize(&mut &vec![0u8; Amm::SPACE][..]).unwrap();
+        amm.b = 500_000_000;
+        amm.status = MarketStatus::Open as u8;
+        amm
+    }
+
+    fn empty_position(owner: Pubkey) -> Position {
+        let mut pos = Position::deserialize(&mut &vec![0u8; Position::SPACE][..]).unwrap();
+        pos.owner = owner;
+        pos
+    }
+
+    fn buy_yes_order(nonce: u64, group_id: u64) -> LimitOrder {
+        LimitOrder {
+            market: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            action: 1,
+            side: 1,
+            shares_e6: 10_000_000,
+            limit_price_e6: 900_000,
+            max_cost_e6: 0,
+            min_proceeds_e6: 0,
+            expiry_ts: 2_000,
+            nonce,
+            keeper_fee_bps: 0,
+            min_fill_bps: 0,
+            trigger_kind: TRIGGER_NONE,
+            trigger_price_e6: 0,
+            group_id,
+            reduce_only: false,
+            slice_count: 0,
+            slice_interval_secs: 0,
+        }
+    }
+
+    #[test]
+    fn grouped_order_cancelled_by_nonce_no_longer_fills() {
+        let amm = open_amm();
+        let order = buy_yes_order(7, 99);
+        let pos = empty_position(order.user);
+        let group = OrderFill::default();
+
+        let mut own = OrderFill::default();
+        assert!(plan_limit_fill(&amm, &pos, &group, &own, &order, 1_000).unwrap() > 0);
+
+        // cancel_order_nonce(7) only touches the order's own state, not the group's
+        own.open(Pubkey::new_unique(), order.nonce, i64::MAX, order.user, 255);
+        own.cancelled = true;
+        assert_eq!(plan_limit_fill(&amm, &pos, &group, &own, &order, 1_000).unwrap_err(), ReaderError::NonceAlreadyUsed.into());
+
+        // Its OCO sibling still fills through the group
+        let sibling = buy_yes_order(8, 99);
+        assert!(plan_limit_fill(&amm, &pos, &group, &OrderFill::default(), &sibling, 1_000).unwrap() > 0);
+    }
+
+    #[test]
+    fn twap_slice_closed_past_its_size_still_waits_for_the_interval() {
+        let amm = open_amm();
+        let mut order = buy_yes_order(7, 0);
+        order.slice_count = 4;
+        order.slice_interval_secs = 60;
+        let pos = empty_position(order.user);
+        let slice = order.slice_size_e6();
+
+        // A partly filled slice keeps the rest of its size and doesn't wait
+        let mut fill = OrderFill::default();
+        fill.record(&order, 1_000_000, 500_000, 1_000);
+        let shares = plan_limit_fill(&amm, &pos, &fill, &fill, &order, 1_001).unwrap();
+        assert!(shares > 0 && shares <= slice - 1_000_000);
+
+        // The buy bisection lands a few units past the slice: it still closes the slice
+        fill.record(&order, slice - 1_000_000 + 3, 1_000_000, 1_010);
+        assert_eq!((fill.slices_done, fill.slice_filled_e6), (1, 0));
+        assert_eq!(plan_limit_fill(&amm, &pos, &fill, &fill, &order, 1_069).unwrap_err(), ReaderError::TwapSliceNotDue.into());
+        let shares = plan_limit_fill(&amm, &pos, &fill, &fill, &order, 1_070).unwrap();
+        assert!(shares > 0 && shares <= slice);
+    }
+
+    // ---------- void ----------
+
+    #[test]
+    fn void_refunds_the_vault_pro_rata_per_share() {
+        // 100 YES + 40 NO outstanding, 130 in the vault: every share gets 130/140, not a flat 0.5
+        let mut amm = open_amm();
+        amm.q_yes = 100_000_000;
+        amm.q_no = 40_000_000;
+        amm.vault_e6 = 130_000_000;
+        assert_eq!(apply_void(&mut amm), 928_571);
+        assert_eq!((amm.winner, amm.w_total_e6), (WINNER_VOID, 140_000_000));
+        assert!(amm.liability_e6 <= amm.vault_e6 && amm.vault_e6 - amm.liability_e6 < 1_000);
+
+        // A vault holding more than W still refunds at most 1.0 per share
+        amm.vault_e6 = 200_000_000;
+        assert_eq!(apply_void(&mut amm), 1_000_000);
+
+        amm.q_yes = 0;
+        amm.q_no = 0;
+        assert_eq!(apply_void(&mut amm), 0);
+        assert_eq!(amm.liability_e6, 0);
+    }
+
+    // ---------- limit order signing envelope ----------
+
+    #[test]
+    fn limit_order_message_body_is_printable_hex_of_the_order() {
+        let order = LimitOrder {
+            market: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            action: 1,
+            side: 2,
+            shares_e6: 5_000_000,
+            limit_price_e6: 650_000,
+            max_cost_e6: i64::MAX,
+            min_proceeds_e6: 0,
+            expiry_ts: 1_700_000_000,
+            nonce: 42,
+            keeper_fee_bps: 10,
+            min_fill_bps: 5_000,
+            trigger_kind: TRIGGER_NONE,
+            trigger_price_e6: 0,
+            group_id: 0,
+            reduce_only: false,
+            slice_count: 0,
+            slice_interval_secs: 0,
+        };
+        let msg = limit_order_message(&order).unwrap();
+
+        // domain(16) | header version | app domain(32) | format | signer count | signer(32) | len(2) | body
+        assert_eq!(&msg[..16], OFFCHAIN_SIGNING_DOMAIN);
+        assert_eq!(&msg[17..49], crate::ID.as_ref());
+        assert_eq!((msg[49], msg[50]), (OFFCHAIN_FORMAT_ASCII, 1));
+        assert_eq!(&msg[51..83], order.user.as_ref());
+        let body = &msg[85..];
+        assert_eq!(u16::from_le_bytes([msg[83], msg[84]]) as usize, body.len());
+        assert!(body.iter().all(|b| (0x20..=0x7e).contains(b)));
+
+        let prefix = format!("limit_order v{} ", LIMIT_ORDER_VERSION);
+        let hex = std::str::from_utf8(body).unwrap().strip_prefix(prefix.as_str()).unwrap();
+        let decoded: Vec<u8> = (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(decoded, order.try_to_vec().unwrap());
+    }
+
+    // ---------- ed25519_entries ----------
+
+    /// Ed25519 verify data in the layout the native program and the clients use: count, padding,
+    /// one offsets record per entry, then each entry's pubkey, signature and message.
+    fn ed25519_data(entries: &[([u8; 32], [u8; 64], &[u8])]) -> Vec<u8> {
+        let mut data = vec![entries.len() as u8, 0];
+        let mut body = Vec::new();
+        let base = 2 + entries.len() * 14;
+        for (pubkey, signature, message) in entries {
+            let pk_off = (base + body.len()) as u16;
+            body.extend_from_slice(pubkey);
+            let sig_off = (base + body.len()) as u16;
+            body.extend_from_slice(signature);
+            let msg_off = (base + body.len()) as u16;
+            body.extend_from_slice(message);
+            for v in [sig_off, u16::MAX, pk_off, u16::MAX, msg_off, message.len() as u16, u16::MAX] {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&body);
+        data
+    }
+
+    fn set_field(data: &mut [u8], entry: usize, k: usize, v: u16) {
+        let at = 2 + entry * 14 + 2 * k;
+        data[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn entries_err(data: &[u8]) -> anchor_lang::error::Error {
+        ed25519_entries(data).err().expect("malformed ed25519 data must be rejected")
+    }
+
+    #[test]
+    fn ed25519_entries_parses_every_record() {
+        let data = ed25519_data(&[([1; 32], [2; 64], b"first"), ([3; 32], [4; 64], b"second order")]);
+        let entries = ed25519_entries(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].pubkey, entries[0].signature, entries[0].message), (&[1u8; 32][..], &[2u8; 64][..], &b"first"[..]));
+        assert_eq!((entries[1].pubkey, entries[1].signature, entries[1].message), (&[3u8; 32][..], &[4u8; 64][..], &b"second order"[..]));
+    }
+
+    #[test]
+    fn ed25519_entries_rejects_empty_and_oversized_counts() {
+        assert_eq!(entries_err(&[]), ReaderError::InvalidSignature.into());
+
+        let mut zero = ed25519_data(&[([1; 32], [2; 64], b"msg")]);
+        zero[0] = 0;
+        assert_eq!(entries_err(&zero), ReaderError::InvalidSignature.into());
+
+        // More records claimed than the data holds
+        let mut too_many = ed25519_data(&[([1; 32], [2; 64], b"msg")]);
+        too_many[0] = 255;
+        assert_eq!(entries_err(&too_many), ReaderError::InvalidSignature.into());
+    }
+
+    #[test]
+    fn ed25519_entries_rejects_truncated_records() {
+        let data = ed25519_data(&[([1; 32], [2; 64], b"msg")]);
+        assert_eq!(entries_err(&data[..2 + 13]), ReaderError::InvalidSignature.into());
+        // Record intact but the message runs past the end
+        assert_eq!(entries_err(&data[..data.len() - 1]), ReaderError::InvalidSignature.into());
+    }
+
+    #[test]
+    fn ed25519_entries_rejects_out_of_range_offsets() {
+        let data = ed25519_data(&[([1; 32], [2; 64], b"msg")]);
+        let len = data.len() as u16;
+        for (k, v) in [(0, len - 63), (2, len - 31), (4, len), (5, 4), (0, u16::MAX - 10)] {
+            let mut bad = data.clone();
+            set_field(&mut bad, 0, k, v);
+            assert_eq!(entries_err(&bad), ReaderError::InvalidSignature.into(), "field {} = {}", k, v);
+        }
+    }
+
+    #[test]
+    fn ed25519_entries_rejects_data_from_other_instructions() {
+        let data = ed25519_data(&[([1; 32], [2; 64], b"msg")]);
+        for k in [1, 3, 6] {
+            for ix in [0u16, 1, u16::MAX - 1] {
+                let mut bad = data.clone();
+                set_field(&mut bad, 0, k, ix);
+                assert_eq!(entries_err(&bad), ReaderError::InvalidSignature.into(), "field {} = {}", k, ix);
+            }
+        }
+    }
+}
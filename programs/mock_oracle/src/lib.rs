@@ -0,0 +1,125 @@
+use anchor_lang::prelude::*;
+
+declare_id!("Azo5s4RrEgwm1qiHnYseYQZXVnTYTmWZxAdM61UTtLG7");
+
+// Mock of the triplet price oracle read by cpi_oracle.
+//
+// `OracleState` has exactly the byte layout cpi_oracle parses (see `OracleStateMirror` there):
+//  [8]  anchor discriminator
+//  [32] update_authority pubkey
+//  [48] btc:  param1, param2, param3 (i64) | ts1, ts2, ts3 (i64, unix ms)
+//  [48] eth:  same layout
+//  [48] sol:  same layout
+//  [1]  decimals (u8)
+//  [1]  bump     (u8)
+//
+// Build cpi_oracle with `--features mock-oracle` so its ORACLE_PROGRAM_ID is this program's id.
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, InitSpace)]
+pub struct Triplet {
+    pub param1: i64, pub param2: i64, pub param3: i64,
+    pub ts1: i64,    pub ts2: i64,    pub ts3: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct OracleState {
+    pub update_authority: Pubkey,
+    pub btc: Triplet,
+    pub eth: Triplet,
+    pub sol: Triplet,
+    pub decimals: u8,
+    pub bump: u8,
+}
+impl OracleState {
+    pub const SEED: &'static [u8] = b"oracle_state";
+
+    fn triplet_mut(&mut self, asset: u8) -> Result<&mut Triplet> {
+        match asset {
+            1 => Ok(&mut self.btc),
+            2 => Ok(&mut self.eth),
+            3 => Ok(&mut self.sol),
+            _ => err!(MockOracleError::BadAsset),
+        }
+    }
+}
+
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    /// One feed per authority: [b"oracle_state", authority]. Prices are raw, scaled by 10^decimals.
+    pub fn initialize(ctx: Context<Initialize>, decimals: u8) -> Result<()> {
+        require!(decimals <= 18, MockOracleError::BadParam);
+
+        let state = &mut ctx.accounts.oracle_state;
+        state.update_authority = ctx.accounts.authority.key();
+        state.btc = Triplet::default();
+        state.eth = Triplet::default();
+        state.sol = Triplet::default();
+        state.decimals = decimals;
+        state.bump = ctx.bumps.oracle_state;
+
+        msg!("mock oracle {} (dec={}) authority={}", state.key(), decimals, state.update_authority);
+        Ok(())
+    }
+
+    /// Write all three sources of one asset (1=BTC, 2=ETH, 3=SOL), each with its own timestamp.
+    pub fn set_triplet(ctx: Context<Update>, asset: u8, prices: [i64; 3], timestamps_ms: [i64; 3]) -> Result<()> {
+        let t = ctx.accounts.oracle_state.triplet_mut(asset)?;
+        *t = Triplet {
+            param1: prices[0], param2: prices[1], param3: prices[2],
+            ts1: timestamps_ms[0], ts2: timestamps_ms[1], ts3: timestamps_ms[2],
+        };
+        Ok(())
+    }
+
+    /// Convenience: all three sources agree on `price`, stamped with the cluster clock.
+    pub fn set_price(ctx: Context<Update>, asset: u8, price: i64) -> Result<()> {
+        let now_ms = Clock::get()?.unix_timestamp.saturating_mul(1000);
+        let t = ctx.accounts.oracle_state.triplet_mut(asset)?;
+        *t = Triplet {
+            param1: price, param2: price, param3: price,
+            ts1: now_ms, ts2: now_ms, ts3: now_ms,
+        };
+        Ok(())
+    }
+
+    /// Hand the feed to another writer (exercises update_authority pinning).
+    pub fn set_update_authority(ctx: Context<Update>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.oracle_state.update_authority = new_authority;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OracleState::INIT_SPACE,
+        seeds = [OracleState::SEED, authority.key().as_ref()],
+        bump
+    )]
+    pub oracle_state: Account<'info, OracleState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Update<'info> {
+    #[account(mut, has_one = update_authority @ MockOracleError::NotAuthority)]
+    pub oracle_state: Account<'info, OracleState>,
+
+    pub update_authority: Signer<'info>,
+}
+
+#[error_code]
+pub enum MockOracleError {
+    #[msg("asset must be 1=BTC, 2=ETH or 3=SOL")] BadAsset,
+    #[msg("bad parameter")]                       BadParam,
+    #[msg("signer is not the update_authority")]  NotAuthority,
+}
@@ -105,6 +105,7 @@ let showPriceToBeatLine = false; // Toggle for price-to-beat horizontal line on
 let connection = null;
 let ammPda = null;
 let vaultPda = null;
+let insurancePda = null;
 
 let currentLockoutStartSlot = 0;
 let latestObservedSlot = 0;
@@ -335,6 +336,12 @@ window.addEventListener('load', async () => {
     vaultPda = vault;
     console.log('[INIT] Calculated Vault PDA:', vaultPda.toString());
 
+    const [insurance] = await solanaWeb3.PublicKey.findProgramAddressSync(
+        [stringToUint8Array('insurance'), ammPda.toBytes()],
+        new solanaWeb3.PublicKey(CONFIG.PROGRAM_ID)
+    );
+    insurancePda = insurance;
+
     updateSlotHeaderDisplay();
     startSlotPolling();
 
@@ -3578,6 +3585,25 @@ function showToast(type, title, message) {
 
 // ============= LIMIT ORDER FUNCTIONS =============
 
+// Signing envelope around the serialized order - must match limit_order_message in the program:
+// Solana off-chain message (header v0) with the program as application domain, 1 signer,
+// ASCII body "limit_order v<version> " || lowercase hex(borsh(order))
+const LIMIT_ORDER_VERSION = 5;
+function limitOrderMessage(orderBytes, programId, userPubkey) {
+    const enc = new TextEncoder();
+    const hex = Array.from(orderBytes, b => b.toString(16).padStart(2, '0')).join('');
+    const body = enc.encode(`limit_order v${LIMIT_ORDER_VERSION} ${hex}`);
+    return new Uint8Array([
+        0xff, ...enc.encode('solana offchain'),   // signing domain
+        0,                                        // header version
+        ...programId.toBytes(),                   // application domain
+        0, 1,                                     // format: restricted ASCII, 1 signer
+        ...userPubkey.toBytes(),
+        body.length & 0xff, body.length >> 8,     // body length (u16 LE)
+        ...body,
+    ]);
+}
+
 // Serialize order to bytes (Borsh format) - matches orderbook.html implementation
 function serializeOrder(order, marketPubkey, userPubkey) {
     const buffers = [];
@@ -3621,6 +3647,12 @@ function serializeOrder(order, marketPubkey, userPubkey) {
     buffers.push(writeU64LE(order.nonce));
     buffers.push(writeU16LE(order.keeper_fee_bps));
     buffers.push(writeU16LE(order.min_fill_bps));
+    buffers.push(writeU8(order.trigger_kind || 0));
+    buffers.push(writeI64LE(order.trigger_price_e6 || 0));
+    buffers.push(writeU64LE(order.group_id || 0));
+    buffers.push(writeU8(order.reduce_only ? 1 : 0));
+    buffers.push(writeU16LE(order.slice_count || 0));
+    buffers.push(writeI64LE(order.slice_interval_secs || 0));
 
     // Concatenate all buffers
     const totalLength = buffers.reduce((sum, buf) => sum + buf.length, 0);
@@ -3689,8 +3721,8 @@ async function submitLimitOrder(tradeData) {
             min_fill_bps: minFillBps
         };
 
-        // Serialize order (Borsh encoding)
-        const messageBytes = serializeOrder(order, marketPda, wallet.publicKey);
+        // Serialize order (Borsh encoding) inside the signing envelope
+        const messageBytes = limitOrderMessage(serializeOrder(order, marketPda, wallet.publicKey), programId, wallet.publicKey);
 
         // Sign with Ed25519 using session wallet
         const signature = nacl.sign.detached(messageBytes, wallet.secretKey);
@@ -3984,6 +4016,7 @@ async function executeTradeInternal(tradeData) {
             { pubkey: feeDest, isSigner: false, isWritable: true },
             { pubkey: vaultPda, isSigner: false, isWritable: true },
             { pubkey: new solanaWeb3.PublicKey(CONFIG.ORACLE_STATE), isSigner: false, isWritable: false },  // oracle_state for timestamp
+            { pubkey: insurancePda, isSigner: false, isWritable: true },  // insurance fund PDA
             { pubkey: solanaWeb3.SystemProgram.programId, isSigner: false, isWritable: false },
             { pubkey: solanaWeb3.SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
         ];
@@ -4222,6 +4255,7 @@ async function executeClosePosition() {
             { pubkey: feeDest, isSigner: false, isWritable: true },
             { pubkey: vaultPda, isSigner: false, isWritable: true },
             { pubkey: oraclePk, isSigner: false, isWritable: false },  // Oracle for lockout check
+            { pubkey: insurancePda, isSigner: false, isWritable: true },
             { pubkey: solanaWeb3.SystemProgram.programId, isSigner: false, isWritable: false },
             { pubkey: solanaWeb3.SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
         ];
@@ -4716,6 +4750,10 @@ async function initAmm(bScaled = 500_000_000, feeBps = 25) {
 
         const discriminator = await createDiscriminator('init_amm');
         const feeDest = wallet.publicKey; // Use wallet pubkey as fee dest during initialization
+        const [roundCounterPda] = await solanaWeb3.PublicKey.findProgramAddress(
+            [stringToUint8Array('round_counter'), ammPda.toBytes()],
+            new solanaWeb3.PublicKey(CONFIG.PROGRAM_ID)
+        );
 
         // Create b_scaled buffer (8 bytes, little endian)
         const bBuf = new Uint8Array(8);
@@ -4735,6 +4773,7 @@ async function initAmm(bScaled = 500_000_000, feeBps = 25) {
             { pubkey: feeDest, isSigner: false, isWritable: true },
             { pubkey: vaultPda, isSigner: false, isWritable: true },
             { pubkey: solanaWeb3.SystemProgram.programId, isSigner: false, isWritable: false },
+            { pubkey: roundCounterPda, isSigner: false, isWritable: true },
         ];
 
         const instruction = new solanaWeb3.TransactionInstruction({
@@ -4846,63 +4885,6 @@ async function initPosition() {
     }
 }
 
-async function wipePosition() {
-    if (!wallet) {
-        addLog('ERROR: No wallet connected', 'error');
-        return false;
-    }
-
-    try {
-        const [posPda] = await solanaWeb3.PublicKey.findProgramAddressSync(
-            [stringToUint8Array('pos'), ammPda.toBytes(), wallet.publicKey.toBytes()],
-            new solanaWeb3.PublicKey(CONFIG.PROGRAM_ID)
-        );
-
-        // Check if position exists
-        const posInfo = await connection.getAccountInfo(posPda);
-        if (!posInfo) {
-            addLog('Position does not exist, nothing to wipe', 'info');
-            return true;
-        }
-
-        addLog('Wiping position shares...', 'info');
-
-        const discriminator = await createDiscriminator('wipe_position');
-
-        const keys = [
-            { pubkey: ammPda, isSigner: false, isWritable: true },
-            { pubkey: wallet.publicKey, isSigner: true, isWritable: false }, // admin
-            { pubkey: wallet.publicKey, isSigner: false, isWritable: false }, // owner (can be same as admin)
-            { pubkey: posPda, isSigner: false, isWritable: true },
-        ];
-
-        const instruction = new solanaWeb3.TransactionInstruction({
-            programId: new solanaWeb3.PublicKey(CONFIG.PROGRAM_ID),
-            keys,
-            data: discriminator
-        });
-
-        const budgetIxs = createComputeBudgetInstructions();
-        const transaction = new solanaWeb3.Transaction().add(...budgetIxs, instruction);
-        transaction.feePayer = wallet.publicKey;
-        transaction.recentBlockhash = (await connection.getLatestBlockhash()).blockhash;
-        transaction.sign(wallet);
-
-        addLog('Submitting wipe position transaction...', 'tx');
-        const signature = await connection.sendRawTransaction(transaction.serialize());
-        addLog('TX: ' + signature, 'tx');
-
-        await connection.confirmTransaction(signature, 'confirmed');
-        addLog('Position wiped successfully!', 'success');
-        return true;
-
-    } catch (err) {
-        addLog('Wipe Position FAILED: ' + err.message, 'error');
-        console.error('Wipe position error:', err);
-        return false;
-    }
-}
-
 async function closeAmm() {
     if (!wallet) {
         addLog('ERROR: No wallet connected', 'error');